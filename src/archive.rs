@@ -0,0 +1,91 @@
+use std::fs;
+use std::path::Path;
+use crate::error::AssembleError;
+use crate::object::ObjectFile;
+use crate::utility::json::JsonValue;
+
+/// Written into every archive so object files and other JSON files aren't mistaken for one.
+const FORMAT: &str = "sriscasm-archive";
+const VERSION: i64 = 1;
+
+/// An object file inside an archive, named after the file it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveMember {
+    pub name: String,
+    pub object: ObjectFile,
+}
+
+/// A library of object files. Linking only takes the members whose global symbols are used.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Archive {
+    pub members: Vec<ArchiveMember>,
+}
+
+impl Archive {
+    /// Adds the member, replacing one of the same name.
+    pub fn add(&mut self, name: String, object: ObjectFile) {
+        match self.members.iter_mut().find(|member| member.name == name) {
+            Some(member) => member.object = object,
+            None => self.members.push(ArchiveMember{name, object}),
+        }
+    }
+
+    /// The members the modules need: those declaring `.global` a symbol that is `.extern` in a module or a member
+    /// taken before. Members are taken until nothing is missing anymore or no archive has what is missing, so the
+    /// order of the archives and their members doesn't matter.
+    pub fn select(modules: &[ObjectFile], archives: &[Archive]) -> Vec<ObjectFile> {
+        let mut selected: Vec<ObjectFile> = Vec::new();
+        let mut taken: Vec<(usize, usize)> = Vec::new();
+        loop {
+            let linked = || modules.iter().chain(selected.iter());
+            let missing: Vec<&String> = linked().flat_map(|module| module.externs.iter())
+                .filter(|name| !linked().any(|module| Self::defines(module, name)))
+                .collect();
+            let found = archives.iter().enumerate().flat_map(|(archive, library)| library.members.iter().enumerate().map(move |(member, content)| ((archive, member), content)))
+                .find(|(position, member)| !taken.contains(position) && missing.iter().any(|name| Self::defines(&member.object, name)));
+            let Some((position, member)) = found else { return selected; };
+            taken.push(position);
+            selected.push(member.object.clone());
+        }
+    }
+
+    fn defines(module: &ObjectFile, name: &str) -> bool {
+        module.symbols.iter().any(|symbol| symbol.global && symbol.name == name)
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let members: Vec<JsonValue> = self.members.iter().map(|member| JsonValue::object(vec![
+            ("name", member.name.clone().into()),
+            ("object", member.object.to_json()),
+        ])).collect();
+        JsonValue::object(vec![
+            ("format", FORMAT.into()),
+            ("version", VERSION.into()),
+            ("members", members.into()),
+        ])
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Archive, String> {
+        if json.get("format").and_then(JsonValue::as_str) != Some(FORMAT) {
+            return Err("it isn't an archive written by sriscasm".to_string());
+        }
+        if json.get("version").and_then(JsonValue::as_i64) != Some(VERSION) {
+            return Err(format!("only version {} is supported", VERSION));
+        }
+        let mut archive = Archive::default();
+        for member in json.get("members").and_then(JsonValue::as_array).ok_or("members is missing")? {
+            let name = member.get("name").and_then(JsonValue::as_str).ok_or("a member has no name")?;
+            let object = member.get("object").ok_or_else(|| format!("member {} has no object", name))
+                .and_then(|object| ObjectFile::from_json(object).map_err(|reason| format!("member {}: {}", name, reason)))?;
+            archive.add(name.to_string(), object);
+        }
+        Ok(archive)
+    }
+
+    /// Reads an archive written by `sriscasm ar`.
+    pub fn read(path: &Path) -> Result<Archive, AssembleError> {
+        let content = fs::read_to_string(path).map_err(|reason| AssembleError::Io { path: path.display().to_string(), reason: reason.to_string() })?;
+        JsonValue::parse(&content).and_then(|json| Self::from_json(&json))
+            .map_err(|reason| AssembleError::BadObject { path: path.display().to_string(), reason })
+    }
+}
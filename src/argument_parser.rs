@@ -1,7 +1,14 @@
 use std::cmp::PartialEq;
-use colored::Colorize;
-use crate::instruction;
+use crate::diagnostics::Diagnostics;
+use crate::error::{AssembleError, AssembleWarning};
+use crate::instruction::{Instruction, Operand};
+use crate::isa::{Isa, OperandKind};
+use crate::lexer::{Lexeme, LexemeKind, Lexer};
+use crate::section::{Endianness, Fixup, FixupKind, Section};
+use crate::source::{SourceLine, Span, Token};
+use crate::symbol_table::SymbolTable;
 use crate::utility::replacement::Replacement;
+use crate::utility::expression::Expression;
 use crate::utility::math;
 
 const CHARACTERS: [&str; 36] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z"];
@@ -18,12 +25,25 @@ enum DataType{
 }
 
 impl DataType {
-    fn from_string(d_type: &str) -> DataType {
-        match d_type {
-            "8b" | "str" | "char" => DataType::Int8,
-            "16b" => DataType::Int16,
-            "32b" => DataType::Int32,
-            _ => { let error = format!("Unknown data type: {}", d_type).red(); panic!("{}", error); }
+    /// The type and, for 16le, 32be and the like, the byte order that overrides the one of the file.
+    fn from_token(d_type: &Token) -> Result<(DataType, Option<Endianness>), AssembleError> {
+        match d_type.text.as_str() {
+            "8b" | "str" | "char" => Ok((DataType::Int8, None)),
+            "16b" => Ok((DataType::Int16, None)),
+            "16le" => Ok((DataType::Int16, Some(Endianness::Little))),
+            "16be" => Ok((DataType::Int16, Some(Endianness::Big))),
+            "32b" => Ok((DataType::Int32, None)),
+            "32le" => Ok((DataType::Int32, Some(Endianness::Little))),
+            "32be" => Ok((DataType::Int32, Some(Endianness::Big))),
+            _ => Err(AssembleError::BadDataType { data_type: d_type.text.clone(), span: d_type.span }),
+        }
+    }
+
+    fn width(&self) -> usize {
+        match self {
+            DataType::Int8 => 1,
+            DataType::Int16 => 2,
+            DataType::Int32 => 4,
         }
     }
 }
@@ -46,50 +66,106 @@ impl PartialEq for DataType {
 }
 
 impl ArgumentParser {
-    pub fn argument_to_8_bit_binary(argument: &str, line: i32) -> u8 {
-        if let Some(arg_decimal_interpratation) = argument.parse::<i64>().ok() {
+    pub fn argument_to_8_bit_binary(argument: &str, span: Span) -> Result<u8, AssembleError> {
+        if let Ok(arg_decimal_interpratation) = argument.parse::<i64>() {
             // The number was a decimal number. Look if it is within range (0...127)
-            if arg_decimal_interpratation < 0 {
-                let error = format!("Argument {} in line {} should be within range 0...127 but is negative. Please define it in the data section instead.", argument, line).red().to_string();
-                panic!("{}", error);
-            }
-            if arg_decimal_interpratation > 127 {
-                let error = format!("Argument {} in line {} should be within range 127 but is too high. Please define it in the data section instead.", argument, line).red().to_string();
-                panic!("{}", error);
-            }
-            // Fits constraints
-            return arg_decimal_interpratation as u8;
+            return Self::literal_in_range(argument, arg_decimal_interpratation, 0, 127, span);
         }
-        println!("Argument (not decimal) is: {}", argument);
         if argument.len() < 2 {
-            let error = format!("Argument {} in line {} is not decimal and needs type and value.", argument, line).red().to_string();
-            panic!("{}", error);
+            return Err(Self::unresolved_argument(argument, "is not decimal and needs type and value", span));
         }
         // It's not plain ol' decimal, so get the encoding
         let splitted_argument = argument.split_at(1);
         let encoding = splitted_argument.0;
         let number = splitted_argument.1;
-        match encoding.to_ascii_lowercase().as_str() {
-            "x" => { Self::convert(number, 16, 10).parse::<u8>().unwrap() },
-            "o" => { Self::convert(number, 8, 10).parse::<u8>().unwrap() },
-            "b" => { Self::convert(number, 2, 10).parse::<u8>().unwrap() },
-            "r" => { number.parse::<u8>().unwrap() | 0b1000_0000 },
-            "\'" => { number.chars().nth(0).unwrap() as u8 },
+        let radix = match encoding.to_ascii_lowercase().as_str() {
+            "x" => 16,
+            "o" => 8,
+            "b" => 2,
+            "r" => {
+                let Ok(register) = number.parse::<i64>() else {
+                    return Err(Self::unresolved_argument(argument, "isn't a valid register", span));
+                };
+                return Ok(Self::literal_in_range(argument, register, 0, 127, span)? | 0b1000_0000);
+            },
+            "\'" => {
+                return match number.chars().next() {
+                    Some(character) if character.is_ascii() => Ok(character as u8),
+                    _ => Err(Self::unresolved_argument(argument, "isn't a single ASCII character", span)),
+                };
+            },
             _ => {
-
-                let error = format!("Number system {} in argument {} (line {}) isn't available.", encoding, argument, line).red().to_string();
-                panic!("{}", error);
+                return Err(Self::unresolved_argument(argument, &format!("uses number system {} which isn't available", encoding), span));
             }
+        };
+
+        match Self::convert(number, radix, 10).and_then(|converted| converted.parse::<i64>().ok()) {
+            Some(value) => Self::literal_in_range(argument, value, 0, 127, span),
+            None => Err(Self::unresolved_argument(argument, &format!("isn't a valid base {} number", radix), span)),
         }
     }
 
-    pub fn split_sections(code: Vec<String>) -> (/*data: */ Vec<String>, /*text*/Vec<String>) {
-        let mut section_data: Vec<String> = Vec::new();
-        let mut section_text: Vec<String> = Vec::new();
+    /// Resolves constants, labels and bracketed expressions in the token and encodes the result.
+    pub fn resolve_argument(token: &Token, symbols: &SymbolTable) -> Result<u8, AssembleError> {
+        let resolved = Self::resolve_all_math_ops_in_line(token.text.clone(), symbols, token.span)?;
+        Self::argument_to_8_bit_binary(&resolved, token.span)
+    }
+
+    /// Resolves an instruction operand and checks that it is of the kind the instruction expects.
+    pub fn resolve_operand(token: &Token, kind: OperandKind, symbols: &SymbolTable) -> Result<u8, AssembleError> {
+        let value = Self::resolve_argument(token, symbols)?;
+        if kind == OperandKind::Register && value & 0b1000_0000 == 0 {
+            return Err(AssembleError::BadOperand { operand: token.text.clone(), reason: "has to be a register".to_string(), span: token.span });
+        }
+        Ok(value)
+    }
+
+    fn literal_in_range(literal: &str, value: i64, min: i64, max: i64, span: Span) -> Result<u8, AssembleError> {
+        if value < min || value > max {
+            return Err(AssembleError::LiteralOutOfRange { literal: literal.to_string(), min, max, span });
+        }
+        Ok(value as u8)
+    }
+
+    /// Anything that looks like a name was most likely meant to be a symbol that was never declared.
+    fn unresolved_argument(argument: &str, reason: &str, span: Span) -> AssembleError {
+        if Lexer::is_identifier(argument) && !Self::looks_like_literal(argument) {
+            return AssembleError::UndefinedSymbol { name: argument.to_string(), span };
+        }
+        AssembleError::BadOperand { operand: argument.to_string(), reason: reason.to_string(), span }
+    }
+
+    /// The value of a number prefixed with its number system like x1F, o17 or b101.
+    pub fn prefixed_number(argument: &str) -> Option<i64> {
+        if argument.len() < 2 { return None; }
+        let (encoding, number) = argument.split_at(1);
+        let radix = match encoding.to_ascii_lowercase().as_str() {
+            "x" => 16,
+            "o" => 8,
+            "b" => 2,
+            _ => return None,
+        };
+        Self::convert(number, radix, 10)?.parse::<i64>().ok()
+    }
+
+    /// True for registers and prefixed numbers like r1, x1F, o17 or b101.
+    pub fn looks_like_literal(argument: &str) -> bool {
+        argument.len() > 1 && match argument.split_at(1) {
+            ("x" | "X", number) => number.chars().all(|c| c.is_ascii_hexdigit()),
+            ("o" | "O", number) => number.chars().all(|c| ('0'..='7').contains(&c)),
+            ("b" | "B", number) => number.chars().all(|c| c == '0' || c == '1'),
+            ("r" | "R", number) => number.chars().all(|c| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+
+    pub fn split_sections(code: Vec<SourceLine>, diagnostics: &mut Diagnostics) -> (/*data: */ Vec<SourceLine>, /*text*/Vec<SourceLine>) {
+        let mut section_data: Vec<SourceLine> = Vec::new();
+        let mut section_text: Vec<SourceLine> = Vec::new();
         let mut current_section: Option<SectionType> = None;
 
         for line in code {
-            match line.as_str() {
+            match line.text.as_str() {
                 "<text>" => {current_section = Some(SectionType::TEXT); continue;},
                 "<data>" => {current_section = Some(SectionType::DATA); continue;},
                 _ => {},
@@ -98,139 +174,234 @@ impl ArgumentParser {
             match current_section {
                 Some(SectionType::TEXT) => {section_text.push(line);},
                 Some(SectionType::DATA) => {section_data.push(line);},
-                None => {},
+                None if line.text.trim().is_empty() || line.text.trim_start().starts_with('#') => {},
+                None => diagnostics.warning(AssembleWarning::LineOutsideSection { content: line.text, span: line.span }),
             }
         }
 
         (section_data, section_text)
     }
 
-    // The first replacement vector refers to data that has to be changed to fit the offset while the second one does not.
-    pub fn compile_data_section(lines: Vec<String>, replacements_in: &mut Vec<Replacement>) -> (Vec<u8>, Vec<Replacement>) {
-        let mut data: Vec<u8> = Vec::new();
-        let mut replacements: Vec<Replacement> = Vec::new();
-        let mut bytes_count: u32 = 0;
-        for mut line in lines {
-            if line.chars().nth(0) == Some('.') {
-                println!("Line : {} does start with . ", line);
-                line.remove(0);
-                let arguments = line.split_whitespace().collect::<Vec<&str>>();
-                let name = arguments[0];
-                let mut rest = arguments[1..].join(" ");
-                rest = rest.replace('$', &*(bytes_count - 1).to_string());
-                replacements_in.push(Replacement::new(name.to_string(), Self::resolve_all_math_ops_in_line(rest, [replacements.clone(), replacements_in.clone()].concat()), false));
+    // Pass 1 of the data section: data labels are declared with their final address, values are left to fixups.
+    // $ is the address of the last byte emitted before the line. Lines that fail are reported and still declare their name so later lines don't fail because of them.
+    // `.endian little|big` sets the byte order of the 16b and 32b values after it, little endian is the default.
+    // For a relocatable module, where the section ends up is only known once it is linked, so $ in constants counts from data_offset.
+    pub fn layout_data_section(lines: Vec<SourceLine>, symbols: &mut SymbolTable, data_offset: u32, relocatable: bool, diagnostics: &mut Diagnostics) -> Section {
+        let mut section = Section::new();
+        let mut endianness = Endianness::default();
+        for line in lines {
+            if line.text.trim().is_empty() || line.text.trim_start().starts_with('#') { continue; }
+            let tokens = Self::line_to_tokens(&line);
+            let location_counter = data_offset as i64 + section.bytes.len() as i64 - 1;
+            if tokens[0].text == ".endian" {
+                match tokens.get(1).map(|token| token.text.as_str()) {
+                    Some("little") => endianness = Endianness::Little,
+                    Some("big") => endianness = Endianness::Big,
+                    _ => diagnostics.error(AssembleError::BadDeclaration { declaration: line.text.clone(), reason: "Endianness has to be little or big.".to_string(), span: line.span }),
+                }
                 continue;
             }
-            println!("Line : {} doesnt start with . ", line);
-            let parts = Self::line_to_argument_parts(line.as_str());//line.split(" ").collect::<Vec<&str>>();
-            println!("Parts: {:?}, line: {}", parts, line);
-            let name = &parts[0];
-            let data_type = DataType::from_string(&*parts[1]);
-            let data_def = parts[2..].to_vec();
-            let mut bytes = Self::data_to_bytes(data_def.clone(), data_type, bytes_count);
-            let start_position = data.len();
-
-            bytes_count += bytes.len() as u32;
-
-            data.append(&mut bytes);
-
-            let replacement = Replacement::new(name.to_string(), start_position.to_string(), false);
+            if line.text.starts_with('.') {
+                let location = if relocatable { format!("data_offset + {}", section.bytes.len() as i64 - 1) } else { location_counter.to_string() };
+                Self::declare_constant(&line, tokens, Some(location), symbols, diagnostics);
+                continue;
+            }
+            if tokens.len() < 3 {
+                diagnostics.error(AssembleError::BadDeclaration { declaration: line.text.clone(), reason: "Data declaration requires name, type and value.".to_string(), span: line.span });
+                continue;
+            }
+            let start_position = data_offset + section.bytes.len() as u32;
+            if let Err(error) = symbols.declare(Replacement::declared(tokens[0].text.clone(), start_position.to_string(), false, tokens[0].span)) {
+                diagnostics.error(error);
+            }
 
-            replacements.push(replacement);
+            let data_def = tokens[2..].to_vec();
+            let start = section.bytes.len();
+            let laid_out = DataType::from_token(&tokens[1]).and_then(|(data_type, own_endianness)| {
+                Self::layout_data(data_def, data_type, own_endianness.unwrap_or(endianness), location_counter, &mut section)
+            });
+            if let Err(error) = laid_out {
+                diagnostics.error(error);
+            }
+            section.record_line(start, Self::strip_comment(&line).map(|line| line.span).unwrap_or(line.span));
         }
-        (data, replacements)
+        section
     }
 
-    fn data_to_bytes(data: Vec<String>, data_type: DataType, passed_bytes: u32) -> Vec<u8> {
-        let data = data;
-        let mut data_bytes: Vec<u8> = Vec::new();
-        if data_type == DataType::Int8 {
-            let mut data0chars = data[0].chars().collect::<Vec<char>>();
-            if data0chars[0] == '"' {
-                // Decode the chars
-                data0chars.remove(0);
-                data0chars.remove(data0chars.len() - 1);
-                for date in data0chars{
-                    let replacement = Self::argument_to_8_bit_binary(&("\'".to_owned() + date.to_string().as_str() + "\'"), -1);
-                    data_bytes.push(replacement);
-                }
-            }else{
-                // Decode the numbers
-                for date in data{
-                    data_bytes.push(Self::resolve_all_math_ops_in_line(date, vec![Replacement::new("$".to_string(), (passed_bytes - 1).to_string(), false)]).parse().unwrap());
-                }
+    fn layout_data(data: Vec<Token>, data_type: DataType, endianness: Endianness, location_counter: i64, section: &mut Section) -> Result<(), AssembleError> {
+        let mut data0chars = data[0].text.chars().collect::<Vec<char>>();
+        if data0chars[0] == '"' {
+            if data_type != DataType::Int8 {
+                return Err(AssembleError::BadOperand { operand: data[0].text.clone(), reason: "is text, which can only be stored as 8b, str or char".to_string(), span: data[0].span });
+            }
+            // Decode the chars
+            data0chars.remove(0);
+            if data0chars.last() == Some(&'"') { data0chars.pop(); }
+            for date in data0chars{
+                let replacement = Self::argument_to_8_bit_binary(&("\'".to_owned() + date.to_string().as_str() + "\'"), data[0].span)?;
+                section.bytes.push(replacement);
+            }
+        }else{
+            // The numbers may refer to data further down, so they are resolved in pass 2
+            for date in data{
+                section.push_fixup(date, FixupKind::Data { width: data_type.width(), endianness }, Some(location_counter));
             }
         }
 
-        data_bytes
+        Ok(())
+    }
+
+    /// Evaluates a data value and encodes it with the given amount of bytes.
+    /// Values from the smallest signed to the biggest unsigned number fit, negative ones are stored in two's complement.
+    pub fn resolve_data(token: &Token, width: usize, endianness: Endianness, symbols: &SymbolTable) -> Result<Vec<u8>, AssembleError> {
+        let value = Expression::parse(&token.text, token.span)?.evaluate(symbols, &token.text, token.span)?;
+        let bits = width as u32 * 8;
+        let (min, max) = (-(1i64 << (bits - 1)), (1i64 << bits) - 1);
+        if value < min || value > max {
+            return Err(AssembleError::LiteralOutOfRange { literal: token.text.clone(), min, max, span: token.span });
+        }
+        let mut bytes = value.to_le_bytes()[..width].to_vec();
+        if endianness == Endianness::Big { bytes.reverse(); }
+        Ok(bytes)
     }
-    
-   
-
-    // ZKW
-    pub fn get_replacements_from_code(code: Vec<String>) -> Vec<Replacement> {
-        let mut replacements: Vec<Replacement> = Vec::new();
-        let mut passed_bytes: u32 = 0;
-        let mut current_line_number: u16 = 0;
+
+    // Pass 1 of the text section: declares constants and labels and lays out the instructions, leaving their operands to fixups.
+    // Labels get the address of the next instruction, which is known exactly because the instruction itself is laid out here.
+    // Afterwards data_offset (the address where the data section starts) is declared as well.
+    pub fn layout_text_section(code: Vec<SourceLine>, isa: &Isa, symbols: &mut SymbolTable, diagnostics: &mut Diagnostics) -> Section {
+        let mut section = Section::new();
         for line in code.iter() {
-            current_line_number += 1;
-            // Ensure line has at least one char
-            if line.is_empty() { continue; }
-            if line.starts_with('.') {
-                let parts: Vec<String> = Self::line_to_argument_parts(line);//line.split_whitespace().collect();
-                if parts.len() != 2{
-                    let error = format!("Two arguments required for constant declaration, but {} were found at line {} ({}).", parts.len(), current_line_number, line).red().to_string();
-                    panic!("{}", error);
-                }
-                let constant_name = parts.get(0).unwrap().chars().collect::<Vec<char>>()[1..].iter().collect::<String>();
-                let constant_value = parts.get(1).unwrap().chars().collect::<Vec<char>>().iter().collect();
-                replacements.push(Replacement::new(constant_name, constant_value, false));
+            let tokens = Self::line_to_tokens(line);
+            // Ensure line has at least one token
+            let Some(first_token) = tokens.first().cloned() else { continue; };
+            if line.text.starts_with('#') { continue; }
+            if first_token.text == ".endian" {
+                diagnostics.error(AssembleError::BadDeclaration { declaration: line.text.clone(), reason: "Endianness can only be set in the data section.".to_string(), span: first_token.span });
                 continue;
             }
-            if line.ends_with(":"){
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() != 1{
-                    let error = format!("Function declaration requires function name (and nothing more or less). This wasn't followed at line {}.", current_line_number).red().to_string();
-                    panic!("{}", error);
+            if line.text.starts_with('.') {
+                Self::declare_constant(line, tokens, None, symbols, diagnostics);
+                continue;
+            }
+            if line.text.ends_with(":"){
+                if tokens.len() != 1{
+                    let reason = "Function declaration requires function name (and nothing more or less).".to_string();
+                    diagnostics.error(AssembleError::BadDeclaration { declaration: line.text.clone(), reason, span: first_token.span.to(tokens[tokens.len() - 1].span) });
+                    continue;
+                }
+                let function_name = first_token.text[..first_token.text.len() - 1].to_string();
+                let name_span = Span{length: first_token.span.length - 1, ..first_token.span};
+                if let Err(error) = symbols.declare(Replacement::declared(function_name, section.bytes.len().to_string(), true, name_span)) {
+                    diagnostics.error(error);
                 }
-                let characters = parts.get(0).unwrap().chars().collect::<Vec<char>>();
-                let function_name = characters[0..characters.len() - 1].iter().collect::<String>();
-                let replacement = Replacement::new(function_name, passed_bytes.to_string(), true);
-                replacements.push(replacement);
 
                 continue;
             }
-            if let Some(instruction_name) = line.split_whitespace().nth(0) {
-                passed_bytes += instruction::Instruction::bytes_required_by_instruction_by_name(instruction_name.to_string()) as u32;
+            let Some(line) = Self::strip_comment(line) else { continue; };
+            match Instruction::from_string(&line, isa) {
+                Ok(instruction) => {
+                    let start = section.bytes.len();
+                    section.bytes.append(&mut instruction.to_vec());
+                    for (i, operand) in instruction.get_operands().iter().enumerate() {
+                        if let Operand::Written(token, kind) = operand {
+                            section.fixups.push(Fixup{offset: start + 1 + i, token: token.clone(), kind: FixupKind::Operand(*kind), location_counter: None});
+                        }
+                    }
+                    section.record_line(start, line.span);
+                },
+                Err(error) => diagnostics.error(error),
             }
         }
 
-        replacements = Self::replace_replacements(replacements);
-        replacements.push(Replacement::new("data_offset".to_string(), passed_bytes.to_string(), true));
+        if let Err(error) = symbols.declare(Replacement::new("data_offset".to_string(), section.bytes.len().to_string(), false)) {
+            diagnostics.error(error);
+        }
 
-        replacements
+        section
     }
 
-    pub fn line_to_argument_parts(lines: &str) -> Vec<String> {
-        let characters: Vec<char> = lines.chars().collect();
-        let mut arguments: Vec<String> = vec![];
+    /// Declares `.name value`. A value made of more than one token is an expression like `.size 4 * 2`.
+    /// The value is kept as written and resolved where it is used, so it may refer to symbols declared further down.
+    fn declare_constant(line: &SourceLine, mut tokens: Vec<Token>, location_counter: Option<String>, symbols: &mut SymbolTable, diagnostics: &mut Diagnostics) {
+        let first_token = tokens[0].clone();
+        // Comments behind the value aren't part of it
+        if let Some(comment_start) = tokens.iter().position(|token| token.text.starts_with('#')) {
+            tokens.truncate(comment_start);
+        }
+        if first_token.text.len() < 2 || tokens.len() < 2 {
+            let reason = "Constant declaration requires a name and a value.".to_string();
+            diagnostics.error(AssembleError::BadDeclaration { declaration: line.text.clone(), reason, span: first_token.span });
+            return;
+        }
+        let constant_name = first_token.text[1..].to_string();
+        let mut constant_value = tokens[1].text.clone();
+        if tokens.len() > 2 {
+            let expression = tokens[1..].iter().map(|token| token.text.clone()).collect::<Vec<String>>().join(" ");
+            let expression_span = tokens[1].span.to(tokens[tokens.len() - 1].span);
+            if let Err(error) = Expression::parse(&expression, expression_span) {
+                diagnostics.error(error);
+                return;
+            }
+            constant_value = format!("[{}]", expression);
+        }
+        // $ means the place of the declaration, not the place of the use
+        if let Some(location_counter) = location_counter {
+            constant_value = Self::replace_location_counter(&constant_value, &location_counter, tokens[1].span);
+        }
+        let name_span = Span{column: first_token.span.column + 1, length: first_token.span.length - 1, ..first_token.span};
+        if let Err(error) = symbols.declare(Replacement::declared(constant_name, constant_value, false, name_span)) {
+            diagnostics.error(error);
+        }
+    }
+
+    fn replace_location_counter(value: &str, location_counter: &str, span: Span) -> String {
+        let characters: Vec<char> = value.chars().collect();
+        let mut output: String = "".to_string();
+        let mut copied_until = 0;
+        for lexeme in Lexer::tokenize(value, span).iter().filter(|lexeme| lexeme.text == "$") {
+            let offset = (lexeme.span.column - span.column) as usize;
+            output += &characters[copied_until..offset].iter().collect::<String>();
+            // Wrapped in brackets so that a negative location can't merge with a preceding minus
+            output += &format!("({})", location_counter);
+            copied_until = offset + 1;
+        }
+        output += &characters[copied_until..].iter().collect::<String>();
+        output
+    }
+
+    /// Splits a line at whitespace into tokens which remember their position.
+    /// Quoted text and bracketed expressions stay a single token, a backslash escapes the next character.
+    pub fn line_to_tokens(line: &SourceLine) -> Vec<Token> {
+        let mut tokens: Vec<Token> = vec![];
         let mut current_argument = "".to_string();
+        let mut current_start: Option<u32> = None;
         let mut next_character_escaped = false;
         let mut whitespaces_escaped = false;
+        let mut bracket_depth = 0;
+        let mut column = line.span.column;
 
-        for character in characters {
-            if (character == ' ' || character == '\t') && !(next_character_escaped || whitespaces_escaped) {
+        for character in line.text.chars() {
+            if (character == ' ' || character == '\t') && !(next_character_escaped || whitespaces_escaped || bracket_depth > 0) {
                 // add current argument
-                if current_argument != "" {
-                    arguments.push(current_argument.parse().unwrap());
-                    current_argument = "".to_string();
+                if let Some(start) = current_start.take() {
+                    tokens.push(Token{text: std::mem::take(&mut current_argument), span: Span::new(line.span.file, line.span.line, start, column - start)});
                 }
+                column += 1;
                 continue;
             }
+            if current_start.is_none() { current_start = Some(column); }
+            column += 1;
 
-            if character == '\'' || character == '"' {
+            if (character == '\'' || character == '"') && !next_character_escaped {
                 whitespaces_escaped = !whitespaces_escaped;
             }
+            if !whitespaces_escaped && !next_character_escaped {
+                match character {
+                    '[' => bracket_depth += 1,
+                    ']' if bracket_depth > 0 => bracket_depth -= 1,
+                    _ => {},
+                }
+            }
 
             if character == '\\' && !next_character_escaped {
                 next_character_escaped = true;
@@ -239,132 +410,120 @@ impl ArgumentParser {
                 next_character_escaped = false;
             }
 
-            current_argument = current_argument.to_string() + character.to_string().as_str();
-        }
-
-        if current_argument != "" {
-            arguments.push(current_argument.parse().unwrap());
+            current_argument.push(character);
         }
 
-        arguments
-
-    }
-
-    fn replace_replacements(replacements: Vec<Replacement>) -> Vec<Replacement> {
-        let mut output: Vec<Replacement> = replacements.to_vec();
-        for _ in 0..replacements.len() {
-            for i in 0..output.len() {
-                let  origin = output[i].clone();
-                for replacement in replacements.iter() {
-                    if replacement.get_name() == output[i].get_value() {
-                        output[i].set_value(replacement.get_value(), origin.get_is_function())
-                    }
-                }
-            }
+        if let Some(start) = current_start {
+            tokens.push(Token{text: current_argument, span: Span::new(line.span.file, line.span.line, start, column - start)});
         }
 
-        output
+        tokens
     }
 
-    pub fn remove_declaration_lines(code: Vec<String>) -> Vec<String> {
-        let mut result: Vec<String> = Vec::new();
+    pub fn remove_declaration_lines(code: Vec<SourceLine>) -> Vec<SourceLine> {
+        let mut result: Vec<SourceLine> = Vec::new();
 
         for line in code.iter() {
-            if line.is_empty() { continue; }
-            if line.starts_with('.') { continue; }
-            if line.ends_with(':') { continue; }
-            if line.starts_with('#') { continue; }
+            if line.text.is_empty() { continue; }
+            if line.text.starts_with('.') { continue; }
+            if line.text.ends_with(':') { continue; }
+            if line.text.starts_with('#') { continue; }
 
-            let mut line_without_comments = "".to_string();
-
-            for char in line.chars(){
-                if char == '#' { break; }
-                line_without_comments += char.to_string().as_str();
+            if let Some(line_without_comments) = Self::strip_comment(line) {
+                result.push(line_without_comments);
             }
-
-            result.push(line_without_comments);
         }
 
         result
     }
 
-    pub fn apply_replacements_in_code(replacements: Vec<Replacement>, code: &mut Vec<String>){
-        for i in 0..code.iter().len(){
-            for replacement in replacements.clone().iter() {
-                code[i] = code[i].replace(replacement.get_name().as_str(), replacement.get_value().as_str()).as_str().to_string();
-                code[i] = Self::resolve_all_math_ops_in_line(code[i].clone(), replacements.clone());
-            }
+    /// The line up to its comment, or None if nothing is left.
+    pub fn strip_comment(line: &SourceLine) -> Option<SourceLine> {
+        let mut line_without_comments = "".to_string();
+
+        for char in line.text.chars(){
+            if char == '#' { break; }
+            line_without_comments += char.to_string().as_str();
         }
-    }
+        if line_without_comments.trim().is_empty() { return None; }
 
+        let length = line_without_comments.chars().count() as u32;
+        Some(SourceLine{text: line_without_comments, span: Span{length, ..line.span}})
+    }
 
-    pub fn convert(a: &str, a_sys: i8, b_sys: i8, ) -> String {
+    /// Returns None if a contains a character that isn't a digit in a_sys or the number is too big.
+    pub fn convert(a: &str, a_sys: i8, b_sys: i8, ) -> Option<String> {
         // Convert to int
-        let mut total = 0;
-        let mut i = 0;
-        for character in a.to_ascii_uppercase().chars().rev() {
-            let corresponding_number = CHARACTERS.iter().position(|p| *p.to_string() == character.to_string());
-            let number_in_sum = corresponding_number.unwrap() as i32 * (a_sys as i32).pow(i);
-            total += number_in_sum;
-            i += 1;
+        let mut total: i64 = 0;
+        for character in a.to_ascii_uppercase().chars() {
+            let corresponding_number = CHARACTERS.iter().position(|p| *p.to_string() == character.to_string())?;
+            if corresponding_number as i8 >= a_sys { return None; }
+            total = total.checked_mul(a_sys as i64)?.checked_add(corresponding_number as i64)?;
         }
 
         // Convert to new type
         let mut b: String = "".to_string();
 
         while total > 0{
-            b = b + CHARACTERS[(total % b_sys as i32) as usize];
-            total = total / b_sys as i32;
+            b = b + CHARACTERS[(total % b_sys as i64) as usize];
+            total /= b_sys as i64;
         }
+        if b.is_empty() { b = "0".to_string(); }
 
-        b.chars().rev().collect::<String>()
+        Some(b.chars().rev().collect::<String>())
     }
 
-    pub fn resolve_all_math_ops_in_line(line: String, replacements: Vec<Replacement>) -> String {
-        let mut next_replacement_data = "".to_string();
-        let mut output: String = "".to_string();
-        let mut write_to_repl_data = false;
-        let mut line = line;
-
-        for replacement in replacements.iter() {
-            line = line.replace(replacement.get_name().as_str(), replacement.get_value().as_str()).parse().unwrap();
-        }
-
+    /// Replaces every name in the line by its value and every bracketed expression by its result.
+    /// Only whole identifiers are replaced, so a symbol `a` leaves `add` and `r1` leaves `r10` alone.
+    pub fn resolve_all_math_ops_in_line(line: String, symbols: &SymbolTable, span: Span) -> Result<String, AssembleError> {
         let characters: Vec<char> = line.chars().collect();
+        let lexemes = Lexer::tokenize(&line, span);
+        let offset = |lexeme: &Lexeme| (lexeme.span.column - span.column) as usize;
+        let mut output: String = "".to_string();
+        let mut copied_until = 0;
+        let mut i = 0;
 
-
-
-        println!("characters: {:?}", line);
-
-
-
-        for character in characters.iter() {
-            if *character == '[' {
-                write_to_repl_data = true;
-                continue;
-            }
-            if *character == ']' {
-                write_to_repl_data = false;
-                output += math::resolve_string(next_replacement_data.clone().parse().unwrap(), replacements.clone()).as_str();
-                next_replacement_data = "".to_string();
-                continue;
-            }
-            if write_to_repl_data {
-                next_replacement_data += character.to_string().as_str();
-            }else {
-                output = (&*(output.to_owned() + &*character.to_string())).parse().unwrap();
-            }
-        }
-
-        if !output.parse::<i32>().is_ok() {
-            for replacement in replacements.iter() {
-                output = output.replace(replacement.get_name().as_str(), replacement.get_value().as_str()).as_str().parse().unwrap();
-                println!("replacement: {}, out: {}", replacement.make_description(), output);
+        while i < lexemes.len() {
+            let lexeme = &lexemes[i];
+            // Keep everything between the lexemes (e.g. whitespace) as it is
+            output += &characters[copied_until..offset(lexeme)].iter().collect::<String>();
+            match lexeme.kind {
+                LexemeKind::OpenBracket => {
+                    // Find the matching bracket, brackets inside are part of the expression
+                    let mut depth = 0;
+                    let length = lexemes[i..].iter().position(|lexeme| {
+                        match lexeme.kind {
+                            LexemeKind::OpenBracket => depth += 1,
+                            LexemeKind::CloseBracket => depth -= 1,
+                            _ => {},
+                        }
+                        depth == 0
+                    });
+                    let Some(length) = length else {
+                        let expression = characters[offset(lexeme) + 1..].iter().collect();
+                        return Err(AssembleError::BadExpression { expression, reason: "the closing ] is missing".to_string(), span });
+                    };
+                    let closing = &lexemes[i + length];
+                    let expression: String = characters[offset(lexeme) + 1..offset(closing)].iter().collect();
+                    let expression_span = Span{column: lexeme.span.column + 1, length: closing.span.column - lexeme.span.column - 1, ..lexeme.span};
+                    output += math::resolve_string(expression, symbols, expression_span)?.as_str();
+                    copied_until = offset(closing) + closing.text.chars().count();
+                    i += length + 1;
+                    continue;
+                },
+                LexemeKind::Identifier => {
+                    match symbols.resolve(&lexeme.text, lexeme.span)? {
+                        Some(value) => output += &value,
+                        None => output += &lexeme.text,
+                    }
+                },
+                _ => output += &lexeme.text,
             }
+            copied_until = offset(lexeme) + lexeme.text.chars().count();
+            i += 1;
         }
+        output += &characters[copied_until.min(characters.len())..].iter().collect::<String>();
 
-
-
-        output
+        Ok(output)
     }
-}
\ No newline at end of file
+}
@@ -1,54 +1,183 @@
-use instruction::Instruction;
-use crate::{instruction};
-use colored::Colorize;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use crate::argument_parser::ArgumentParser;
+use crate::diagnostics::Diagnostics;
+use crate::error::{AssembleError, AssembleWarning};
+use crate::isa::Isa;
+use crate::preprocessor::Preprocessor;
+use crate::object::{Linkage, ObjectFile, ObjectSection, ObjectSymbol, Relocation};
+use crate::section::{Fixup, LineEntry, Section};
+use crate::source::{SourceLine, SourceMap};
+use crate::symbol_map::{SymbolKind, SymbolMap};
+use crate::symbol_table::SymbolTable;
 use crate::utility::replacement::Replacement;
 
 pub struct Assembler {
-    pub code: String,
-    pub output: Vec<u8>,
+    pub sources: SourceMap,
+    pub diagnostics: Diagnostics,
+    /// Every label, constant and data name, filled in by `assemble`.
+    pub symbols: SymbolTable,
+    /// The source line of every instruction and data entry by address, filled in by `assemble`.
+    pub line_table: Vec<LineEntry>,
+    file: usize,
+    isa: Isa,
+    /// Constants given on the command line with `-D NAME=value`.
+    defines: Vec<(String, String)>,
+    /// Directories given with `-I` to look for included files in.
+    include_paths: Vec<PathBuf>,
 }
 
 impl Assembler {
-    pub fn new(code: String) -> Assembler {
-        Assembler{code, output: Vec::new()}
-    }
-    pub fn assemble(&mut self) {
-        // Remove all comments and empty lines
-        let code_seperated_by_lines = self.code.lines();
-        let lines = code_seperated_by_lines.clone().map(|x| x.chars().collect()).collect();
-        let sections = ArgumentParser::split_sections(lines);
-        let data_section = sections.0;
-        let text_section = sections.1;
-        let mut replacements: Vec<Replacement> = ArgumentParser::get_replacements_from_code(text_section.clone());
-        let data_parsed = ArgumentParser::compile_data_section(data_section, &mut replacements);
-        let data_replacements = data_parsed.1;
-        let data_bytes = data_parsed.0;
-        let mut lines_except_values: Vec<String> = ArgumentParser::remove_declaration_lines(text_section.clone());
-        let data_offset = replacements[replacements.iter().position(|x| x.get_name() == "data_offset").unwrap()].get_value().parse::<u32>().unwrap();
-
-        for replacement in data_replacements{
-            replacements.push(Replacement::new(replacement.get_name(), (replacement.get_value().parse::<u32>().unwrap() + data_offset).to_string(), false));
-        }
+    /// The file name points at the code in diagnostics, included files are looked up next to it.
+    pub fn new(file_name: String, code: String) -> Assembler {
+        let mut sources = SourceMap::new();
+        let file = sources.add_file(file_name, code);
+        Assembler{sources, diagnostics: Diagnostics::new(), symbols: SymbolTable::new(), line_table: Vec::new(), file, isa: Isa::builtin(), defines: Vec::new(), include_paths: Vec::new()}
+    }
+
+    /// Encodes for another instruction set than the built-in one.
+    pub fn with_isa(mut self, isa: Isa) -> Assembler {
+        self.isa = isa;
+        self
+    }
+
+    /// Declares constants before the first line, for conditions and code alike.
+    pub fn with_defines(mut self, defines: Vec<(String, String)>) -> Assembler {
+        self.defines = defines;
+        self
+    }
+
+    /// Where `.include` looks for files that aren't next to the including file.
+    pub fn with_include_paths(mut self, include_paths: Vec<PathBuf>) -> Assembler {
+        self.include_paths = include_paths;
+        self
+    }
 
+    /// The assembled file in `sources`, the others are included files and macro expansions.
+    pub fn get_file(&self) -> usize {
+        self.file
+    }
+
+    pub fn get_isa(&self) -> &Isa {
+        &self.isa
+    }
+
+    /// Bytes of the text section, the data section starts right after it.
+    pub fn get_text_length(&self) -> usize {
+        self.symbols.get("data_offset").and_then(|symbol| symbol.get_value().parse().ok()).unwrap_or_default()
+    }
+
+    /// The lines of the assembled file, without the ones of included files.
+    pub fn get_lines(&self) -> Vec<SourceLine> {
+        self.sources.get_lines(self.file)
+    }
 
-        ArgumentParser::apply_replacements_in_code(replacements, &mut lines_except_values);
+    /// Assembles the whole code, continuing after errors so that every problem ends up in `diagnostics`.
+    pub fn assemble(&mut self) -> Result<Vec<u8>, Vec<AssembleError>> {
+        let Layout{mut text, mut data, mut symbols, text_section, ..} = self.layout(false);
+        // Pass 2: fill in the operands and values now that every symbol is known
+        text.resolve_fixups(&mut symbols, &mut self.diagnostics);
+        data.resolve_fixups(&mut symbols, &mut self.diagnostics);
+        self.symbols = symbols;
 
-        let mut binary: Vec<u8> = vec![];
-        let mut i: u32 = 0;
-        for line in lines_except_values.clone() {
-            i += 1;
-            if let Some(instruction) = Instruction::from_string(line.clone(), i){
-                let mut binary_instruction = instruction.to_vec();
-                binary.append(&mut binary_instruction);
-            }else{
-                let warning = format!("Couldn't decode line {} at line {}.", line.clone().to_string(), i).yellow().to_string();
-                println!("{}", warning);
+        let lines_except_values: Vec<SourceLine> = ArgumentParser::remove_declaration_lines(text_section);
+        if let Some(last_token) = lines_except_values.last().and_then(|line| ArgumentParser::line_to_tokens(line).first().cloned()) {
+            let last_instruction = last_token.text.to_ascii_lowercase();
+            if last_instruction != "halt" && last_instruction != "jmp" {
+                self.diagnostics.warning(AssembleWarning::MissingHalt { last_instruction, span: last_token.span });
             }
         }
-        binary.append(&mut data_bytes.clone());
-        self.output = binary;
+        if self.diagnostics.has_errors() {
+            return Err(self.diagnostics.get_errors());
+        }
+        let mut binary = text.bytes;
+        binary.append(&mut data.bytes);
+        Ok(binary)
+    }
+
+    /// Assembles the code as a module of a bigger program. Values that depend on addresses are left to the linker,
+    /// data names and `$` count from the start of the module's data section.
+    pub fn assemble_object(&mut self) -> Result<ObjectFile, Vec<AssembleError>> {
+        let Layout{mut text, mut data, mut symbols, linkage, ..} = self.layout(true);
+        self.symbols = symbols.clone();
+        let externs: Vec<String> = linkage.externs.iter().map(|token| token.text.clone()).collect();
+        let kinds: HashMap<String, SymbolKind> = symbols.iter().map(|symbol| (symbol.get_name(), SymbolMap::kind(self, symbol))).collect();
+        for external in linkage.externs.iter() {
+            if let Some(symbol) = symbols.get(&external.text).filter(|symbol| symbol.get_span().line > 0) {
+                self.diagnostics.error(AssembleError::SymbolRedeclared { name: external.text.clone(), span: external.span, previous: symbol.get_span() });
+            }
+        }
+        for global in linkage.globals.iter() {
+            let reason = match kinds.get(&global.text) {
+                None => format!("Symbol {} is declared .global but never defined in this file.", global.text),
+                Some(SymbolKind::Constant) => format!("Symbol {} is a constant, only labels and data names can be .global.", global.text),
+                Some(_) => continue,
+            };
+            self.diagnostics.error(AssembleError::BadDirective { directive: ".global".to_string(), reason, span: global.span });
+        }
+
+        // Pass 2 only fills in what doesn't depend on where the sections end up
+        let mut relocations = Vec::new();
+        for (section, object_section) in [(&mut text, ObjectSection::Text), (&mut data, ObjectSection::Data)] {
+            let (relocated, fixed): (Vec<Fixup>, Vec<Fixup>) = std::mem::take(&mut section.fixups).into_iter()
+                .partition(|fixup| ObjectFile::is_relocatable(&fixup.token.text, &symbols, &kinds, &externs));
+            section.fixups = fixed;
+            section.resolve_fixups(&mut symbols, &mut self.diagnostics);
+            relocations.extend(relocated.into_iter().map(|fixup| Relocation{
+                section: object_section,
+                offset: fixup.offset,
+                kind: fixup.kind,
+                expression: fixup.token.text,
+                location_counter: fixup.location_counter,
+                line: self.sources.outermost(fixup.token.span).line,
+                column: self.sources.outermost(fixup.token.span).column,
+            }));
+        }
+        if self.diagnostics.has_errors() {
+            return Err(self.diagnostics.get_errors());
+        }
+
+        let globals: Vec<&str> = linkage.globals.iter().map(|token| token.text.as_str()).collect();
+        let symbols = symbols.iter().filter(|symbol| symbol.get_name() != "data_offset").map(|symbol| ObjectSymbol{
+            name: symbol.get_name(),
+            kind: kinds[&symbol.get_name()],
+            value: symbol.get_value(),
+            global: globals.contains(&symbol.get_name().as_str()),
+            line: self.sources.outermost(symbol.get_span()).line,
+        }).collect();
+        let source = self.sources.get_name(self.file).to_string();
+        Ok(ObjectFile{source, text: text.bytes, data: data.bytes, symbols, externs, relocations})
     }
 
+    /// Pass 1: preprocesses the code and lays out both sections, which gives every label and data name its final address
+    /// (or its offset into the section for a relocatable module).
+    fn layout(&mut self, relocatable: bool) -> Layout {
+        let lines = self.sources.get_lines(self.file);
+        let lines = Preprocessor::new(&self.isa).with_defines(&self.defines).with_include_paths(&self.include_paths).process(lines, &mut self.sources, &mut self.diagnostics);
+        let (lines, linkage) = Linkage::collect(lines, &mut self.diagnostics);
+        let (data_section, text_section) = ArgumentParser::split_sections(lines, &mut self.diagnostics);
+        let mut symbols = SymbolTable::new();
+        for (name, value) in self.defines.iter() {
+            if let Err(error) = symbols.declare(Replacement::new(name.clone(), value.clone(), false)) {
+                self.diagnostics.error(error);
+            }
+        }
+        let text = ArgumentParser::layout_text_section(text_section.clone(), &self.isa, &mut symbols, &mut self.diagnostics);
+        let data_offset = if relocatable { 0 } else { text.bytes.len() };
+        let data = ArgumentParser::layout_data_section(data_section, &mut symbols, data_offset as u32, relocatable, &mut self.diagnostics);
+        self.line_table = text.lines.iter().cloned()
+            .chain(data.lines.iter().map(|entry| LineEntry{address: entry.address + text.bytes.len(), ..entry.clone()}))
+            .collect();
+        Layout{text, data, symbols, linkage, text_section}
+    }
 }
 
+/// Both sections after pass 1, with what is needed to finish them.
+struct Layout {
+    text: Section,
+    data: Section,
+    symbols: SymbolTable,
+    linkage: Linkage,
+    /// The lines of the text section, to check how it ends.
+    text_section: Vec<SourceLine>,
+}
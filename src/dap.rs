@@ -0,0 +1,330 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use crate::assembler::Assembler;
+use crate::debugger::Debugger;
+use crate::emulator::{Emulator, Fault, CARRY_FLAG, ZERO_FLAG};
+use crate::error::AssembleError;
+use crate::isa;
+use crate::isa::Isa;
+use crate::preprocessor::Preprocessor;
+use crate::section::LineEntry;
+use crate::utility::json::JsonValue;
+use crate::utility::message;
+
+/// The emulator only has one thread of execution.
+const THREAD_ID: i64 = 1;
+/// References of the scopes in `variables` requests.
+const REGISTERS_REFERENCE: i64 = 1;
+const FLAGS_REFERENCE: i64 = 2;
+const STACK_REFERENCE: i64 = 3;
+
+/// A program loaded by `launch`.
+struct Session {
+    emulator: Emulator,
+    path: String,
+    /// The name of the program and of every file it includes by file id.
+    files: Vec<(usize, String)>,
+    line_table: Vec<LineEntry>,
+    /// Where the data section starts, breakpoints only go on lines before it.
+    text_length: usize,
+    labels: Vec<(String, usize)>,
+    /// The addresses of the breakpoints by the source they were set in.
+    breakpoints: BTreeMap<PathBuf, BTreeSet<u8>>,
+    stop_on_entry: bool,
+    /// How much of the emulator output was already sent as output events.
+    sent_output: usize,
+}
+
+/// Why running stopped, reported to the editor as the reason of a stopped event.
+enum Stop {
+    Step,
+    Breakpoint,
+    Halted,
+    Fault(Fault),
+}
+
+/// Lets editors debug programs through the Debug Adapter Protocol. Breakpoints are set on source lines,
+/// which are mapped to addresses with the line table of the assembler.
+pub struct DapServer {
+    isa: Isa,
+    max_steps: u64,
+    session: Option<Session>,
+    /// The lines that got a breakpoint by their source, mapped again when the program is launched.
+    breakpoints: BTreeMap<PathBuf, Vec<i64>>,
+    sequence: i64,
+    /// Set once the editor disconnected.
+    pub finished: bool,
+}
+
+impl DapServer {
+    pub fn new(isa: Isa, max_steps: u64) -> DapServer {
+        DapServer{isa, max_steps, session: None, breakpoints: BTreeMap::new(), sequence: 0, finished: false}
+    }
+
+    /// Answers requests until the editor disconnects or closes the stream.
+    pub fn serve(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> std::io::Result<()> {
+        while let Some(body) = message::read_message(reader)? {
+            let replies = match JsonValue::parse(&body) {
+                Ok(request) => self.handle(&request),
+                Err(_) => Vec::new(),
+            };
+            for reply in replies {
+                message::write_message(writer, &reply)?;
+            }
+            writer.flush()?;
+            if self.finished { break; }
+        }
+        Ok(())
+    }
+
+    /// The response to a request followed by the events it caused.
+    pub fn handle(&mut self, request: &JsonValue) -> Vec<JsonValue> {
+        let command = request.get("command").and_then(JsonValue::as_str).unwrap_or_default().to_string();
+        let request_sequence = request.get("seq").and_then(JsonValue::as_i64).unwrap_or_default();
+        // The response is sent before the events it caused
+        let sequence = self.next_sequence();
+        let empty = JsonValue::Object(Vec::new());
+        let arguments = request.get("arguments").unwrap_or(&empty);
+        let mut events = Vec::new();
+        let result = match command.as_str() {
+            "initialize" => {
+                events.push(self.event("initialized", JsonValue::Object(Vec::new())));
+                Ok(JsonValue::object(vec![("supportsConfigurationDoneRequest", true.into())]))
+            },
+            "launch" => self.launch(arguments),
+            "setBreakpoints" => Ok(self.set_breakpoints(arguments)),
+            "configurationDone" => {
+                match self.session.as_ref().map(|session| session.stop_on_entry) {
+                    Some(true) => events.push(self.stopped_event("entry", None)),
+                    Some(false) => events.append(&mut self.resume(false)),
+                    None => {},
+                }
+                Ok(JsonValue::Null)
+            },
+            "threads" => Ok(JsonValue::object(vec![("threads", vec![JsonValue::object(vec![("id", THREAD_ID.into()), ("name", "main".into())])].into())])),
+            "stackTrace" => self.with_session(|session| Ok(Self::stack_trace(session))),
+            "scopes" => Ok(Self::scopes()),
+            "variables" => {
+                let reference = arguments.get("variablesReference").and_then(JsonValue::as_i64).unwrap_or_default();
+                self.with_session(|session| Ok(JsonValue::object(vec![("variables", Self::variables(session, reference).into())])))
+            },
+            "continue" => {
+                events.append(&mut self.resume(false));
+                Ok(JsonValue::object(vec![("allThreadsContinued", true.into())]))
+            },
+            "next" => {
+                events.append(&mut self.step_over());
+                Ok(JsonValue::Null)
+            },
+            "stepIn" => {
+                events.append(&mut self.resume(true));
+                Ok(JsonValue::Null)
+            },
+            // Programs run until they stop on their own, so they are never running when this arrives
+            "pause" => Ok(JsonValue::Null),
+            "disconnect" | "terminate" => {
+                self.finished = true;
+                Ok(JsonValue::Null)
+            },
+            _ => Err(format!("{} isn't supported.", command)),
+        };
+        let mut response = vec![
+            ("seq", sequence.into()),
+            ("type", "response".into()),
+            ("request_seq", request_sequence.into()),
+            ("command", command.into()),
+            ("success", result.is_ok().into()),
+        ];
+        match result {
+            Ok(JsonValue::Null) => {},
+            Ok(body) => response.push(("body", body)),
+            Err(message) => response.push(("message", message.into())),
+        }
+        let mut replies = vec![JsonValue::object(response)];
+        replies.append(&mut events);
+        replies
+    }
+
+    /// Assembles the program given as `program`, reporting the first error if it doesn't assemble.
+    fn launch(&mut self, arguments: &JsonValue) -> Result<JsonValue, String> {
+        let path = arguments.get("program").and_then(JsonValue::as_str).ok_or("The launch configuration needs a program.")?.to_string();
+        let code = fs::read_to_string(&path).map_err(|reason| format!("Couldn't read {}: {}", path, reason))?;
+        // The same as -D and -I on the command line, so the program is built like sriscasm builds it
+        let strings = |key: &str| arguments.get(key).and_then(JsonValue::as_array).into_iter().flatten().filter_map(JsonValue::as_str).collect::<Vec<&str>>();
+        let defines = strings("defines").into_iter().map(Preprocessor::parse_define).collect::<Result<Vec<(String, String)>, AssembleError>>()
+            .map_err(|error| error.to_string())?;
+        let include_paths = strings("includePaths").into_iter().map(PathBuf::from).collect();
+        let mut assembler = Assembler::new(path.clone(), code).with_isa(self.isa.clone()).with_defines(defines).with_include_paths(include_paths);
+        let binary = match assembler.assemble() {
+            Ok(binary) => binary,
+            Err(errors) => {
+                let location = errors.first().and_then(|error| error.span()).map(|span| assembler.sources.outermost(span))
+                    .map(|span| format!(" ({}:{})", span.line, span.column)).unwrap_or_default();
+                return Err(format!("{}{}", errors.first().map(ToString::to_string).unwrap_or_default(), location));
+            },
+        };
+        let emulator = Emulator::new(&binary, self.isa.clone()).map_err(|fault| fault.to_string())?;
+        let stop_on_entry = arguments.get("stopOnEntry").and_then(JsonValue::as_bool).unwrap_or(false);
+        let text_length = assembler.symbols.get("data_offset").and_then(|symbol| symbol.get_value().parse().ok()).unwrap_or(binary.len());
+        // Expanded macros belong to the line that uses them, included code stays in its file
+        let line_table = assembler.line_table.iter().map(|entry| LineEntry{span: assembler.sources.root(entry.span), ..entry.clone()}).collect();
+        let files = assembler.sources.get_written_files().into_iter().map(|file| (file, assembler.sources.get_name(file).to_string())).collect();
+        let mut session = Session{emulator, path, files, line_table, text_length, labels: assembler.symbols.labels(), breakpoints: BTreeMap::new(), stop_on_entry, sent_output: 0};
+        for (source, lines) in &self.breakpoints {
+            Self::map_breakpoints(&mut session, source, lines);
+        }
+        self.session = Some(session);
+        Ok(JsonValue::Null)
+    }
+
+    /// Replaces the breakpoints of the source with the requested lines. A line without code gets the breakpoint of the next line with code.
+    fn set_breakpoints(&mut self, arguments: &JsonValue) -> JsonValue {
+        let path = arguments.get("source").and_then(|source| source.get("path")).and_then(JsonValue::as_str).unwrap_or_default();
+        let source = Preprocessor::identity(Path::new(path));
+        let lines: Vec<i64> = arguments.get("breakpoints").and_then(JsonValue::as_array)
+            .map(|breakpoints| breakpoints.iter().filter_map(|breakpoint| breakpoint.get("line").and_then(JsonValue::as_i64)).collect())
+            .unwrap_or_default();
+        let breakpoints = match &mut self.session {
+            Some(session) => Self::map_breakpoints(session, &source, &lines),
+            None => lines.iter().map(|line| JsonValue::object(vec![("verified", false.into()), ("line", (*line).into())])).collect(),
+        };
+        self.breakpoints.insert(source, lines);
+        JsonValue::object(vec![("breakpoints", breakpoints.into())])
+    }
+
+    fn map_breakpoints(session: &mut Session, source: &Path, lines: &[i64]) -> Vec<JsonValue> {
+        // A file included more than once has several ids
+        let files: Vec<usize> = session.files.iter().filter(|(_, name)| Preprocessor::identity(Path::new(name)) == source).map(|(file, _)| *file).collect();
+        let mut addresses = BTreeSet::new();
+        let breakpoints = lines.iter().map(|line| {
+            let entry = session.line_table.iter()
+                .filter(|entry| files.contains(&entry.span.file) && entry.address < session.text_length && entry.span.line as i64 >= *line)
+                .min_by_key(|entry| (entry.span.line, entry.address));
+            match entry {
+                Some(entry) => {
+                    addresses.insert(entry.address as u8);
+                    JsonValue::object(vec![("verified", true.into()), ("line", (entry.span.line as i64).into())])
+                },
+                None if files.is_empty() => JsonValue::object(vec![("verified", false.into()), ("line", (*line).into()), ("message", "The file isn't part of the program.".into())]),
+                None => JsonValue::object(vec![("verified", false.into()), ("line", (*line).into()), ("message", "There is no instruction on or after this line.".into())]),
+            }
+        }).collect();
+        session.breakpoints.insert(source.to_path_buf(), addresses);
+        breakpoints
+    }
+
+    fn with_session(&self, answer: impl Fn(&Session) -> Result<JsonValue, String>) -> Result<JsonValue, String> {
+        self.session.as_ref().map_or_else(|| Err("No program was launched.".to_string()), answer)
+    }
+
+    /// The current instruction as the only frame, named after the label it belongs to.
+    fn stack_trace(session: &Session) -> JsonValue {
+        let address = session.emulator.program_counter() as usize;
+        let entry = session.line_table.iter().find(|entry| (entry.address..entry.address + entry.length).contains(&address));
+        let line = entry.map(|entry| entry.span.line as i64).unwrap_or_default();
+        let path = entry.and_then(|entry| session.files.iter().find(|(file, _)| *file == entry.span.file)).map(|(_, name)| name.clone()).unwrap_or_else(|| session.path.clone());
+        let name = session.labels.iter().filter(|(_, start)| *start <= address).max_by_key(|(_, start)| *start)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| "main".to_string());
+        let file_name = Path::new(&path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let frame = JsonValue::object(vec![
+            ("id", 0.into()),
+            ("name", format!("{} ({:#04X})", name, address).into()),
+            ("source", JsonValue::object(vec![("name", file_name.into()), ("path", path.into())])),
+            ("line", line.into()),
+            ("column", 1.into()),
+            ("instructionPointerReference", format!("{:#04X}", address).into()),
+        ]);
+        JsonValue::object(vec![("stackFrames", vec![frame].into()), ("totalFrames", 1.into())])
+    }
+
+    fn scopes() -> JsonValue {
+        let scope = |name: &str, reference: i64| JsonValue::object(vec![("name", name.into()), ("variablesReference", reference.into()), ("expensive", false.into())]);
+        JsonValue::object(vec![("scopes", vec![scope("Registers", REGISTERS_REFERENCE), scope("Flags", FLAGS_REFERENCE), scope("Stack", STACK_REFERENCE)].into())])
+    }
+
+    fn variables(session: &Session, reference: i64) -> Vec<JsonValue> {
+        let variable = |name: String, value: String| JsonValue::object(vec![("name", name.into()), ("value", value.into()), ("variablesReference", 0.into())]);
+        let emulator = &session.emulator;
+        match reference {
+            REGISTERS_REFERENCE => emulator.registers.iter().enumerate().map(|(i, value)| {
+                let name = isa::register_name(i as u8 | 0b1000_0000).map(|name| format!("r{} ({})", i, name)).unwrap_or_else(|| format!("r{}", i));
+                variable(name, format!("{:#04X} ({})", value, value))
+            }).collect(),
+            FLAGS_REFERENCE => {
+                let flags = emulator.registers[(isa::FLAGS_REGISTER & 0b0111_1111) as usize];
+                vec![variable("zero".to_string(), (flags & ZERO_FLAG != 0).to_string()), variable("carry".to_string(), (flags & CARRY_FLAG != 0).to_string())]
+            },
+            // frame_ptr starts at 0, the first push moves it to the end of memory
+            STACK_REFERENCE if emulator.frame_pointer() != 0 => (emulator.frame_pointer() as usize..emulator.memory.len())
+                .map(|address| variable(format!("{:#04X}", address), format!("{:#04X} ({})", emulator.memory[address], emulator.memory[address])))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Runs a call until it returns, anything else for a single instruction.
+    fn step_over(&mut self) -> Vec<JsonValue> {
+        let Some(session) = &self.session else { return Vec::new(); };
+        let Some(return_address) = Debugger::call_return_address(&session.emulator) else { return self.resume(true); };
+        let frame_ptr = session.emulator.frame_pointer();
+        self.run(|emulator| emulator.program_counter() == return_address && emulator.frame_pointer() == frame_ptr)
+    }
+
+    fn resume(&mut self, single_step: bool) -> Vec<JsonValue> {
+        self.run(|_| single_step)
+    }
+
+    /// Steps until `done` or a breakpoint, then tells the editor why it stopped and what the program printed.
+    fn run(&mut self, done: impl Fn(&Emulator) -> bool) -> Vec<JsonValue> {
+        let max_steps = self.max_steps;
+        let Some(session) = &mut self.session else { return Vec::new(); };
+        let stop = loop {
+            if session.emulator.halted { break Stop::Halted; }
+            if session.emulator.steps >= max_steps { break Stop::Fault(Fault::StepLimitReached { steps: session.emulator.steps }); }
+            if let Err(fault) = session.emulator.step() { break Stop::Fault(fault); }
+            if session.emulator.halted { break Stop::Halted; }
+            if done(&session.emulator) { break Stop::Step; }
+            if session.breakpoints.values().any(|addresses| addresses.contains(&session.emulator.program_counter())) { break Stop::Breakpoint; }
+        };
+        // soc clears the output, everything after it is new
+        let output = &session.emulator.output;
+        if output.len() < session.sent_output { session.sent_output = 0; }
+        let new_output = String::from_utf8_lossy(&output[session.sent_output..]).to_string();
+        session.sent_output = output.len();
+
+        let mut events = Vec::new();
+        if !new_output.is_empty() {
+            events.push(self.event("output", JsonValue::object(vec![("category", "stdout".into()), ("output", new_output.into())])));
+        }
+        match stop {
+            Stop::Step => events.push(self.stopped_event("step", None)),
+            Stop::Breakpoint => events.push(self.stopped_event("breakpoint", None)),
+            Stop::Fault(fault) => events.push(self.stopped_event("exception", Some(fault.to_string()))),
+            Stop::Halted => {
+                events.push(self.event("exited", JsonValue::object(vec![("exitCode", 0.into())])));
+                events.push(self.event("terminated", JsonValue::Object(Vec::new())));
+            },
+        }
+        events
+    }
+
+    fn stopped_event(&mut self, reason: &str, text: Option<String>) -> JsonValue {
+        let mut body = vec![("reason", reason.into()), ("threadId", THREAD_ID.into()), ("allThreadsStopped", true.into())];
+        if let Some(text) = text {
+            body.push(("text", text.into()));
+        }
+        self.event("stopped", JsonValue::object(body))
+    }
+
+    fn event(&mut self, name: &str, body: JsonValue) -> JsonValue {
+        JsonValue::object(vec![("seq", self.next_sequence().into()), ("type", "event".into()), ("event", name.into()), ("body", body)])
+    }
+
+    fn next_sequence(&mut self) -> i64 {
+        self.sequence += 1;
+        self.sequence
+    }
+}
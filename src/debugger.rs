@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::disassembler::Disassembler;
+use crate::emulator::{Emulator, Fault, CARRY_FLAG, MEMORY_SIZE, ZERO_FLAG};
+use crate::isa;
+use crate::source::Span;
+use crate::symbol_table::SymbolTable;
+use crate::utility::expression::Expression;
+
+const HELP: &str = "\
+break <location>        (b)  stop before the instruction at the location, e.g. `break loop` or `break 0x0A`
+delete [location]       (d)  remove the breakpoint, or all of them
+breakpoints                  list the breakpoints
+step [count]            (s)  execute one (or count) instructions
+next                    (n)  like step, but runs a call (pushb of the return address followed by jmp) until it returns
+continue                (c)  run until a breakpoint, a watched address changes, halt or a fault
+registers               (r)  print all registers and flags
+memory <location> [count] (x)  print count bytes (default 16) starting at the location
+stack                        print the bytes between frame_ptr and the end of memory
+watch <location>        (w)  stop as soon as the byte at the location changes
+unwatch <location>           stop watching the location
+disassemble             (dis) print the program with its labels
+symbols                      print the labels, data names and constants
+help                    (h)  print this text
+quit                    (q)  leave the debugger";
+
+/// Runs a program under control of textual commands. The symbol table of the assembler is used to
+/// resolve locations like `loop + 2` and to show addresses as labels.
+pub struct Debugger {
+    pub emulator: Emulator,
+    symbols: SymbolTable,
+    labels: BTreeMap<usize, String>,
+    /// How many bytes of memory the program occupies.
+    program_length: usize,
+    /// Where the data section starts, labels only describe addresses before it.
+    text_length: usize,
+    breakpoints: BTreeSet<u8>,
+    /// Watched addresses with the value they had when they were last checked.
+    watches: BTreeMap<u8, u8>,
+    max_steps: u64,
+}
+
+/// Why running stopped before the requested number of instructions.
+enum Stop {
+    Halted,
+    Breakpoint,
+    Watch { address: u8, old: u8, new: u8 },
+    Fault(Fault),
+}
+
+impl Debugger {
+    /// Pass an empty symbol table for binaries without their source.
+    pub fn new(emulator: Emulator, symbols: SymbolTable, program_length: usize, max_steps: u64) -> Debugger {
+        let labels = symbols.labels().into_iter().map(|(name, address)| (address, name)).collect();
+        let text_length = symbols.get("data_offset").and_then(|symbol| symbol.get_value().parse().ok()).unwrap_or(program_length);
+        Debugger{emulator, symbols, labels, program_length, text_length, breakpoints: BTreeSet::new(), watches: BTreeMap::new(), max_steps}
+    }
+
+    /// Executes one command line and returns what should be printed. `None` means the user wants to quit.
+    pub fn execute(&mut self, command: &str) -> Option<String> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let argument = words.collect::<Vec<&str>>().join(" ");
+        let output = match name {
+            "" => String::new(),
+            "break" | "b" => self.add_breakpoint(&argument),
+            "delete" | "d" => self.delete_breakpoint(&argument),
+            "breakpoints" => self.list_breakpoints(),
+            "step" | "s" => {
+                let count = if argument.is_empty() { Ok(1) } else { argument.parse::<u64>().map_err(|_| format!("{} isn't a number of steps.", argument)) };
+                match count {
+                    Ok(count) => self.run(|_, executed| executed >= count),
+                    Err(error) => error,
+                }
+            },
+            "next" | "n" => self.step_over(),
+            "continue" | "c" => self.run(|_, _| false),
+            "registers" | "r" => self.registers(),
+            "memory" | "x" => self.memory(&argument),
+            "stack" => self.stack(),
+            "watch" | "w" => self.watch(&argument),
+            "unwatch" => match self.location(&argument) {
+                Ok(address) if self.watches.remove(&address).is_some() => format!("No longer watching {}.", self.describe(address)),
+                Ok(address) => format!("{} isn't watched.", self.describe(address)),
+                Err(error) => error,
+            },
+            "disassemble" | "dis" => {
+                let program = &self.emulator.memory[..self.program_length];
+                Disassembler::disassemble_with_labels(program, self.emulator.isa(), &self.labels).trim_end().to_string()
+            },
+            "symbols" => self.list_symbols(),
+            "help" | "h" => HELP.to_string(),
+            "quit" | "q" => return None,
+            _ => format!("Unknown command {}, try help.", name),
+        };
+        Some(output)
+    }
+
+    /// Where the program currently is and the instruction that runs next.
+    pub fn current(&self) -> String {
+        let address = self.emulator.program_counter();
+        if self.emulator.halted {
+            return format!("Halted at {} after {} instructions.", self.describe(address), self.emulator.steps);
+        }
+        match self.emulator.isa().decode(&self.emulator.fetch(address)) {
+            Some((definition, operands)) => format!("{}: {}", self.describe(address), Disassembler::instruction_to_string(definition, operands, &self.labels)),
+            None => format!("{}: unknown instruction {:#04X}", self.describe(address), self.emulator.memory[address as usize]),
+        }
+    }
+
+    /// Steps until `done` returns true for the number of instructions executed so far or something makes it stop earlier.
+    fn run(&mut self, done: impl Fn(&Debugger, u64) -> bool) -> String {
+        let mut executed = 0;
+        let stop = loop {
+            if self.emulator.halted { break Some(Stop::Halted); }
+            if self.emulator.steps >= self.max_steps {
+                break Some(Stop::Fault(Fault::StepLimitReached { steps: self.emulator.steps }));
+            }
+            if let Err(fault) = self.emulator.step() { break Some(Stop::Fault(fault)); }
+            executed += 1;
+            if let Some(watch) = self.changed_watch() { break Some(watch); }
+            if self.emulator.halted { break Some(Stop::Halted); }
+            if done(self, executed) { break None; }
+            if self.breakpoints.contains(&self.emulator.program_counter()) { break Some(Stop::Breakpoint); }
+        };
+        let mut output = match stop {
+            Some(Stop::Breakpoint) => "Breakpoint reached.\n".to_string(),
+            Some(Stop::Watch { address, old, new }) => format!("{} changed from {} to {}.\n", self.describe(address), old, new),
+            Some(Stop::Fault(fault)) => return format!("{}\n{}", fault, self.current()),
+            Some(Stop::Halted) | None => String::new(),
+        };
+        let output_text = String::from_utf8_lossy(&self.emulator.output);
+        if !output_text.is_empty() {
+            output += &format!("Output: {:?}\n", output_text);
+        }
+        output + &self.current()
+    }
+
+    /// Runs a call until it returns, anything else for a single instruction.
+    fn step_over(&mut self) -> String {
+        let Some(return_address) = Self::call_return_address(&self.emulator) else { return self.run(|_, executed| executed >= 1); };
+        let frame_ptr = self.emulator.frame_pointer();
+        // Recursive calls pass the return address as well, they are over once the frame is popped again
+        self.run(move |debugger, _| debugger.emulator.program_counter() == return_address && debugger.emulator.frame_pointer() == frame_ptr)
+    }
+
+    /// A `pushb` of the address right after a following `jmp` is a call, which returns to that address.
+    pub fn call_return_address(emulator: &Emulator) -> Option<u8> {
+        let address = emulator.program_counter();
+        let bytes = emulator.fetch(address);
+        let (definition, operands) = emulator.isa().decode(&bytes)?;
+        let next = address.wrapping_add(1 + operands.len() as u8);
+        let next_bytes = emulator.fetch(next);
+        let (jump, jump_operands) = emulator.isa().decode(&next_bytes)?;
+        let after_jump = next.wrapping_add(1 + jump_operands.len() as u8);
+        (definition.semantics() == Some("pushb") && jump.semantics() == Some("jmp") && operands.first() == Some(&after_jump)).then_some(after_jump)
+    }
+
+    fn add_breakpoint(&mut self, argument: &str) -> String {
+        match self.location(argument) {
+            Ok(address) => {
+                self.breakpoints.insert(address);
+                format!("Breakpoint at {}.", self.describe(address))
+            },
+            Err(error) => error,
+        }
+    }
+
+    fn delete_breakpoint(&mut self, argument: &str) -> String {
+        if argument.is_empty() {
+            self.breakpoints.clear();
+            return "Deleted all breakpoints.".to_string();
+        }
+        match self.location(argument) {
+            Ok(address) if self.breakpoints.remove(&address) => format!("Deleted the breakpoint at {}.", self.describe(address)),
+            Ok(address) => format!("There is no breakpoint at {}.", self.describe(address)),
+            Err(error) => error,
+        }
+    }
+
+    fn list_breakpoints(&self) -> String {
+        if self.breakpoints.is_empty() { return "No breakpoints.".to_string(); }
+        self.breakpoints.iter().map(|address| self.describe(*address)).collect::<Vec<String>>().join("\n")
+    }
+
+    fn registers(&self) -> String {
+        let mut output = String::new();
+        for (i, value) in self.emulator.registers.iter().enumerate() {
+            let register = i as u8 | 0b1000_0000;
+            let name = isa::register_name(register).map(|name| format!("r{} ({})", i, name)).unwrap_or_else(|| format!("r{}", i));
+            output += &format!("{:<16}{:#04X} {}\n", name, value, value);
+        }
+        let flags = self.emulator.registers[(isa::FLAGS_REGISTER & 0b0111_1111) as usize];
+        let set: Vec<&str> = [(ZERO_FLAG, "zero"), (CARRY_FLAG, "carry")].iter().filter(|(flag, _)| flags & flag != 0).map(|(_, name)| *name).collect();
+        output += &format!("flags: {}", if set.is_empty() { "none".to_string() } else { set.join(" ") });
+        output
+    }
+
+    fn memory(&self, argument: &str) -> String {
+        let mut arguments = argument.rsplitn(2, ' ');
+        // The count is optional, so a single argument is the location
+        let (location, count) = match (arguments.next(), arguments.next()) {
+            (Some(count), Some(location)) if count.parse::<usize>().is_ok() => (location, count.parse().unwrap_or_default()),
+            _ => (argument, 16),
+        };
+        match self.location(location) {
+            Ok(start) => self.dump(start as usize, (start as usize + count).min(MEMORY_SIZE)),
+            Err(error) => error,
+        }
+    }
+
+    fn stack(&self) -> String {
+        let frame_ptr = self.emulator.frame_pointer();
+        // frame_ptr starts at 0, the first push moves it to the end of memory
+        if frame_ptr == 0 { return "The stack is empty.".to_string(); }
+        self.dump(frame_ptr as usize, MEMORY_SIZE)
+    }
+
+    /// Prints the bytes from start to end, 8 per line.
+    fn dump(&self, start: usize, end: usize) -> String {
+        (start..end).step_by(8)
+            .map(|line_start| {
+                let bytes = &self.emulator.memory[line_start..end.min(line_start + 8)];
+                let hex = bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+                format!("{}: {}", self.describe(line_start as u8), hex)
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn watch(&mut self, argument: &str) -> String {
+        match self.location(argument) {
+            Ok(address) => {
+                let value = self.emulator.memory[address as usize];
+                self.watches.insert(address, value);
+                format!("Watching {}, currently {}.", self.describe(address), value)
+            },
+            Err(error) => error,
+        }
+    }
+
+    /// The first watched address whose value changed, remembering the new values.
+    fn changed_watch(&mut self) -> Option<Stop> {
+        let memory = &self.emulator.memory;
+        let mut changed = None;
+        for (address, old) in self.watches.iter_mut() {
+            let new = memory[*address as usize];
+            if new != *old {
+                changed = changed.or(Some(Stop::Watch { address: *address, old: *old, new }));
+                *old = new;
+            }
+        }
+        changed
+    }
+
+    fn list_symbols(&self) -> String {
+        let symbols: Vec<String> = self.symbols.iter()
+            .filter(|symbol| symbol.get_name() != "data_offset")
+            .map(|symbol| format!("{:<16}{}", symbol.get_name(), symbol.get_value()))
+            .collect();
+        if symbols.is_empty() { return "No symbols.".to_string(); }
+        symbols.join("\n")
+    }
+
+    /// Evaluates an address like `loop`, `0x0A` or `msg + 1`.
+    fn location(&self, text: &str) -> Result<u8, String> {
+        if text.is_empty() { return Err("A location is missing.".to_string()); }
+        // Addresses are printed as 0x.., so they can be typed back in like that besides the usual x..
+        let text = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            Some(digits) => format!("x{}", digits),
+            None => text.to_string(),
+        };
+        let span = Span::default();
+        let value = Expression::parse(&text, span)
+            .and_then(|expression| expression.evaluate(&self.symbols, &text, span))
+            .map_err(|error| error.to_string())?;
+        u8::try_from(value).map_err(|_| format!("{} is outside of memory.", value))
+    }
+
+    /// The address in hex with the closest label before it, e.g. `0x05 <loop+2>`.
+    fn describe(&self, address: u8) -> String {
+        let label = self.labels.range(..=address as usize).next_back()
+            .filter(|_| (address as usize) < self.text_length)
+            .map(|(start, name)| match address as usize - start {
+                0 => format!(" <{}>", name),
+                offset => format!(" <{}+{}>", name, offset),
+            });
+        format!("{:#04X}{}", address, label.unwrap_or_default())
+    }
+}
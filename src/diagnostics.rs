@@ -0,0 +1,90 @@
+use colored::{Color, Colorize};
+use crate::error::{AssembleError, AssembleWarning};
+use crate::source::{SourceMap, Span};
+
+/// Collects every error and warning of a run so they can be reported together at the end.
+#[derive(Default)]
+pub struct Diagnostics {
+    errors: Vec<AssembleError>,
+    warnings: Vec<AssembleWarning>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics{errors: Vec::new(), warnings: Vec::new()}
+    }
+
+    pub fn error(&mut self, error: AssembleError) { self.errors.push(error); }
+    pub fn warning(&mut self, warning: AssembleWarning) { self.warnings.push(warning); }
+
+    pub fn has_errors(&self) -> bool { !self.errors.is_empty() }
+    pub fn get_errors(&self) -> Vec<AssembleError> { self.errors.clone() }
+    pub fn get_warnings(&self) -> Vec<AssembleWarning> { self.warnings.clone() }
+
+    /// Prints all warnings and errors (sorted by their position) followed by a short summary.
+    pub fn report(&self, sources: &SourceMap) {
+        let mut warnings = self.warnings.clone();
+        warnings.sort_by_key(|warning| warning.span().map(|span| (span.file, span.line, span.column)));
+        let mut errors = self.errors.clone();
+        errors.sort_by_key(|error| error.span().map(|span| (span.file, span.line, span.column)));
+
+        for warning in warnings.iter() {
+            println!("{}", Self::render("warning", Color::Yellow, &warning.to_string(), warning.span(), &warning.label(), sources));
+            for (span, note) in warning.span().map(|span| sources.origin_notes(span)).unwrap_or_default() {
+                println!("{}", Self::render("note", Color::Cyan, &note, Some(span), "", sources));
+            }
+        }
+        for error in errors.iter() {
+            println!("{}", Self::render("error", Color::Red, &error.to_string(), error.span(), &error.label(), sources));
+            // Errors in expanded macros also point at the macro body and the call, errors in included files at the .include
+            let origin_notes = error.span().map(|span| sources.origin_notes(span)).unwrap_or_default();
+            for (span, note) in origin_notes.into_iter().chain(error.notes()) {
+                println!("{}", Self::render("note", Color::Cyan, &note, Some(span), "", sources));
+            }
+        }
+        if self.errors.is_empty() && self.warnings.is_empty() { return; }
+
+        let summary = format!("{} error(s), {} warning(s) emitted.", self.errors.len(), self.warnings.len());
+        if self.has_errors() {
+            println!("{}", summary.red());
+        } else {
+            println!("{}", summary.yellow());
+        }
+    }
+
+    /// Renders a message the way rustc does, with the offending line and the span underlined:
+    /// ```text
+    /// error: Unknown instruction foo.
+    ///  --> main.asm:5:1
+    ///   |
+    /// 5 | foo r1 r2
+    ///   | ^^^ unknown instruction
+    /// ```
+    pub fn render(severity: &str, color: Color, message: &str, span: Option<Span>, label: &str, sources: &SourceMap) -> String {
+        let mut output = format!("{}{}\n", format!("{}: ", severity).color(color).bold(), message.bold());
+        let Some(span) = span else { return output; };
+
+        let line_number = span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        output += &format!("{}{} {}:{}:{}\n", gutter, "-->".blue().bold(), sources.get_name(span.file), span.line, span.column);
+        let Some(line) = sources.get_line(span) else { return output; };
+
+        // Tabs are expanded so the underline stays below the span
+        let expand = |text: &str| text.replace('\t', "    ");
+        let prefix: String = line.chars().take(span.column.saturating_sub(1) as usize).collect();
+        let marked: String = line.chars().skip(span.column.saturating_sub(1) as usize).take(span.length as usize).collect();
+        let underline = "^".repeat(expand(&marked).chars().count().max(1));
+
+        output += &format!("{} {}\n", gutter, "|".blue().bold());
+        output += &format!("{} {} {}\n", line_number.blue().bold(), "|".blue().bold(), expand(line));
+        output += &format!("{} {} {}{}\n", gutter, "|".blue().bold(), " ".repeat(expand(&prefix).chars().count()), format!("{} {}", underline, label).color(color).bold());
+        output
+    }
+}
+
+/// A run that ended with a single error, like a bad option.
+impl From<AssembleError> for Diagnostics {
+    fn from(error: AssembleError) -> Diagnostics {
+        Diagnostics{errors: vec![error], warnings: Vec::new()}
+    }
+}
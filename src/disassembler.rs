@@ -0,0 +1,129 @@
+use std::collections::BTreeMap;
+use crate::isa;
+use crate::isa::{InstructionDefinition, Isa, OperandKind};
+
+/// Turns binaries back into assembly.
+pub struct Disassembler;
+
+/// A decoded instruction at its address.
+struct Decoded<'a> {
+    address: usize,
+    definition: &'a InstructionDefinition,
+    operands: Vec<u8>,
+}
+
+impl Disassembler {
+    /// Decodes everything that is reachable from address 0 as instructions, following jumps and branches.
+    /// Bytes that are never reached (usually the data section) or can't be decoded are printed as `.8b`.
+    pub fn disassemble(binary: &[u8], isa: &Isa) -> String {
+        Self::disassemble_with_labels(binary, isa, &BTreeMap::new())
+    }
+
+    /// Like `disassemble`, but known labels are used instead of made up ones.
+    pub fn disassemble_with_labels(binary: &[u8], isa: &Isa, known_labels: &BTreeMap<usize, String>) -> String {
+        let instructions = Self::reachable_instructions(binary, isa);
+        let is_instruction = |address: &usize| instructions.iter().any(|instruction| instruction.address == *address);
+        let mut labels: BTreeMap<usize, String> = instructions.iter()
+            .filter_map(|instruction| Self::jump_target(instruction.definition, &instruction.operands))
+            .filter(is_instruction)
+            .map(|address| (address, Self::label(address)))
+            .collect();
+        labels.extend(known_labels.iter().filter(|(address, _)| is_instruction(address)).map(|(address, name)| (*address, name.clone())));
+
+        let mut output = "<text>\n".to_string();
+        let mut address = 0;
+        let mut instructions = instructions.iter().peekable();
+        while address < binary.len() {
+            match instructions.next_if(|instruction| instruction.address == address) {
+                Some(instruction) => {
+                    if let Some(label) = labels.get(&address) {
+                        output += &format!("{}:\n", label);
+                    }
+                    let length = 1 + instruction.operands.len();
+                    let text = Self::instruction_to_string(instruction.definition, &instruction.operands, &labels);
+                    output += &format!("    {:<24}# {:02X}: {}\n", text, address, Self::hex(&binary[address..address + length]));
+                    address += length;
+                },
+                None => {
+                    // Everything up to the next instruction is data
+                    let end = instructions.peek().map(|instruction| instruction.address).unwrap_or(binary.len());
+                    for chunk_start in (address..end).step_by(8) {
+                        let chunk = &binary[chunk_start..end.min(chunk_start + 8)];
+                        let values = chunk.iter().map(|byte| format!("x{:02X}", byte)).collect::<Vec<String>>().join(" ");
+                        output += &format!("    {:<24}# {:02X}: {}\n", format!(".8b {}", values), chunk_start, Self::hex(chunk));
+                    }
+                    address = end;
+                },
+            }
+        }
+        output
+    }
+
+    /// Walks the control flow from address 0 and returns the instructions found, sorted by address.
+    fn reachable_instructions<'a>(binary: &[u8], isa: &'a Isa) -> Vec<Decoded<'a>> {
+        let mut instructions: Vec<Decoded> = Vec::new();
+        let mut is_code = vec![false; binary.len()];
+        let mut pending = vec![0];
+        while let Some(address) = pending.pop() {
+            if address >= binary.len() || is_code[address] { continue; }
+            let Some((definition, operands)) = isa.decode(&binary[address..]) else { continue; };
+            let length = 1 + operands.len();
+            // An instruction can't start in the middle of another one
+            if is_code[address..address + length].iter().any(|code| *code) { continue; }
+            is_code[address..address + length].iter_mut().for_each(|code| *code = true);
+
+            if let Some(target) = Self::jump_target(definition, operands) {
+                pending.push(target);
+            }
+            if !matches!(definition.semantics(), Some("halt" | "jmp")) {
+                pending.push(address + length);
+            }
+            instructions.push(Decoded{address, definition, operands: operands.to_vec()});
+        }
+        instructions.sort_by_key(|instruction| instruction.address);
+        instructions
+    }
+
+    /// Which operand of a jmp or jmpz is the address it continues at, going by what the instruction does and not its name.
+    fn jump_operand(definition: &InstructionDefinition) -> Option<usize> {
+        match definition.semantics()? {
+            "jmp" => Some(0),
+            "jmpz" => Some(1),
+            _ => None,
+        }
+    }
+
+    /// The address a jmp or jmpz continues at, if it is a number and not a register.
+    pub fn jump_target(definition: &InstructionDefinition, operands: &[u8]) -> Option<usize> {
+        let target = operands.get(Self::jump_operand(definition)?)?;
+        if target & 0b1000_0000 != 0 { return None; }
+        Some(*target as usize)
+    }
+
+    /// The instruction as it would be written in the source, with jump targets replaced by their label.
+    pub fn instruction_to_string(definition: &InstructionDefinition, operands: &[u8], labels: &BTreeMap<usize, String>) -> String {
+        let mut text = definition.mnemonic.clone();
+        let jump_operand = Self::jump_operand(definition);
+        for (i, (kind, operand)) in definition.operands.iter().zip(operands).enumerate() {
+            if let OperandKind::Implied(_) = kind { continue; }
+            let label = labels.get(&(*operand as usize)).filter(|_| jump_operand == Some(i));
+            let rendered = if operand & 0b1000_0000 != 0 {
+                isa::register_name(*operand).map(str::to_string).unwrap_or_else(|| format!("r{}", operand & 0b0111_1111))
+            } else if let Some(label) = label {
+                label.clone()
+            } else {
+                operand.to_string()
+            };
+            text += &format!(" {}", rendered);
+        }
+        text
+    }
+
+    fn label(address: usize) -> String {
+        format!("label_{:02X}", address)
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+use crate::isa::{Isa, EXEC_PTR_REGISTER, FLAGS_REGISTER, FRAME_PTR_REGISTER};
+
+/// Bytes of memory, addresses are 8 bit wide.
+pub const MEMORY_SIZE: usize = 256;
+/// Registers r0 to r15.
+pub const REGISTER_COUNT: usize = 16;
+/// Set in the flags register if the last ALU operation resulted in 0.
+pub const ZERO_FLAG: u8 = 0b0000_0001;
+/// Set in the flags register if the last ALU operation didn't fit into 8 bits (or had to borrow).
+pub const CARRY_FLAG: u8 = 0b0000_0010;
+
+/// Why the emulator had to stop before reaching halt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fault {
+    /// The binary doesn't fit into memory.
+    ProgramTooBig { size: usize },
+    /// The bytes at the address aren't an instruction of the ISA.
+    UnknownInstruction { address: u8, opcode: u8 },
+    /// The instruction exists in the ISA, but no built-in instruction has its opcode and length, so it has no known effect.
+    UnsupportedInstruction { address: u8, mnemonic: String },
+    /// An operand refers to a register above r15.
+    NoSuchRegister { address: u8, register: u8 },
+    /// A number was given where the instruction writes to a register.
+    ExpectedRegister { address: u8, operand: u8 },
+    DivisionByZero { address: u8 },
+    /// The program ran for longer than allowed, it most likely loops forever.
+    StepLimitReached { steps: u64 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::ProgramTooBig { size } => write!(f, "The program has {} bytes, but the memory only holds {}.", size, MEMORY_SIZE),
+            Fault::UnknownInstruction { address, opcode } => write!(f, "Unknown instruction {:#04X} at address {:#04X}.", opcode, address),
+            Fault::UnsupportedInstruction { address, mnemonic } => write!(f, "Instruction {} at address {:#04X} can't be emulated.", mnemonic, address),
+            Fault::NoSuchRegister { address, register } => write!(f, "Register r{} used at address {:#04X} doesn't exist.", register & 0b0111_1111, address),
+            Fault::ExpectedRegister { address, operand } => write!(f, "Instruction at address {:#04X} writes to {}, which isn't a register.", address, operand),
+            Fault::DivisionByZero { address } => write!(f, "Division by zero at address {:#04X}.", address),
+            Fault::StepLimitReached { steps } => write!(f, "Stopped after {} instructions without reaching halt.", steps),
+        }
+    }
+}
+
+/// Runs binaries instruction by instruction. The program is loaded at address 0, where execution starts.
+/// The stack grows down from the end of memory, frame_ptr points at the last pushed byte.
+#[derive(Debug, Clone)]
+pub struct Emulator {
+    pub registers: [u8; REGISTER_COUNT],
+    pub memory: [u8; MEMORY_SIZE],
+    /// Everything written with sow since the last soc.
+    pub output: Vec<u8>,
+    pub halted: bool,
+    /// Instructions executed so far.
+    pub steps: u64,
+    isa: Isa,
+}
+
+impl Emulator {
+    pub fn new(binary: &[u8], isa: Isa) -> Result<Emulator, Fault> {
+        if binary.len() > MEMORY_SIZE {
+            return Err(Fault::ProgramTooBig { size: binary.len() });
+        }
+        let mut memory = [0; MEMORY_SIZE];
+        memory[..binary.len()].copy_from_slice(binary);
+        Ok(Emulator{registers: [0; REGISTER_COUNT], memory, output: Vec::new(), halted: false, steps: 0, isa})
+    }
+
+    /// The address of the next instruction.
+    pub fn program_counter(&self) -> u8 {
+        self.registers[Self::index(EXEC_PTR_REGISTER)]
+    }
+
+    /// The address of the last byte pushed onto the stack.
+    pub fn frame_pointer(&self) -> u8 {
+        self.registers[Self::index(FRAME_PTR_REGISTER)]
+    }
+
+    /// Executes a single instruction. Does nothing once the program halted.
+    pub fn step(&mut self) -> Result<(), Fault> {
+        if self.halted { return Ok(()); }
+        let address = self.program_counter();
+        let bytes = self.fetch(address);
+        let Some((definition, operands)) = self.isa.decode(&bytes) else {
+            return Err(Fault::UnknownInstruction { address, opcode: bytes[0] });
+        };
+        // Instructions do what the hardware does with their opcode, even if an ISA file renamed them
+        let Some(semantics) = definition.semantics() else {
+            return Err(Fault::UnsupportedInstruction { address, mnemonic: definition.mnemonic.clone() });
+        };
+        let operands = operands.to_vec();
+        self.registers[Self::index(EXEC_PTR_REGISTER)] = address.wrapping_add(1 + operands.len() as u8);
+
+        let first = operands.first().copied().unwrap_or_default();
+        let second = operands.get(1).copied().unwrap_or_default();
+        match semantics {
+            "halt" => self.halted = true,
+            "sow" => {
+                let value = self.value(first, address)?;
+                self.output.push(value);
+            },
+            "soc" => self.output.clear(),
+            "add" => self.alu(first, second, address, |a, b| (a as u16) + (b as u16))?,
+            "sub" => self.alu(first, second, address, |a, b| (a as u16).wrapping_sub(b as u16))?,
+            "mul" => self.alu(first, second, address, |a, b| (a as u16) * (b as u16))?,
+            "div" | "mod" => {
+                if self.value(second, address)? == 0 { return Err(Fault::DivisionByZero { address }); }
+                match semantics {
+                    "div" => self.alu(first, second, address, |a, b| (a / b) as u16)?,
+                    _ => self.alu(first, second, address, |a, b| (a % b) as u16)?,
+                }
+            },
+            "mov" => {
+                let value = self.value(second, address)?;
+                self.set_register(first, value, address)?;
+            },
+            "jmp" => self.registers[Self::index(EXEC_PTR_REGISTER)] = self.value(first, address)?,
+            "jmpz" => {
+                if self.value(first, address)? == 0 {
+                    self.registers[Self::index(EXEC_PTR_REGISTER)] = self.value(second, address)?;
+                }
+            },
+            "ldb" => {
+                let value = self.memory[self.value(second, address)? as usize];
+                self.set_register(first, value, address)?;
+            },
+            "stb" => {
+                let value = self.value(first, address)?;
+                self.memory[self.value(second, address)? as usize] = value;
+            },
+            "pushb" => {
+                let value = self.value(first, address)?;
+                let frame_ptr = self.registers[Self::index(FRAME_PTR_REGISTER)].wrapping_sub(1);
+                self.registers[Self::index(FRAME_PTR_REGISTER)] = frame_ptr;
+                self.memory[frame_ptr as usize] = value;
+            },
+            "popb" => {
+                let frame_ptr = self.registers[Self::index(FRAME_PTR_REGISTER)];
+                self.set_register(first, self.memory[frame_ptr as usize], address)?;
+                self.registers[Self::index(FRAME_PTR_REGISTER)] = frame_ptr.wrapping_add(1);
+            },
+            _ => unreachable!("every built-in instruction is emulated"),
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    /// The bytes an instruction at the address could consist of.
+    pub fn fetch(&self, address: u8) -> Vec<u8> {
+        // Instructions may wrap around the end of memory like the program counter does
+        (0..8).map(|i| self.memory[address.wrapping_add(i) as usize]).collect()
+    }
+
+    pub fn isa(&self) -> &Isa {
+        &self.isa
+    }
+
+    /// Executes instructions until halt, a fault or the step limit.
+    pub fn run(&mut self, max_steps: u64) -> Result<(), Fault> {
+        while !self.halted {
+            if self.steps >= max_steps {
+                return Err(Fault::StepLimitReached { steps: self.steps });
+            }
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Performs the operation on the register and the value, stores the lowest 8 bits in the register and updates the flags.
+    fn alu(&mut self, register: u8, operand: u8, address: u8, operation: fn(u8, u8) -> u16) -> Result<(), Fault> {
+        let result = operation(self.value(register, address)?, self.value(operand, address)?);
+        self.set_register(register, result as u8, address)?;
+        let mut flags = 0;
+        if result as u8 == 0 { flags |= ZERO_FLAG; }
+        if result > 0xFF { flags |= CARRY_FLAG; }
+        self.registers[Self::index(FLAGS_REGISTER)] = flags;
+        Ok(())
+    }
+
+    /// The content of the register if bit 7 is set, the number itself otherwise.
+    fn value(&self, operand: u8, address: u8) -> Result<u8, Fault> {
+        if operand & 0b1000_0000 == 0 { return Ok(operand); }
+        self.registers.get(Self::index(operand)).copied().ok_or(Fault::NoSuchRegister { address, register: operand })
+    }
+
+    fn set_register(&mut self, operand: u8, value: u8, address: u8) -> Result<(), Fault> {
+        if operand & 0b1000_0000 == 0 { return Err(Fault::ExpectedRegister { address, operand }); }
+        let register = self.registers.get_mut(Self::index(operand)).ok_or(Fault::NoSuchRegister { address, register: operand })?;
+        *register = value;
+        Ok(())
+    }
+
+    fn index(register: u8) -> usize {
+        (register & 0b0111_1111) as usize
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use crate::source::Span;
+
+/// Everything that can go wrong while turning source code into a binary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssembleError {
+    /// The first word of a text line isn't a known instruction.
+    UnknownMnemonic { mnemonic: String, span: Span },
+    /// The instruction exists but was given the wrong amount of operands.
+    WrongOperandCount { mnemonic: String, found: usize, span: Span },
+    /// An operand couldn't be understood at all (unknown number system, missing value, ...).
+    BadOperand { operand: String, reason: String, span: Span },
+    /// A literal was understood but doesn't fit where it is used.
+    LiteralOutOfRange { literal: String, min: i64, max: i64, span: Span },
+    /// A name was used that was never declared as label, constant or data.
+    UndefinedSymbol { name: String, span: Span },
+    /// A name was declared a second time.
+    SymbolRedeclared { name: String, span: Span, previous: Span },
+    /// A name was declared that reads like a register or number (e.g. r1 or x1F), so it could never be used.
+    SymbolShadowsLiteral { name: String, span: Span },
+    /// A data declaration used a type other than 8b, 16b, 32b, str or char.
+    BadDataType { data_type: String, span: Span },
+    /// A constant, label or data declaration is malformed.
+    BadDeclaration { declaration: String, reason: String, span: Span },
+    /// A bracketed expression couldn't be evaluated.
+    BadExpression { expression: String, reason: String, span: Span },
+    /// A directive like `.macro` or `.endm` is malformed or misplaced.
+    BadDirective { directive: String, reason: String, span: Span },
+    /// A macro was used with another number of arguments than it declares.
+    MacroArgumentCount { name: String, expected: usize, found: usize, span: Span },
+    /// Macros kept expanding each other past the limit, most likely forever.
+    MacroRecursion { name: String, limit: usize, span: Span },
+    /// The file of an `.include` couldn't be found or read, or is already being included.
+    BadInclude { path: String, reason: String, span: Span },
+    /// The source couldn't be read or the output couldn't be written.
+    Io { path: String, reason: String },
+    /// The ISA description given with --isa couldn't be read or is inconsistent.
+    BadIsa { path: String, reason: String },
+    /// A command line option has a value that can't be used.
+    BadOption { option: String, reason: String },
+    /// The command line doesn't name exactly one file to work on.
+    BadInputs { inputs: Vec<String> },
+    /// A hex or S-record image couldn't be read.
+    BadImage { path: String, reason: String },
+    /// The program doesn't fit into the memory given with `--depth`.
+    ImageTooLarge { image: String, size: usize, depth: usize },
+    /// A file given to the linker isn't an object file written by `--object` or an archive written by `ar`.
+    BadObject { path: String, reason: String },
+    /// A module uses an `.extern` symbol that no module declares `.global`.
+    UndefinedExternal { name: String, module: String },
+    /// Two modules declare the same symbol `.global`.
+    DuplicateGlobal { name: String, module: String, previous: String },
+    /// A value of a module couldn't be filled in while linking.
+    BadRelocation { module: String, line: u32, reason: String },
+}
+
+impl AssembleError {
+    /// Where the error happened, if it happened inside the source code.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            AssembleError::UnknownMnemonic { span, .. } | AssembleError::WrongOperandCount { span, .. } |
+            AssembleError::BadOperand { span, .. } | AssembleError::LiteralOutOfRange { span, .. } |
+            AssembleError::UndefinedSymbol { span, .. } | AssembleError::BadDataType { span, .. } |
+            AssembleError::SymbolRedeclared { span, .. } | AssembleError::SymbolShadowsLiteral { span, .. } |
+            AssembleError::BadDeclaration { span, .. } | AssembleError::BadExpression { span, .. } |
+            AssembleError::BadDirective { span, .. } | AssembleError::MacroArgumentCount { span, .. } |
+            AssembleError::MacroRecursion { span, .. } | AssembleError::BadInclude { span, .. } => Some(*span),
+            AssembleError::Io { .. } | AssembleError::BadIsa { .. } | AssembleError::BadOption { .. } | AssembleError::BadInputs { .. } | AssembleError::BadImage { .. } | AssembleError::ImageTooLarge { .. } |
+            AssembleError::BadObject { .. } | AssembleError::UndefinedExternal { .. } | AssembleError::DuplicateGlobal { .. } | AssembleError::BadRelocation { .. } => None,
+        }
+    }
+
+    /// A short text shown next to the underlined source.
+    pub fn label(&self) -> String {
+        match self {
+            AssembleError::UnknownMnemonic { .. } => "unknown instruction".to_string(),
+            AssembleError::WrongOperandCount { found, .. } => format!("{} operand(s) found", found),
+            AssembleError::BadOperand { reason, .. } => reason.clone(),
+            AssembleError::LiteralOutOfRange { min, max, .. } => format!("not within {}...{}", min, max),
+            AssembleError::UndefinedSymbol { .. } => "not declared".to_string(),
+            AssembleError::SymbolRedeclared { .. } => "declared again".to_string(),
+            AssembleError::SymbolShadowsLiteral { .. } => "reads like a register or number".to_string(),
+            AssembleError::BadDataType { .. } => "expected 8b, 16b, 32b, str or char".to_string(),
+            AssembleError::BadDeclaration { .. } => "malformed declaration".to_string(),
+            AssembleError::BadExpression { reason, .. } => reason.clone(),
+            AssembleError::BadDirective { .. } => "malformed directive".to_string(),
+            AssembleError::MacroArgumentCount { found, .. } => format!("{} argument(s) given", found),
+            AssembleError::MacroRecursion { .. } => "expands without end".to_string(),
+            AssembleError::BadInclude { .. } => "can't be included".to_string(),
+            AssembleError::Io { reason, .. } | AssembleError::BadIsa { reason, .. } |
+            AssembleError::BadOption { reason, .. } | AssembleError::BadImage { reason, .. } |
+            AssembleError::BadObject { reason, .. } | AssembleError::BadRelocation { reason, .. } => reason.clone(),
+            AssembleError::BadInputs { .. } => "not exactly one file".to_string(),
+            AssembleError::ImageTooLarge { depth, .. } => format!("more than {} words", depth),
+            AssembleError::UndefinedExternal { .. } => "not declared .global".to_string(),
+            AssembleError::DuplicateGlobal { .. } => "declared .global again".to_string(),
+        }
+    }
+
+    /// The same error reported at another place, e.g. where a constant with a broken value is used.
+    pub fn at(mut self, new_span: Span) -> AssembleError {
+        match &mut self {
+            AssembleError::UnknownMnemonic { span, .. } | AssembleError::WrongOperandCount { span, .. } |
+            AssembleError::BadOperand { span, .. } | AssembleError::LiteralOutOfRange { span, .. } |
+            AssembleError::UndefinedSymbol { span, .. } | AssembleError::BadDataType { span, .. } |
+            AssembleError::SymbolRedeclared { span, .. } | AssembleError::SymbolShadowsLiteral { span, .. } |
+            AssembleError::BadDeclaration { span, .. } | AssembleError::BadExpression { span, .. } |
+            AssembleError::BadDirective { span, .. } | AssembleError::MacroArgumentCount { span, .. } |
+            AssembleError::MacroRecursion { span, .. } | AssembleError::BadInclude { span, .. } => *span = new_span,
+            AssembleError::Io { .. } | AssembleError::BadIsa { .. } | AssembleError::BadOption { .. } | AssembleError::BadInputs { .. } | AssembleError::BadImage { .. } | AssembleError::ImageTooLarge { .. } |
+            AssembleError::BadObject { .. } | AssembleError::UndefinedExternal { .. } | AssembleError::DuplicateGlobal { .. } | AssembleError::BadRelocation { .. } => {},
+        }
+        self
+    }
+
+    /// Other places in the source that help to understand the error.
+    pub fn notes(&self) -> Vec<(Span, String)> {
+        match self {
+            AssembleError::SymbolRedeclared { previous, .. } => vec![(*previous, "first declared here".to_string())],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssembleError::UnknownMnemonic { mnemonic, .. } => write!(f, "Unknown instruction {}.", mnemonic),
+            AssembleError::WrongOperandCount { mnemonic, found, .. } => write!(f, "Instruction {} doesn't take {} operand(s).", mnemonic, found),
+            AssembleError::BadOperand { operand, reason, .. } => write!(f, "Argument {} {}.", operand, reason),
+            AssembleError::LiteralOutOfRange { literal, min, max, .. } => {
+                write!(f, "Argument {} should be within range {}...{}.", literal, min, max)?;
+                // Only instruction operands are limited to 7 bits, the data section can hold bigger values
+                if *max == 127 { write!(f, " Please define it in the data section instead.")?; }
+                Ok(())
+            },
+            AssembleError::UndefinedSymbol { name, .. } => write!(f, "Symbol {} was never declared.", name),
+            AssembleError::SymbolRedeclared { name, .. } => write!(f, "Symbol {} is declared more than once.", name),
+            AssembleError::SymbolShadowsLiteral { name, .. } => write!(f, "Symbol {} can't be declared because it would hide a register or number.", name),
+            AssembleError::BadDataType { data_type, .. } => write!(f, "Unknown data type {}.", data_type),
+            AssembleError::BadDeclaration { reason, .. } => write!(f, "{}", reason),
+            AssembleError::BadExpression { expression, reason, .. } => write!(f, "Couldn't resolve [{}]: {}.", expression, reason),
+            AssembleError::BadDirective { reason, .. } => write!(f, "{}", reason),
+            AssembleError::MacroArgumentCount { name, expected, found, .. } => write!(f, "Macro {} takes {} argument(s) but {} were given.", name, expected, found),
+            AssembleError::MacroRecursion { name, limit, .. } => write!(f, "Macro {} is nested more than {} expansions deep.", name, limit),
+            AssembleError::BadInclude { path, reason, .. } => write!(f, "Couldn't include {}: {}.", path, reason),
+            AssembleError::Io { path, reason } => write!(f, "Couldn't access {}: {}", path, reason),
+            AssembleError::BadIsa { path, reason } => write!(f, "Couldn't load the ISA from {}: {}.", path, reason),
+            AssembleError::BadOption { option, reason } => write!(f, "Option {} {}.", option, reason),
+            AssembleError::BadInputs { inputs } if inputs.is_empty() => write!(f, "Expected a file to work on, like sriscasm path/to/file.asm."),
+            AssembleError::BadInputs { inputs } => write!(f, "Expected one file to work on but got {}.", inputs.join(", ")),
+            AssembleError::BadImage { path, reason } => write!(f, "Couldn't read the image {}: {}.", path, reason),
+            AssembleError::ImageTooLarge { image, size, depth } => write!(f, "The {} needs {} words but the memory only holds {}.", image, size, depth),
+            AssembleError::BadObject { path, reason } => write!(f, "Couldn't read the object file or archive {}: {}.", path, reason),
+            AssembleError::UndefinedExternal { name, module } => write!(f, "Symbol {} is used by {} but no module declares it .global.", name, module),
+            AssembleError::DuplicateGlobal { name, module, previous } => write!(f, "Symbol {} is declared .global by both {} and {}.", name, previous, module),
+            AssembleError::BadRelocation { module, line, reason } => write!(f, "Couldn't link line {} of {}: {}", line, module, reason),
+        }
+    }
+}
+
+/// Things that assemble fine but are most likely not what was meant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AssembleWarning {
+    /// Lines before the first <text> or <data> marker are never assembled.
+    LineOutsideSection { content: String, span: Span },
+    /// The last instruction neither halts nor jumps, so the CPU would run into the data section.
+    MissingHalt { last_instruction: String, span: Span },
+}
+
+impl AssembleWarning {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            AssembleWarning::LineOutsideSection { span, .. } | AssembleWarning::MissingHalt { span, .. } => Some(*span),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            AssembleWarning::LineOutsideSection { .. } => "ignored".to_string(),
+            AssembleWarning::MissingHalt { .. } => "last instruction".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AssembleWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssembleWarning::LineOutsideSection { content, .. } => write!(f, "Line {} isn't inside a <text> or <data> section and is ignored.", content),
+            AssembleWarning::MissingHalt { last_instruction, .. } => write!(f, "The text section ends with {} instead of halt or jmp, so execution would continue into the data section.", last_instruction),
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+use crate::emulator::{Emulator, Fault, MEMORY_SIZE, REGISTER_COUNT};
+use crate::isa;
+
+/// Signals reported to gdb when execution stops.
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGILL: u8 = 4;
+const SIGFPE: u8 = 8;
+const SIGSEGV: u8 = 11;
+const SIGXCPU: u8 = 24;
+/// How many instructions `continue` executes between two checks whether gdb wants to interrupt it.
+const INTERRUPT_INTERVAL: u64 = 1000;
+
+/// The architecture named in the target description. gdb has no port for it, so it warns that it doesn't know it
+/// and uses the registers of the description; a gdb with a port registered under this name picks it on its own.
+const ARCHITECTURE: &str = "stupid-risc";
+
+/// The registers as gdb sees them: r0 to r15 with one byte each, exec_ptr is the program counter.
+fn target_description() -> String {
+    let mut registers = String::new();
+    for register in 0..REGISTER_COUNT as u8 {
+        let name = isa::register_name(register | 0b1000_0000).map(str::to_string).unwrap_or_else(|| format!("r{}", register));
+        let kind = match register | 0b1000_0000 {
+            isa::EXEC_PTR_REGISTER => "code_ptr",
+            isa::FRAME_PTR_REGISTER => "data_ptr",
+            _ => "uint8",
+        };
+        registers += &format!("<reg name=\"{}\" bitsize=\"8\" type=\"{}\"/>", name, kind);
+    }
+    format!("<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\"><target><architecture>{}</architecture><feature name=\"org.stupid-risc.core\">{}</feature></target>", ARCHITECTURE, registers)
+}
+
+/// Lets gdb control the emulator over the GDB Remote Serial Protocol.
+pub struct GdbStub {
+    pub emulator: Emulator,
+    breakpoints: BTreeSet<u8>,
+    max_steps: u64,
+    /// Tells whether gdb sent an interrupt (Ctrl-C) while the program runs.
+    interrupted: Option<Box<dyn FnMut() -> bool>>,
+    /// Set once gdb killed or detached from the program.
+    pub finished: bool,
+}
+
+impl GdbStub {
+    pub fn new(emulator: Emulator, max_steps: u64) -> GdbStub {
+        GdbStub{emulator, breakpoints: BTreeSet::new(), max_steps, interrupted: None, finished: false}
+    }
+
+    /// Checks for an interrupt every now and then during `continue`, which stops the program with SIGINT.
+    pub fn with_interrupt(mut self, interrupted: impl FnMut() -> bool + 'static) -> GdbStub {
+        self.interrupted = Some(Box::new(interrupted));
+        self
+    }
+
+    /// Answers packets until gdb kills or detaches from the program or closes the connection.
+    pub fn serve(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> std::io::Result<()> {
+        while let Some(packet) = Self::read_packet(reader, writer)? {
+            if let Some(reply) = self.handle_packet(&packet) {
+                writer.write_all(Self::frame(&reply).as_bytes())?;
+            }
+            writer.flush()?;
+            if self.finished { break; }
+        }
+        Ok(())
+    }
+
+    /// Reads the next `$data#checksum` packet and acknowledges it. Packets with a wrong checksum are
+    /// rejected with `-`, so gdb sends them again. `None` means the connection was closed.
+    pub fn read_packet(reader: &mut impl BufRead, writer: &mut impl Write) -> std::io::Result<Option<String>> {
+        loop {
+            let mut skipped = Vec::new();
+            // Acknowledgements and interrupts between packets don't need an answer
+            if reader.read_until(b'$', &mut skipped)? == 0 || skipped.last() != Some(&b'$') { return Ok(None); }
+            let mut packet = Vec::new();
+            reader.read_until(b'#', &mut packet)?;
+            if packet.pop() != Some(b'#') { return Ok(None); }
+            let mut checksum = [0; 2];
+            reader.read_exact(&mut checksum)?;
+            let expected = std::str::from_utf8(&checksum).ok().and_then(|text| u8::from_str_radix(text, 16).ok());
+            if expected == Some(Self::checksum(&packet)) {
+                writer.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&packet).to_string()));
+            }
+            writer.write_all(b"-")?;
+            writer.flush()?;
+        }
+    }
+
+    /// Wraps the data into a packet with its checksum.
+    pub fn frame(data: &str) -> String {
+        format!("${}#{:02x}", data, Self::checksum(data.as_bytes()))
+    }
+
+    fn checksum(data: &[u8]) -> u8 {
+        data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+    }
+
+    /// The reply to a packet without its framing. An empty reply tells gdb the packet isn't supported,
+    /// `None` means no reply is expected.
+    pub fn handle_packet(&mut self, packet: &str) -> Option<String> {
+        let (command, arguments) = packet.split_at(packet.chars().next().map(char::len_utf8).unwrap_or(0));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => Self::hex(&self.emulator.registers),
+            "G" => match Self::bytes(arguments) {
+                Some(values) if values.len() == REGISTER_COUNT => {
+                    self.emulator.registers.copy_from_slice(&values);
+                    "OK".to_string()
+                },
+                _ => "E01".to_string(),
+            },
+            "p" => match usize::from_str_radix(arguments, 16).ok().and_then(|register| self.emulator.registers.get(register)) {
+                Some(value) => Self::hex(&[*value]),
+                None => "E01".to_string(),
+            },
+            "P" => {
+                let written = arguments.split_once('=').and_then(|(register, value)| {
+                    let register = usize::from_str_radix(register, 16).ok().filter(|register| *register < REGISTER_COUNT)?;
+                    Some((register, *Self::bytes(value)?.first()?))
+                });
+                match written {
+                    Some((register, value)) => {
+                        self.emulator.registers[register] = value;
+                        "OK".to_string()
+                    },
+                    None => "E01".to_string(),
+                }
+            },
+            "m" => match Self::memory_range(arguments) {
+                Some((start, end)) => Self::hex(&self.emulator.memory[start..end]),
+                None => "E01".to_string(),
+            },
+            "M" => {
+                let written = arguments.split_once(':').and_then(|(range, data)| Some((Self::memory_range(range)?, Self::bytes(data)?)));
+                match written {
+                    Some(((start, end), data)) if data.len() == end - start => {
+                        self.emulator.memory[start..end].copy_from_slice(&data);
+                        "OK".to_string()
+                    },
+                    _ => "E01".to_string(),
+                }
+            },
+            "Z" | "z" => self.change_breakpoint(command == "Z", arguments),
+            "c" | "s" => {
+                if !arguments.is_empty() {
+                    match u8::from_str_radix(arguments, 16) {
+                        Ok(address) => self.emulator.registers[(isa::EXEC_PTR_REGISTER & 0b0111_1111) as usize] = address,
+                        Err(_) => return Some("E01".to_string()),
+                    }
+                }
+                self.resume(command == "s")
+            },
+            "H" => "OK".to_string(),
+            "k" => {
+                self.finished = true;
+                return None;
+            },
+            "D" => {
+                self.finished = true;
+                "OK".to_string()
+            },
+            "q" => self.query(arguments),
+            _ => String::new(),
+        };
+        Some(reply)
+    }
+
+    /// Answers the general queries gdb sends while connecting.
+    fn query(&self, query: &str) -> String {
+        if query.starts_with("Supported") {
+            return "PacketSize=1000;qXfer:features:read+".to_string();
+        }
+        if let Some(range) = query.strip_prefix("Xfer:features:read:target.xml:") {
+            let description = target_description();
+            let Some((offset, length)) = range.split_once(',').and_then(|(offset, length)| {
+                Some((usize::from_str_radix(offset, 16).ok()?, usize::from_str_radix(length, 16).ok()?))
+            }) else { return "E01".to_string(); };
+            // gdb picks the numbers, which can reach past usize
+            let end = offset.saturating_add(length);
+            let part = description.get(offset.min(description.len())..end.min(description.len())).unwrap_or_default();
+            let more = end < description.len();
+            return format!("{}{}", if more { "m" } else { "l" }, part);
+        }
+        match query {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// `Z0,addr,kind` sets a software breakpoint, `Z1` a hardware one, both are the same here.
+    fn change_breakpoint(&mut self, insert: bool, arguments: &str) -> String {
+        let mut parts = arguments.split(',');
+        if !matches!(parts.next(), Some("0" | "1")) { return String::new(); }
+        let Some(address) = parts.next().and_then(|address| u8::from_str_radix(address, 16).ok()) else { return "E01".to_string(); };
+        if insert {
+            self.breakpoints.insert(address);
+        } else {
+            self.breakpoints.remove(&address);
+        }
+        "OK".to_string()
+    }
+
+    /// Executes one instruction or runs until a breakpoint or an interrupt and reports why execution stopped.
+    fn resume(&mut self, single_step: bool) -> String {
+        let start = self.emulator.steps;
+        loop {
+            let executed = self.emulator.steps - start;
+            if executed > 0 && executed.is_multiple_of(INTERRUPT_INTERVAL) && self.interrupted.as_mut().is_some_and(|interrupted| interrupted()) {
+                return format!("S{:02x}", SIGINT);
+            }
+            if self.emulator.halted { return "W00".to_string(); }
+            if self.emulator.steps >= self.max_steps { return Self::stop_reply(&Fault::StepLimitReached { steps: self.emulator.steps }); }
+            if let Err(fault) = self.emulator.step() { return Self::stop_reply(&fault); }
+            if self.emulator.halted { return "W00".to_string(); }
+            if single_step || self.breakpoints.contains(&self.emulator.program_counter()) {
+                return format!("S{:02x}", SIGTRAP);
+            }
+        }
+    }
+
+    fn stop_reply(fault: &Fault) -> String {
+        let signal = match fault {
+            Fault::UnknownInstruction { .. } | Fault::UnsupportedInstruction { .. } => SIGILL,
+            Fault::DivisionByZero { .. } => SIGFPE,
+            Fault::StepLimitReached { .. } => SIGXCPU,
+            _ => SIGSEGV,
+        };
+        format!("S{:02x}", signal)
+    }
+
+    /// The bytes of `addr,length` within memory.
+    fn memory_range(arguments: &str) -> Option<(usize, usize)> {
+        let (start, length) = arguments.split_once(',')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        let end = start.checked_add(usize::from_str_radix(length, 16).ok()?)?;
+        if end > MEMORY_SIZE { return None; }
+        Some((start, end))
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn bytes(hex: &str) -> Option<Vec<u8>> {
+        if !hex.len().is_multiple_of(2) { return None; }
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()).collect()
+    }
+}
@@ -0,0 +1,41 @@
+/// Trailing zero words that are worth a single `[from..to] : 0;` entry in a MIF file.
+const MIF_MIN_RUN: usize = 2;
+
+/// A word with as many digits as the width needs in the radix.
+fn digits(word: u64, width: usize, radix: u32) -> String {
+    match radix {
+        2 => format!("{:0width$b}", word, width = width),
+        _ => format!("{:0width$x}", word, width = width.div_ceil(4)),
+    }
+}
+
+/// A `$readmemh` (radix 16) or `$readmemb` (radix 2) file with one word per line.
+pub fn readmem(words: &[u64], width: usize, radix: u32) -> String {
+    let mut text = format!("// {} words of {} bits\n", words.len(), width);
+    for word in words {
+        text += &digits(*word, width, radix);
+        text += "\n";
+    }
+    text
+}
+
+/// An Altera/Intel memory initialization file. Zeros at the end are written as one range.
+pub fn mif(words: &[u64], width: usize) -> String {
+    let address_digits = format!("{:x}", words.len().saturating_sub(1)).len();
+    let used = words.iter().rposition(|word| *word != 0).map(|last| last + 1).unwrap_or(0);
+    let used = if words.len() - used >= MIF_MIN_RUN { used } else { words.len() };
+    let mut text = format!("WIDTH={};\nDEPTH={};\n\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\nCONTENT BEGIN\n", width, words.len());
+    for (address, word) in words[..used].iter().enumerate() {
+        text += &format!("\t{:0digits$X} : {};\n", address, digits(*word, width, 16).to_uppercase(), digits = address_digits);
+    }
+    if used < words.len() {
+        text += &format!("\t[{:0digits$X}..{:0digits$X}] : {};\n", used, words.len() - 1, digits(0, width, 16), digits = address_digits);
+    }
+    text + "END;\n"
+}
+
+/// A Xilinx coefficient file for block memory.
+pub fn coe(words: &[u64], width: usize) -> String {
+    let vector = words.iter().map(|word| digits(*word, width, 16).to_uppercase()).collect::<Vec<String>>().join(",\n");
+    format!("; {} words of {} bits\nmemory_initialization_radix=16;\nmemory_initialization_vector=\n{};\n", words.len(), width, vector)
+}
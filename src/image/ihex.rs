@@ -0,0 +1,67 @@
+use super::{assemble_pieces, hex_bytes, RECORD_LENGTH};
+
+const DATA: u8 = 0x00;
+const END_OF_FILE: u8 = 0x01;
+const EXTENDED_SEGMENT_ADDRESS: u8 = 0x02;
+const START_SEGMENT_ADDRESS: u8 = 0x03;
+const EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+const START_LINEAR_ADDRESS: u8 = 0x05;
+
+/// `:LLAAAATT<data>CC` where the checksum makes all bytes of the record add up to zero.
+fn record(kind: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend(address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend(data);
+    let checksum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)).wrapping_neg();
+    bytes.push(checksum);
+    format!(":{}\n", bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>())
+}
+
+/// Data records of at most 16 bytes, with an extended linear address record whenever the upper half of the address changes.
+pub fn encode(binary: &[u8], load_address: u32) -> String {
+    let mut text = String::new();
+    let mut upper = 0;
+    let mut offset = 0;
+    while offset < binary.len() {
+        let address = load_address.wrapping_add(offset as u32);
+        if address >> 16 != upper {
+            upper = address >> 16;
+            text += &record(EXTENDED_LINEAR_ADDRESS, 0, &(upper as u16).to_be_bytes());
+        }
+        // Records may not wrap around within a 64 KiB segment
+        let length = RECORD_LENGTH.min(binary.len() - offset).min(0x1_0000 - (address & 0xFFFF) as usize);
+        text += &record(DATA, address as u16, &binary[offset..offset + length]);
+        offset += length;
+    }
+    text + &record(END_OF_FILE, 0, &[])
+}
+
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut pieces = Vec::new();
+    let mut base = 0u64;
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() { continue; }
+        let number = index + 1;
+        let Some(record) = line.strip_prefix(':') else { return Err(format!("line {} doesn't start with ':'", number)); };
+        let bytes = hex_bytes(record, number)?;
+        if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+            return Err(format!("the record on line {} has the wrong length", number));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            return Err(format!("the record on line {} has a wrong checksum", number));
+        }
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u64;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            DATA => pieces.push((base + address, data.to_vec())),
+            END_OF_FILE => break,
+            EXTENDED_SEGMENT_ADDRESS if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u64) << 4,
+            EXTENDED_LINEAR_ADDRESS if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u64) << 16,
+            START_SEGMENT_ADDRESS | START_LINEAR_ADDRESS => {},
+            kind => return Err(format!("the record on line {} has the unknown type {:02X}", number, kind)),
+        }
+    }
+    assemble_pieces(pieces)
+}
@@ -0,0 +1,81 @@
+/// Words written on one line.
+const WORDS_PER_LINE: usize = 16;
+const PLAIN_HEADER: &str = "v3.0 hex words plain";
+const ADDRESSED_HEADER: &str = "v3.0 hex words addressed";
+
+/// True if the text starts like a Logisim-evolution memory image.
+pub fn is_image(text: &str) -> bool {
+    text.trim_start().starts_with("v3.0 hex")
+}
+
+/// The words in Logisim-evolution's `v3.0 hex words` format, with every line starting with the
+/// address of its first word if `addressed` is set.
+pub fn encode(words: &[u64], width: usize, addressed: bool) -> String {
+    let digits = format!("{:x}", words.len().saturating_sub(1)).len().max(2);
+    let mut text = format!("{}\n", if addressed { ADDRESSED_HEADER } else { PLAIN_HEADER });
+    for (i, line) in words.chunks(WORDS_PER_LINE).enumerate() {
+        if addressed {
+            text += &format!("{:0digits$x}: ", i * WORDS_PER_LINE, digits = digits);
+        }
+        text += &line.iter().map(|word| format!("{:0width$x}", word, width = width.div_ceil(4))).collect::<Vec<String>>().join(" ");
+        text += "\n";
+    }
+    text
+}
+
+/// Reads both variants, including runs written as `count*word`. Addresses that are left out are zero.
+/// The widest word tells the width, wider words are split into bytes with the least significant one first.
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut lines = text.lines().enumerate();
+    let addressed = match lines.next().map(|(_, header)| header.trim()) {
+        Some(PLAIN_HEADER) => false,
+        Some(ADDRESSED_HEADER) => true,
+        _ => return Err(format!("it has to start with '{}' or '{}'", PLAIN_HEADER, ADDRESSED_HEADER)),
+    };
+    // The line number, the address of the first word if there is one, and the words
+    let mut rows = Vec::new();
+    for (index, line) in lines {
+        let number = index + 1;
+        let mut words = line.split('#').next().unwrap_or_default();
+        let mut address = None;
+        if addressed && !words.trim().is_empty() {
+            let (start, rest) = words.split_once(':').ok_or_else(|| format!("line {} has no address", number))?;
+            address = Some(usize::from_str_radix(start.trim(), 16).map_err(|_| format!("line {} has an invalid address", number))?);
+            words = rest;
+        }
+        rows.push((number, address, words));
+    }
+    let digits = rows.iter().flat_map(|(_, _, words)| words.split_whitespace()).map(|word| word.rsplit('*').next().unwrap_or(word).len()).max();
+    let bytes = digits.unwrap_or_default().div_ceil(2).max(1);
+    if bytes > 8 {
+        return Err("its words are wider than 64 bits".to_string());
+    }
+
+    let mut binary = Vec::new();
+    for (number, address, words) in rows {
+        if let Some(address) = address {
+            let start = address.saturating_mul(bytes);
+            if start < binary.len() {
+                return Err(format!("line {} goes back to address {:x}", number, address));
+            }
+            if start > super::MAX_IMAGE_SPAN as usize {
+                return Err(format!("line {} starts beyond address {:x}", number, super::MAX_IMAGE_SPAN as usize / bytes));
+            }
+            binary.resize(start, 0);
+        }
+        for word in words.split_whitespace() {
+            let (count, value) = match word.split_once('*') {
+                Some((count, value)) => (count.parse::<usize>().map_err(|_| format!("line {} has an invalid count {}", number, count))?, value),
+                None => (1, word),
+            };
+            let value = u64::from_str_radix(value, 16).map_err(|_| format!("line {} has the invalid word {}", number, value))?;
+            if binary.len() + count.saturating_mul(bytes) > super::MAX_IMAGE_SPAN as usize {
+                return Err(format!("it holds more than {} bytes", super::MAX_IMAGE_SPAN));
+            }
+            for _ in 0..count {
+                binary.extend_from_slice(&value.to_le_bytes()[..bytes]);
+            }
+        }
+    }
+    Ok(binary)
+}
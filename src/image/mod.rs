@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::argument_parser::ArgumentParser;
+use crate::error::AssembleError;
+
+pub mod fpga;
+pub mod ihex;
+pub mod logisim;
+pub mod srec;
+
+/// The most bytes a single data record carries.
+pub const RECORD_LENGTH: usize = 16;
+/// Images spreading over more than this are rejected instead of filling the gaps.
+const MAX_IMAGE_SPAN: u64 = 0x1_0000;
+/// The word width in bits, matching the byte addressed memory of the CPU.
+pub const DEFAULT_WIDTH: usize = 8;
+
+/// How an assembled program is written to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ImageFormat {
+    #[default]
+    Binary,
+    IntelHex,
+    SRecord,
+    /// Logisim-evolution's `v3.0 hex words` memory image, with or without addresses.
+    Logisim { addressed: bool },
+    /// One word per line for Verilog's `$readmemh`.
+    ReadMemH,
+    /// One word per line for Verilog's `$readmemb`.
+    ReadMemB,
+    /// Altera/Intel memory initialization file.
+    Mif,
+    /// Xilinx coefficient file.
+    Coe,
+}
+
+impl ImageFormat {
+    /// The format as given to `--format`.
+    pub fn from_name(name: &str) -> Option<ImageFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "bin" | "binary" => Some(ImageFormat::Binary),
+            "ihex" | "hex" => Some(ImageFormat::IntelHex),
+            "srec" | "s19" | "mot" => Some(ImageFormat::SRecord),
+            "logisim" => Some(ImageFormat::Logisim { addressed: true }),
+            "logisim-plain" => Some(ImageFormat::Logisim { addressed: false }),
+            "readmemh" => Some(ImageFormat::ReadMemH),
+            "readmemb" => Some(ImageFormat::ReadMemB),
+            "mif" => Some(ImageFormat::Mif),
+            "coe" => Some(ImageFormat::Coe),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format from the file extension, anything unknown is a raw binary.
+    pub fn from_path(path: &Path) -> ImageFormat {
+        let extension = path.extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "hex" | "ihex" | "ihx" => ImageFormat::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot" => ImageFormat::SRecord,
+            _ => ImageFormat::Binary,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Binary => "bin",
+            ImageFormat::IntelHex => "hex",
+            ImageFormat::SRecord => "srec",
+            ImageFormat::Logisim { .. } => "txt",
+            ImageFormat::ReadMemH | ImageFormat::ReadMemB => "mem",
+            ImageFormat::Mif => "mif",
+            ImageFormat::Coe => "coe",
+        }
+    }
+
+    /// The file content for the program placed at the load address. Formats made of words group
+    /// the bytes into words of the width, the first byte being the least significant one.
+    pub fn encode(&self, binary: &[u8], load_address: u32, width: usize) -> Vec<u8> {
+        let words = || words(binary, width);
+        let text = match self {
+            ImageFormat::Binary => return binary.to_vec(),
+            ImageFormat::IntelHex => ihex::encode(binary, load_address),
+            ImageFormat::SRecord => srec::encode(binary, load_address),
+            // The image is loaded into the memory component, whose addresses start at 0
+            ImageFormat::Logisim { addressed } => logisim::encode(&words(), width, *addressed),
+            ImageFormat::ReadMemH => fpga::readmem(&words(), width, 16),
+            ImageFormat::ReadMemB => fpga::readmem(&words(), width, 2),
+            ImageFormat::Mif => fpga::mif(&words(), width),
+            ImageFormat::Coe => fpga::coe(&words(), width),
+        };
+        text.into_bytes()
+    }
+
+    /// The program in the file, starting at its lowest address.
+    pub fn decode(&self, content: &[u8]) -> Result<Vec<u8>, String> {
+        let text = || std::str::from_utf8(content).map_err(|_| "it isn't a text file".to_string());
+        match self {
+            ImageFormat::Binary => Ok(content.to_vec()),
+            ImageFormat::IntelHex => ihex::decode(text()?),
+            ImageFormat::SRecord => srec::decode(text()?),
+            ImageFormat::Logisim { .. } => logisim::decode(text()?),
+            ImageFormat::ReadMemH | ImageFormat::ReadMemB | ImageFormat::Mif | ImageFormat::Coe => Err("this format can only be written".to_string()),
+        }
+    }
+}
+
+/// How the assembled program is turned into files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageOptions {
+    pub format: ImageFormat,
+    /// Where the program is placed in an Intel HEX or S-record image.
+    pub load_address: u32,
+    /// The number of words of the memory the image is loaded into, images are padded to it.
+    pub depth: Option<usize>,
+    /// The bits per word, a multiple of 8.
+    pub width: usize,
+    /// Writes the text section as ROM image and the data section as RAM image.
+    pub split: bool,
+}
+
+impl Default for ImageOptions {
+    fn default() -> ImageOptions {
+        ImageOptions{format: ImageFormat::default(), load_address: 0, depth: None, width: DEFAULT_WIDTH, split: false}
+    }
+}
+
+impl ImageOptions {
+    /// The format given to `--format`: bin, ihex, srec, logisim, logisim-plain, readmemh, readmemb, mif or coe.
+    pub fn with_format(mut self, format: Option<&str>) -> Result<ImageOptions, AssembleError> {
+        if let Some(format) = format {
+            self.format = ImageFormat::from_name(format).ok_or_else(|| AssembleError::BadOption {
+                option: "--format".to_string(),
+                reason: format!("expects ihex, srec, logisim, logisim-plain, readmemh, readmemb, mif, coe or bin but got {}", format),
+            })?;
+        }
+        Ok(self)
+    }
+
+    pub fn with_load_address(mut self, address: Option<&str>) -> Result<ImageOptions, AssembleError> {
+        if let Some(address) = address {
+            self.load_address = Self::number(address).and_then(|value| u32::try_from(value).ok()).ok_or_else(|| AssembleError::BadOption {
+                option: "--load-address".to_string(),
+                reason: format!("expects an address like 256 or x100 but got {}", address),
+            })?;
+        }
+        Ok(self)
+    }
+
+    pub fn with_depth(mut self, depth: Option<&str>) -> Result<ImageOptions, AssembleError> {
+        if let Some(depth) = depth {
+            let value = Self::number(depth).and_then(|value| usize::try_from(value).ok()).filter(|value| *value > 0);
+            self.depth = Some(value.ok_or_else(|| AssembleError::BadOption {
+                option: "--depth".to_string(),
+                reason: format!("expects a positive number of words but got {}", depth),
+            })?);
+        }
+        Ok(self)
+    }
+
+    pub fn with_width(mut self, width: Option<&str>) -> Result<ImageOptions, AssembleError> {
+        if let Some(width) = width {
+            let value = Self::number(width).and_then(|value| usize::try_from(value).ok()).filter(|value| (8..=64).contains(value) && value.is_multiple_of(8));
+            self.width = value.ok_or_else(|| AssembleError::BadOption {
+                option: "--width".to_string(),
+                reason: format!("expects 8, 16, 24, ... or 64 bits but got {}", width),
+            })?;
+        }
+        Ok(self)
+    }
+
+    pub fn with_split(mut self, split: bool) -> ImageOptions {
+        self.split = split;
+        self
+    }
+
+    /// A decimal or x, o or b prefixed number.
+    fn number(argument: &str) -> Option<i64> {
+        argument.parse::<i64>().ok().or_else(|| ArgumentParser::prefixed_number(argument))
+    }
+
+    /// The files to write next to the input: one image, or a .rom and a .ram image when splitting.
+    pub fn outputs(&self, input_path: &Path, binary: &[u8], text_length: usize) -> Result<Vec<(PathBuf, Vec<u8>)>, AssembleError> {
+        let extension = self.format.extension();
+        if !self.split {
+            return Ok(vec![(input_path.with_extension(extension), self.encode("program", binary, self.load_address)?)]);
+        }
+        let (text, data) = binary.split_at(text_length.min(binary.len()));
+        let data_address = self.load_address.wrapping_add(text.len() as u32);
+        Ok(vec![
+            (input_path.with_extension(format!("rom.{}", extension)), self.encode("text section", text, self.load_address)?),
+            (input_path.with_extension(format!("ram.{}", extension)), self.encode("data section", data, data_address)?),
+        ])
+    }
+
+    /// Pads the bytes to whole words and the depth and encodes them.
+    fn encode(&self, image: &str, binary: &[u8], load_address: u32) -> Result<Vec<u8>, AssembleError> {
+        let word_length = self.width / 8;
+        let size = binary.len().div_ceil(word_length);
+        let depth = self.depth.unwrap_or(size);
+        if size > depth {
+            return Err(AssembleError::ImageTooLarge { image: image.to_string(), size, depth });
+        }
+        let mut binary = binary.to_vec();
+        binary.resize(depth * word_length, 0);
+        Ok(self.format.encode(&binary, load_address, self.width))
+    }
+}
+
+/// Reads a program in the format its extension suggests, Logisim images are recognized by their header.
+pub fn read_image(path: &Path) -> Result<Vec<u8>, AssembleError> {
+    let content = fs::read(path).map_err(|reason| AssembleError::Io { path: path.display().to_string(), reason: reason.to_string() })?;
+    let format = match std::str::from_utf8(&content) {
+        Ok(text) if logisim::is_image(text) => ImageFormat::Logisim { addressed: false },
+        _ => ImageFormat::from_path(path),
+    };
+    format.decode(&content).map_err(|reason| AssembleError::BadImage { path: path.display().to_string(), reason })
+}
+
+/// The bytes grouped into little endian words of the width.
+fn words(binary: &[u8], width: usize) -> Vec<u64> {
+    binary.chunks(width / 8).map(|word| word.iter().rev().fold(0, |value, byte| value << 8 | *byte as u64)).collect()
+}
+
+/// Places the pieces of a decoded image next to each other, gaps between them are zero.
+fn assemble_pieces(pieces: Vec<(u64, Vec<u8>)>) -> Result<Vec<u8>, String> {
+    let Some(start) = pieces.iter().map(|(address, _)| *address).min() else { return Ok(Vec::new()); };
+    let end = pieces.iter().map(|(address, data)| address + data.len() as u64).max().unwrap_or(start);
+    if end - start > MAX_IMAGE_SPAN {
+        return Err(format!("it spreads over {} bytes, more than the {} that can be loaded", end - start, MAX_IMAGE_SPAN));
+    }
+    let mut binary = vec![0; (end - start) as usize];
+    for (address, data) in pieces {
+        let offset = (address - start) as usize;
+        binary[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    Ok(binary)
+}
+
+/// Turns a record of hex digit pairs into bytes.
+fn hex_bytes(record: &str, line: usize) -> Result<Vec<u8>, String> {
+    if !record.len().is_multiple_of(2) {
+        return Err(format!("the record on line {} has an odd number of digits", line));
+    }
+    (0..record.len()).step_by(2)
+        .map(|i| record.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| format!("the record on line {} isn't hexadecimal", line))
+}
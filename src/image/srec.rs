@@ -0,0 +1,72 @@
+use super::{assemble_pieces, hex_bytes, RECORD_LENGTH};
+
+/// Written into the S0 header record.
+const HEADER: &[u8] = b"sriscasm";
+
+/// `S<type><count><address><data><checksum>` where the count covers everything after it and the
+/// checksum is the ones' complement of the low byte of their sum.
+fn record(kind: u8, address: u32, address_length: usize, data: &[u8]) -> String {
+    let mut bytes = vec![(address_length + data.len() + 1) as u8];
+    bytes.extend(&address.to_be_bytes()[4 - address_length..]);
+    bytes.extend(data);
+    let checksum = !bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    bytes.push(checksum);
+    format!("S{}{}\n", kind, bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>())
+}
+
+/// S1, S2 or S3 data records depending on the highest address, followed by the count and the matching termination record.
+pub fn encode(binary: &[u8], load_address: u32) -> String {
+    let last_address = load_address as u64 + binary.len().saturating_sub(1) as u64;
+    let (data_kind, end_kind, address_length) = match last_address {
+        0..=0xFFFF => (1, 9, 2),
+        0x1_0000..=0xFF_FFFF => (2, 8, 3),
+        _ => (3, 7, 4),
+    };
+    let mut text = record(0, 0, 2, HEADER);
+    let chunks = binary.chunks(RECORD_LENGTH);
+    let count = chunks.len();
+    for (i, chunk) in chunks.enumerate() {
+        text += &record(data_kind, load_address.wrapping_add((i * RECORD_LENGTH) as u32), address_length, chunk);
+    }
+    if count <= 0xFFFF {
+        text += &record(5, count as u32, 2, &[]);
+    }
+    text + &record(end_kind, load_address, address_length, &[])
+}
+
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut pieces = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() { continue; }
+        let number = index + 1;
+        let mut characters = line.chars();
+        if !characters.next().is_some_and(|character| character.eq_ignore_ascii_case(&'S')) {
+            return Err(format!("line {} doesn't start with 'S'", number));
+        }
+        let kind = characters.next().and_then(|kind| kind.to_digit(10)).ok_or_else(|| format!("the record on line {} has no type", number))?;
+        let bytes = hex_bytes(characters.as_str(), number)?;
+        if bytes.is_empty() || bytes.len() != bytes[0] as usize + 1 {
+            return Err(format!("the record on line {} has the wrong length", number));
+        }
+        if bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0xFF {
+            return Err(format!("the record on line {} has a wrong checksum", number));
+        }
+        let address_length = match kind {
+            0 | 1 | 5 | 9 => 2,
+            2 | 6 | 8 => 3,
+            3 | 7 => 4,
+            _ => return Err(format!("the record on line {} has the unknown type S{}", number, kind)),
+        };
+        if bytes.len() < address_length + 2 {
+            return Err(format!("the record on line {} is too short for its address", number));
+        }
+        let address = bytes[1..=address_length].iter().fold(0u64, |address, byte| address << 8 | *byte as u64);
+        match kind {
+            1..=3 => pieces.push((address, bytes[address_length + 1..bytes.len() - 1].to_vec())),
+            7..=9 => break,
+            _ => {},
+        }
+    }
+    assemble_pieces(pieces)
+}
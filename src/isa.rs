@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::Path;
+use crate::error::AssembleError;
+use crate::lexer::Lexer;
+use crate::utility::json::JsonValue;
+use crate::utility::toml;
+
+// Task encoding after bits:
+// Extension (1 = yes)
+// If no extension:
+// 1 means internal only
+// 0 means external ops (RAM load, etc.)
+// 010: ALU
+// 011: Other internal (mov, etc)
+// 000: Memory OP
+// 001: Reserved for future applications
+// 0b0110_1010 is reserved for loading an immediate into an internal register.
+
+#[allow(dead_code)]
+pub const RESERVED_REGISTER: u8 = 11+128;
+pub const FLAGS_REGISTER: u8 = 12 + 128;
+pub const EXEC_PTR_REGISTER: u8 = 15 + 128;
+pub const FRAME_PTR_REGISTER: u8 = 13 + 128;
+#[allow(dead_code)]
+pub const EMPTY_ARGUMENT: u8 = 0;
+
+/// The name of a register with a special purpose, e.g. frame_ptr for r13.
+pub fn register_name(register: u8) -> Option<&'static str> {
+    match register {
+        FRAME_PTR_REGISTER => Some("frame_ptr"),
+        EXEC_PTR_REGISTER => Some("exec_ptr"),
+        FLAGS_REGISTER => Some("flags"),
+        _ => None,
+    }
+}
+
+/// What an operand byte of an instruction may be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    /// A register like r1.
+    Register,
+    /// A register or a number within 0...127.
+    Value,
+    /// Not written in the source, the byte always has the given value.
+    Implied(u8),
+}
+
+/// The top three bits of an opcode.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InstructionClass {
+    /// 000: external operations like RAM access.
+    Memory,
+    /// 001: reserved for future applications, no instruction may use it.
+    Reserved,
+    /// 010
+    Alu,
+    /// 011: other internal operations like mov.
+    Internal,
+    /// 1xx
+    Extension,
+}
+
+impl InstructionClass {
+    pub fn of(opcode: u8) -> InstructionClass {
+        match opcode >> 5 {
+            0b000 => InstructionClass::Memory,
+            0b001 => InstructionClass::Reserved,
+            0b010 => InstructionClass::Alu,
+            0b011 => InstructionClass::Internal,
+            _ => InstructionClass::Extension,
+        }
+    }
+
+    fn from_string(name: &str) -> Option<InstructionClass> {
+        match name {
+            "memory" => Some(InstructionClass::Memory),
+            "alu" => Some(InstructionClass::Alu),
+            "internal" => Some(InstructionClass::Internal),
+            "extension" => Some(InstructionClass::Extension),
+            _ => None,
+        }
+    }
+}
+
+/// One row of the ISA: everything the encoder, the sizer and other tools need to know about a mnemonic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionDefinition {
+    pub mnemonic: String,
+    pub opcode: u8,
+    /// The operand bytes following the opcode, in order.
+    pub operands: Vec<OperandKind>,
+}
+
+impl InstructionDefinition {
+    /// Operands that have to be written in the source.
+    pub fn written_operands(&self) -> usize {
+        self.operands.iter().filter(|kind| !matches!(kind, OperandKind::Implied(_))).count()
+    }
+
+    /// Rows with implied operands are shorthands for another instruction, e.g. inc for add.
+    pub fn is_alias(&self) -> bool {
+        self.written_operands() != self.operands.len()
+    }
+
+    /// The built-in instruction of the same opcode and length, which is what the hardware executes whatever an ISA
+    /// file calls it. Aliases give the instruction they stand for, e.g. add for inc.
+    pub fn semantics(&self) -> Option<&'static str> {
+        BUILTIN_INSTRUCTIONS.iter()
+            .find(|(_, opcode, operands)| *opcode == self.opcode && operands.len() == self.operands.len() && !operands.iter().any(|kind| matches!(kind, Implied(_))))
+            .map(|(mnemonic, ..)| *mnemonic)
+    }
+}
+
+use OperandKind::{Implied, Register, Value};
+
+// Mnemonic, opcode and operands of the instruction set the hardware currently implements
+// 15 of the 256 opcodes are used (5.8%), the aliases inc and dec share theirs with add and sub
+const BUILTIN_INSTRUCTIONS: [(&str, u8, &[OperandKind]); 17] = [
+    ("sow", 0b0000_0001, &[Value]),
+    ("soc", 0b0000_0010, &[]),
+    ("add", 0b0100_0000, &[Register, Value]),
+    ("sub", 0b0100_0001, &[Register, Value]),
+    ("mul", 0b0100_0010, &[Register, Value]),
+    ("div", 0b0100_0011, &[Register, Value]),
+    ("mod", 0b0100_0100, &[Register, Value]),
+    // inc and dec are add and sub with an implied 1
+    ("inc", 0b0100_0000, &[Register, Implied(1)]),
+    ("dec", 0b0100_0001, &[Register, Implied(1)]),
+    ("halt", 0b0110_0000, &[]),
+    ("mov", 0b0110_0001, &[Register, Value]),
+    ("jmp", 0b0110_0010, &[Value]),
+    // Jumps to arg2 if arg1 is 0
+    ("jmpz", 0b0110_0011, &[Value, Value]),
+    ("ldb", 0b0110_0100, &[Register, Value]),
+    ("pushb", 0b0110_0101, &[Value]),
+    ("popb", 0b0110_1100, &[Register]),
+    ("stb", 0b0111_0100, &[Value, Value]),
+];
+
+/// The instruction set the assembler encodes for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isa {
+    instructions: Vec<InstructionDefinition>,
+}
+
+impl Default for Isa {
+    fn default() -> Isa {
+        Isa::builtin()
+    }
+}
+
+impl Isa {
+    pub fn builtin() -> Isa {
+        let instructions = BUILTIN_INSTRUCTIONS.iter().map(|(mnemonic, opcode, operands)| {
+            InstructionDefinition{mnemonic: mnemonic.to_string(), opcode: *opcode, operands: operands.to_vec()}
+        }).collect();
+        Isa{instructions}
+    }
+
+    /// Checks that mnemonics are unique, that no two instructions share an opcode (aliases may share it with
+    /// an instruction of the same length) and that no opcode uses the reserved class.
+    pub fn new(instructions: Vec<InstructionDefinition>) -> Result<Isa, String> {
+        for (i, definition) in instructions.iter().enumerate() {
+            if InstructionClass::of(definition.opcode) == InstructionClass::Reserved {
+                return Err(format!("{} uses opcode {:#010b}, but class 001 is reserved", definition.mnemonic, definition.opcode));
+            }
+            for other in instructions[..i].iter() {
+                if other.mnemonic.eq_ignore_ascii_case(&definition.mnemonic) {
+                    return Err(format!("{} is defined more than once", definition.mnemonic));
+                }
+                let same_encoding = other.operands.len() == definition.operands.len() && (other.is_alias() || definition.is_alias());
+                if other.opcode == definition.opcode && !same_encoding {
+                    return Err(format!("{} and {} both use opcode {:#010b}", other.mnemonic, definition.mnemonic, definition.opcode));
+                }
+            }
+        }
+        Ok(Isa{instructions})
+    }
+
+    /// Loads a description like the following from a .toml or .json file:
+    /// ```toml
+    /// [[instructions]]
+    /// mnemonic = "add"
+    /// opcode = 0b0100_0000
+    /// operands = ["register", "value"]   # an integer is an implied operand
+    /// class = "alu"                      # optional, checked against the opcode
+    /// size = 3                           # optional, checked against the operands
+    /// ```
+    pub fn load(path: &Path) -> Result<Isa, AssembleError> {
+        let bad_isa = |reason: String| AssembleError::BadIsa { path: path.display().to_string(), reason };
+        let text = fs::read_to_string(path).map_err(|error| bad_isa(error.to_string()))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&text),
+            _ => Self::from_toml(&text),
+        }.map_err(bad_isa)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Isa, String> {
+        Self::from_description(&toml::parse(text)?)
+    }
+
+    pub fn from_json(text: &str) -> Result<Isa, String> {
+        Self::from_description(&JsonValue::parse(text)?)
+    }
+
+    fn from_description(description: &JsonValue) -> Result<Isa, String> {
+        let Some(rows) = description.get("instructions").and_then(JsonValue::as_array) else {
+            return Err("the list of instructions is missing".to_string());
+        };
+        let mut instructions = Vec::new();
+        for (i, row) in rows.iter().enumerate() {
+            let mnemonic = row.get("mnemonic").and_then(JsonValue::as_str)
+                .ok_or_else(|| format!("instruction {} has no mnemonic", i + 1))?.to_string();
+            if !Lexer::is_identifier(&mnemonic) {
+                return Err(format!("{} isn't a valid mnemonic", mnemonic));
+            }
+            let opcode = row.get("opcode").and_then(JsonValue::as_i64).and_then(|opcode| u8::try_from(opcode).ok())
+                .ok_or_else(|| format!("{} needs an opcode within 0...255", mnemonic))?;
+            let mut operands = Vec::new();
+            for operand in row.get("operands").and_then(JsonValue::as_array).map(Vec::as_slice).unwrap_or_default() {
+                operands.push(match (operand.as_str(), operand.as_i64()) {
+                    (Some("register"), _) => Register,
+                    (Some("value"), _) => Value,
+                    (_, Some(implied)) if (0..=255).contains(&implied) => Implied(implied as u8),
+                    _ => return Err(format!("{} has operand {}, expected \"register\", \"value\" or a byte", mnemonic, operand)),
+                });
+            }
+            if let Some(class) = row.get("class") {
+                let expected = class.as_str().and_then(InstructionClass::from_string)
+                    .ok_or_else(|| format!("{} has class {}, expected \"memory\", \"alu\", \"internal\" or \"extension\"", mnemonic, class))?;
+                if InstructionClass::of(opcode) != expected {
+                    return Err(format!("{} is declared as {} but opcode {:#010b} has the class bits of {:?}", mnemonic, class, opcode, InstructionClass::of(opcode)));
+                }
+            }
+            if let Some(size) = row.get("size") {
+                if size.as_i64() != Some(1 + operands.len() as i64) {
+                    return Err(format!("{} has size {} but its opcode and operands take {} bytes", mnemonic, size, 1 + operands.len()));
+                }
+            }
+            instructions.push(InstructionDefinition{mnemonic, opcode, operands});
+        }
+        Isa::new(instructions)
+    }
+
+    pub fn get_instructions(&self) -> &Vec<InstructionDefinition> {
+        &self.instructions
+    }
+
+    /// The instruction at the start of the bytes and its operand bytes (implied ones included).
+    /// Aliases are preferred when their implied operands match, so add r1 1 reads as inc r1.
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Option<(&InstructionDefinition, &'a [u8])> {
+        let opcode = *bytes.first()?;
+        let mut candidates = self.instructions.iter().filter(|definition| definition.opcode == opcode && definition.operands.len() < bytes.len());
+        let definition = candidates.clone().find(|definition| definition.is_alias() && definition.operands.iter().zip(&bytes[1..]).all(|(kind, byte)| match kind {
+            OperandKind::Implied(implied) => implied == byte,
+            _ => true,
+        })).or_else(|| candidates.find(|definition| !definition.is_alias()))?;
+        Some((definition, &bytes[1..definition.operands.len() + 1]))
+    }
+
+    /// Looks up a mnemonic, ignoring its case.
+    pub fn find(&self, mnemonic: &str) -> Option<&InstructionDefinition> {
+        self.instructions.iter().find(|definition| definition.mnemonic.eq_ignore_ascii_case(mnemonic))
+    }
+}
@@ -0,0 +1,97 @@
+use crate::source::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LexemeKind {
+    /// A name like `loop`, `data_offset` or `$`, but also prefixed literals like `r1` or `x1F`.
+    Identifier,
+    /// A plain decimal number.
+    Number,
+    /// A quoted character like `'a'`.
+    Character,
+    /// A quoted string like `"hello"`.
+    Text,
+    Operator,
+    OpenBracket,
+    CloseBracket,
+    OpenParenthesis,
+    CloseParenthesis,
+    /// Anything the lexer doesn't know, kept so it can be reported.
+    Unknown,
+}
+
+/// The smallest meaningful piece of an operand or expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lexeme {
+    pub kind: LexemeKind,
+    pub text: String,
+    pub span: Span,
+}
+
+pub struct Lexer;
+
+const OPERATORS: [&str; 20] = ["<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "<", ">"];
+
+impl Lexer {
+    /// Splits the text into lexemes. The span is where the text starts, every lexeme gets its own span from it.
+    pub fn tokenize(text: &str, span: Span) -> Vec<Lexeme> {
+        let characters: Vec<char> = text.chars().collect();
+        let mut lexemes: Vec<Lexeme> = Vec::new();
+        let mut i = 0;
+
+        while i < characters.len() {
+            let character = characters[i];
+            let start = i;
+            if character.is_whitespace() {
+                i += 1;
+                continue;
+            }
+
+            let kind = if Self::is_identifier_start(character) {
+                i += 1;
+                while i < characters.len() && Self::is_identifier_continue(characters[i]) { i += 1; }
+                LexemeKind::Identifier
+            } else if character.is_ascii_digit() {
+                while i < characters.len() && characters[i].is_ascii_digit() { i += 1; }
+                LexemeKind::Number
+            } else if character == '\'' || character == '"' {
+                // Everything up to the closing quote (or the end) belongs to the literal
+                i += 1;
+                while i < characters.len() && characters[i] != character { i += 1; }
+                i = (i + 1).min(characters.len());
+                if character == '\'' { LexemeKind::Character } else { LexemeKind::Text }
+            } else {
+                let rest: String = characters[i..].iter().take(2).collect();
+                match (character, OPERATORS.iter().find(|operator| rest.starts_with(**operator))) {
+                    ('[', _) => { i += 1; LexemeKind::OpenBracket },
+                    (']', _) => { i += 1; LexemeKind::CloseBracket },
+                    ('(', _) => { i += 1; LexemeKind::OpenParenthesis },
+                    (')', _) => { i += 1; LexemeKind::CloseParenthesis },
+                    (_, Some(operator)) => { i += operator.len(); LexemeKind::Operator },
+                    _ => { i += 1; LexemeKind::Unknown },
+                }
+            };
+
+            lexemes.push(Lexeme{
+                kind,
+                text: characters[start..i].iter().collect(),
+                span: Span::new(span.file, span.line, span.column + start as u32, (i - start) as u32),
+            });
+        }
+
+        lexemes
+    }
+
+    /// True if the whole text is a single name.
+    pub fn is_identifier(text: &str) -> bool {
+        let mut characters = text.chars();
+        characters.next().is_some_and(Self::is_identifier_start) && characters.all(Self::is_identifier_continue)
+    }
+
+    fn is_identifier_start(character: char) -> bool {
+        character.is_ascii_alphabetic() || character == '_' || character == '$'
+    }
+
+    fn is_identifier_continue(character: char) -> bool {
+        character.is_ascii_alphanumeric() || character == '_'
+    }
+}
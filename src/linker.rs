@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use crate::argument_parser::ArgumentParser;
+use crate::error::AssembleError;
+use crate::object::{ObjectFile, ObjectSection};
+use crate::section::FixupKind;
+use crate::source::{Span, Token};
+use crate::symbol_map::SymbolKind;
+use crate::symbol_table::SymbolTable;
+use crate::utility::replacement::Replacement;
+
+/// A program made of linked modules.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedProgram {
+    pub binary: Vec<u8>,
+    /// Bytes of all text sections together, the data sections follow them.
+    pub text_length: usize,
+}
+
+/// Places the modules one after the other and fills in the values that depend on where they ended up.
+pub struct Linker;
+
+impl Linker {
+    /// The text sections come first in the order of the modules (so the first one starts at address 0), then the data sections.
+    /// In each module `data_offset` is where its own data section starts, for the first module that's the same as in a single assembled file.
+    pub fn link(modules: &[ObjectFile]) -> Result<LinkedProgram, Vec<AssembleError>> {
+        let mut errors = Vec::new();
+        let text_length: usize = modules.iter().map(|module| module.text.len()).sum();
+        let mut bases = Vec::new();
+        let (mut text_base, mut data_base) = (0, text_length);
+        for module in modules {
+            bases.push((text_base, data_base));
+            text_base += module.text.len();
+            data_base += module.data.len();
+        }
+        let address = |module: usize, kind: SymbolKind, value: &str| -> Option<i64> {
+            let base = if kind == SymbolKind::Label { bases[module].0 } else { bases[module].1 };
+            value.parse::<i64>().ok().map(|offset| base as i64 + offset)
+        };
+
+        // Every global symbol gets its final address
+        let mut globals: HashMap<&str, (i64, usize)> = HashMap::new();
+        for (index, module) in modules.iter().enumerate() {
+            for symbol in module.symbols.iter().filter(|symbol| symbol.global) {
+                let Some(value) = address(index, symbol.kind, &symbol.value).filter(|_| symbol.kind != SymbolKind::Constant) else {
+                    errors.push(AssembleError::BadRelocation { module: module.source.clone(), line: symbol.line, reason: format!("Symbol {} can't be global because it isn't an address.", symbol.name) });
+                    continue;
+                };
+                match globals.get(symbol.name.as_str()) {
+                    Some((_, previous)) => errors.push(AssembleError::DuplicateGlobal { name: symbol.name.clone(), module: module.source.clone(), previous: modules[*previous].source.clone() }),
+                    None => _ = globals.insert(&symbol.name, (value, index)),
+                }
+            }
+        }
+
+        let mut text = Vec::with_capacity(text_length);
+        let mut data = Vec::new();
+        for module in modules {
+            text.extend_from_slice(&module.text);
+            data.extend_from_slice(&module.data);
+        }
+        for (index, module) in modules.iter().enumerate() {
+            // The module sees its own symbols first, then the global ones it declared .extern.
+            // data_offset is where its own data section starts, which is what $ in its constants counts from.
+            let (text_base, data_base) = bases[index];
+            let mut symbols = SymbolTable::new();
+            _ = symbols.declare(Replacement::new("data_offset".to_string(), data_base.to_string(), false));
+            for symbol in module.symbols.iter() {
+                let value = match symbol.kind {
+                    SymbolKind::Constant => symbol.value.clone(),
+                    kind => address(index, kind, &symbol.value).unwrap_or_default().to_string(),
+                };
+                _ = symbols.declare(Replacement::new(symbol.name.clone(), value, symbol.kind == SymbolKind::Label));
+            }
+            let known_errors = errors.len();
+            for name in module.externs.iter() {
+                match globals.get(name.as_str()) {
+                    Some((value, _)) => _ = symbols.declare(Replacement::new(name.clone(), value.to_string(), false)),
+                    None => errors.push(AssembleError::UndefinedExternal { name: name.clone(), module: module.source.clone() }),
+                }
+            }
+            // Every use of a missing symbol would only repeat the error
+            if errors.len() > known_errors { continue; }
+
+            for relocation in module.relocations.iter() {
+                let token = Token{text: relocation.expression.clone(), span: Span::new(0, relocation.line, relocation.column, relocation.expression.chars().count() as u32)};
+                symbols.set_location_counter(relocation.location_counter.map(|location| location + data_base as i64));
+                let value = match relocation.kind {
+                    FixupKind::Operand(kind) => ArgumentParser::resolve_operand(&token, kind, &symbols).map(|byte| vec![byte]),
+                    FixupKind::Data { width, endianness } => ArgumentParser::resolve_data(&token, width, endianness, &symbols),
+                };
+                let bytes = match value {
+                    Ok(bytes) => bytes,
+                    Err(error) => {
+                        errors.push(AssembleError::BadRelocation { module: module.source.clone(), line: relocation.line, reason: error.to_string() });
+                        continue;
+                    },
+                };
+                let (section, offset) = match relocation.section {
+                    ObjectSection::Text => (&mut text, text_base + relocation.offset),
+                    ObjectSection::Data => (&mut data, data_base - text_length + relocation.offset),
+                };
+                section[offset..offset + bytes.len()].copy_from_slice(&bytes);
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        text.append(&mut data);
+        Ok(LinkedProgram{binary: text, text_length})
+    }
+}
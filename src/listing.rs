@@ -0,0 +1,72 @@
+use crate::assembler::Assembler;
+use crate::disassembler::Disassembler;
+use crate::section::LineEntry;
+use crate::symbol_map::SymbolMap;
+
+/// Bytes shown next to a source line, longer data continues on the following lines.
+const BYTES_PER_LINE: usize = 4;
+
+/// Writes every source line next to the address and bytes it was assembled to, followed by the symbol table.
+pub struct Listing;
+
+impl Listing {
+    pub fn render(assembler: &Assembler, binary: &[u8]) -> String {
+        let mut output = format!("{:<6}{:<14}{:<40}{}\n", "ADDR", "BYTES", "SOURCE", "RESOLVED");
+        for line in assembler.get_lines() {
+            // A macro use is followed by the lines it expanded to
+            let entries: Vec<&LineEntry> = assembler.line_table.iter().filter(|entry| {
+                let root = assembler.sources.outermost(entry.span);
+                root.file == line.span.file && root.line == line.span.line
+            }).collect();
+            if entries.first().is_none_or(|entry| entry.span.file != line.span.file) {
+                output += format!("{:<20}{}", "", line.text).trim_end();
+                output += "\n";
+            }
+            for entry in entries {
+                let source = if entry.span.file == line.span.file {
+                    line.text.clone()
+                } else {
+                    format!("  {}", assembler.sources.get_line(entry.span).unwrap_or_default())
+                };
+                let bytes = &binary[entry.address..entry.address + entry.length];
+                let resolved = Self::resolve(assembler, entry, bytes);
+                for (i, chunk) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+                    let address = format!("{:02X}", entry.address + i * BYTES_PER_LINE);
+                    let (source, resolved) = if i == 0 { (source.as_str(), resolved.as_str()) } else { ("", "") };
+                    output += format!("{:<6}{:<14}{:<40}{}", address, Self::hex(chunk), source, resolved).trim_end();
+                    output += "\n";
+                }
+            }
+        }
+        output + &Self::symbol_table(assembler)
+    }
+
+    /// The instruction as written with its operands as numbers, data isn't changed by resolving.
+    fn resolve(assembler: &Assembler, entry: &LineEntry, bytes: &[u8]) -> String {
+        if entry.address >= assembler.get_text_length() { return String::new(); }
+        // Decoding alone would turn add r1 1 into its alias inc r1
+        let mnemonic = assembler.sources.get_line(entry.span).and_then(|line| line.split_whitespace().next()).unwrap_or_default();
+        let written = assembler.get_isa().find(mnemonic).filter(|definition| definition.operands.len() + 1 == bytes.len());
+        match written.or_else(|| assembler.get_isa().decode(bytes).map(|(definition, _)| definition)) {
+            Some(definition) => Disassembler::instruction_to_string(definition, &bytes[1..], &Default::default()),
+            None => String::new(),
+        }
+    }
+
+    fn symbol_table(assembler: &Assembler) -> String {
+        let mut output = format!("\nSYMBOLS\n{:<24}{:<10}{}\n", "NAME", "KIND", "VALUE");
+        for entry in SymbolMap::collect(assembler) {
+            let value = match entry.value {
+                Some(value) if value >= 0 => format!("{} ({:#04X})", value, value),
+                Some(value) => value.to_string(),
+                None => "?".to_string(),
+            };
+            output += &format!("{:<24}{:<10}{}\n", entry.name, entry.kind.name(), value);
+        }
+        output
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ")
+    }
+}
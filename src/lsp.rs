@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use crate::argument_parser::ArgumentParser;
+use crate::assembler::Assembler;
+use crate::error::AssembleError;
+use crate::isa::{InstructionClass, Isa, OperandKind};
+use crate::lexer::{Lexeme, LexemeKind, Lexer};
+use crate::preprocessor::Preprocessor;
+use crate::source::Span;
+use crate::utility::json::JsonValue;
+use crate::utility::message;
+
+/// Error codes of JSON-RPC.
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Kinds of completion items.
+const KEYWORD_COMPLETION: i64 = 14;
+const CONSTANT_COMPLETION: i64 = 21;
+
+/// An open document after assembling it.
+struct Analysis {
+    assembler: Assembler,
+    binary: Option<Vec<u8>>,
+}
+
+/// Offers diagnostics, navigation, hover, completion and rename for .asm files through the Language Server Protocol.
+/// Every request assembles the document again, which is fast enough for files that fit into 256 bytes.
+pub struct LanguageServer {
+    isa: Isa,
+    /// The text of every open document by its URI.
+    documents: BTreeMap<String, String>,
+    /// Constants and include directories from the initialization options, like -D and -I.
+    defines: Vec<(String, String)>,
+    include_paths: Vec<PathBuf>,
+    /// Set once the client sent exit.
+    pub finished: bool,
+}
+
+impl LanguageServer {
+    pub fn new(isa: Isa) -> LanguageServer {
+        LanguageServer{isa, documents: BTreeMap::new(), defines: Vec::new(), include_paths: Vec::new(), finished: false}
+    }
+
+    /// Answers messages until the client exits or closes the stream.
+    pub fn serve(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> std::io::Result<()> {
+        while let Some(body) = message::read_message(reader)? {
+            let replies = match JsonValue::parse(&body) {
+                Ok(request) => self.handle(&request),
+                Err(_) => Vec::new(),
+            };
+            for reply in replies {
+                message::write_message(writer, &reply)?;
+            }
+            writer.flush()?;
+            if self.finished { break; }
+        }
+        Ok(())
+    }
+
+    /// The response to a request (notifications don't get one) followed by the notifications it caused.
+    pub fn handle(&mut self, request: &JsonValue) -> Vec<JsonValue> {
+        let method = request.get("method").and_then(JsonValue::as_str).unwrap_or_default();
+        let empty = JsonValue::Object(Vec::new());
+        let params = request.get("params").unwrap_or(&empty);
+        let uri = params.get("textDocument").and_then(|document| document.get("uri")).and_then(JsonValue::as_str).unwrap_or_default().to_string();
+        let result = match method {
+            "initialize" => self.initialize(params),
+            "shutdown" => Ok(JsonValue::Null),
+            "exit" => {
+                self.finished = true;
+                return Vec::new();
+            },
+            "textDocument/didOpen" => {
+                let text = params.get("textDocument").and_then(|document| document.get("text")).and_then(JsonValue::as_str).unwrap_or_default();
+                self.documents.insert(uri.clone(), text.to_string());
+                return vec![self.diagnostics(&uri)];
+            },
+            "textDocument/didChange" => {
+                // The whole text is sent on every change
+                let changes = params.get("contentChanges").and_then(JsonValue::as_array);
+                if let Some(text) = changes.and_then(|changes| changes.last()).and_then(|change| change.get("text")).and_then(JsonValue::as_str) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                return vec![self.diagnostics(&uri)];
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![Self::notification("textDocument/publishDiagnostics", JsonValue::object(vec![("uri", uri.into()), ("diagnostics", Vec::new().into())]))];
+            },
+            "textDocument/definition" => Ok(self.definition(&uri, params)),
+            "textDocument/references" => Ok(self.references(&uri, params)),
+            "textDocument/hover" => Ok(self.hover(&uri, params)),
+            "textDocument/completion" => Ok(self.completion(&uri)),
+            "textDocument/rename" => self.rename(&uri, params),
+            _ => Err((METHOD_NOT_FOUND, format!("{} isn't supported.", method))),
+        };
+        // Notifications the server doesn't know are ignored
+        let Some(id) = request.get("id").cloned() else { return Vec::new(); };
+        let outcome = match result {
+            Ok(result) => ("result", result),
+            Err((code, message)) => ("error", JsonValue::object(vec![("code", code.into()), ("message", message.into())])),
+        };
+        vec![JsonValue::object(vec![("jsonrpc", "2.0".into()), ("id", id), outcome])]
+    }
+
+    /// Takes `defines` and `includePaths` of the initialization options, so documents are assembled like sriscasm does.
+    fn initialize(&mut self, params: &JsonValue) -> Result<JsonValue, (i64, String)> {
+        let options = params.get("initializationOptions");
+        let strings = |key: &str| options.and_then(|options| options.get(key)).and_then(JsonValue::as_array).into_iter().flatten().filter_map(JsonValue::as_str).collect::<Vec<&str>>();
+        self.defines = strings("defines").into_iter().map(Preprocessor::parse_define).collect::<Result<Vec<(String, String)>, AssembleError>>()
+            .map_err(|error| (INVALID_PARAMS, error.to_string()))?;
+        self.include_paths = strings("includePaths").into_iter().map(PathBuf::from).collect();
+        Ok(Self::capabilities())
+    }
+
+    fn capabilities() -> JsonValue {
+        JsonValue::object(vec![
+            ("capabilities", JsonValue::object(vec![
+                // Full synchronisation, the documents are tiny
+                ("textDocumentSync", 1.into()),
+                ("definitionProvider", true.into()),
+                ("referencesProvider", true.into()),
+                ("hoverProvider", true.into()),
+                ("completionProvider", JsonValue::Object(Vec::new())),
+                ("renameProvider", true.into()),
+            ])),
+            ("serverInfo", JsonValue::object(vec![("name", "sriscasm".into())])),
+        ])
+    }
+
+    fn notification(method: &str, params: JsonValue) -> JsonValue {
+        JsonValue::object(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)])
+    }
+
+    fn analyze(&self, uri: &str) -> Option<Analysis> {
+        let text = self.documents.get(uri)?;
+        // Included files are looked up next to the document, so it needs the path it has on disk
+        let mut assembler = Assembler::new(Self::path_of(uri), text.clone()).with_isa(self.isa.clone())
+            .with_defines(self.defines.clone()).with_include_paths(self.include_paths.clone());
+        let binary = assembler.assemble().ok();
+        Some(Analysis{assembler, binary})
+    }
+
+    /// Every error and warning of the document.
+    fn diagnostics(&self, uri: &str) -> JsonValue {
+        let mut diagnostics = Vec::new();
+        if let Some(analysis) = self.analyze(uri) {
+            let found = &analysis.assembler.diagnostics;
+            let errors = found.get_errors().into_iter().map(|error| (1, error.span(), error.to_string()));
+            let warnings = found.get_warnings().into_iter().map(|warning| (2, warning.span(), warning.to_string()));
+            for (severity, span, text) in errors.chain(warnings) {
+                // Problems inside a macro are shown where it is used
+                let span = span.map(|span| analysis.assembler.sources.outermost(span)).unwrap_or_default();
+                diagnostics.push(JsonValue::object(vec![
+                    ("range", Self::range(&analysis, span)),
+                    ("severity", severity.into()),
+                    ("source", "sriscasm".into()),
+                    ("message", text.into()),
+                ]));
+            }
+        }
+        Self::notification("textDocument/publishDiagnostics", JsonValue::object(vec![("uri", uri.into()), ("diagnostics", diagnostics.into())]))
+    }
+
+    fn definition(&self, uri: &str, params: &JsonValue) -> JsonValue {
+        let Some(analysis) = self.analyze(uri) else { return JsonValue::Null; };
+        let Some(lexeme) = Self::symbol_at(&analysis, params) else { return JsonValue::Null; };
+        match analysis.assembler.symbols.get(&lexeme.text).map(|symbol| analysis.assembler.sources.outermost(symbol.get_span())).filter(|span| span.line > 0) {
+            Some(span) => Self::location(&analysis, uri, span),
+            None => JsonValue::Null,
+        }
+    }
+
+    fn references(&self, uri: &str, params: &JsonValue) -> JsonValue {
+        let Some(analysis) = self.analyze(uri) else { return JsonValue::Null; };
+        let Some(lexeme) = Self::symbol_at(&analysis, params) else { return JsonValue::Null; };
+        let include_declaration = params.get("context").and_then(|context| context.get("includeDeclaration")).and_then(JsonValue::as_bool).unwrap_or(true);
+        let declaration = analysis.assembler.symbols.get(&lexeme.text).map(|symbol| symbol.get_span());
+        Self::occurrences(&analysis, &lexeme.text).into_iter()
+            .filter(|span| include_declaration || Some(*span) != declaration)
+            .map(|span| Self::location(&analysis, uri, span))
+            .collect::<Vec<JsonValue>>()
+            .into()
+    }
+
+    /// The encoding of an instruction or the value of a symbol.
+    fn hover(&self, uri: &str, params: &JsonValue) -> JsonValue {
+        let Some(analysis) = self.analyze(uri) else { return JsonValue::Null; };
+        let Some((lexeme, is_first)) = Self::lexeme_at(&analysis, params) else { return JsonValue::Null; };
+        let text = match self.isa.find(&lexeme.text).filter(|_| is_first) {
+            Some(definition) => {
+                let operands = definition.operands.iter()
+                    .filter(|kind| !matches!(kind, OperandKind::Implied(_)))
+                    .map(|kind| format!(" {:?}", kind).to_lowercase())
+                    .collect::<String>();
+                let mut text = format!("**{}**{}\n\nopcode {:#04X} ({:#010b}), class {:?}, {} byte(s)",
+                    definition.mnemonic, operands, definition.opcode, definition.opcode, InstructionClass::of(definition.opcode), 1 + definition.operands.len());
+                let entry = analysis.assembler.line_table.iter().find(|entry| entry.span.file == analysis.assembler.get_file() && entry.span.line == lexeme.span.line);
+                if let (Some(entry), Some(binary)) = (entry, &analysis.binary) {
+                    let bytes = binary[entry.address..entry.address + entry.length].iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+                    text += &format!("\n\nencoded at {:#04X} as `{}`", entry.address, bytes);
+                }
+                text
+            },
+            None => match analysis.assembler.symbols.get(&lexeme.text).filter(|_| lexeme.kind == LexemeKind::Identifier) {
+                Some(symbol) => format!("**{}** = {}", symbol.get_name(), symbol.get_value()),
+                None => return JsonValue::Null,
+            },
+        };
+        JsonValue::object(vec![
+            ("contents", JsonValue::object(vec![("kind", "markdown".into()), ("value", text.into())])),
+            ("range", Self::range(&analysis, lexeme.span)),
+        ])
+    }
+
+    /// The mnemonics of the instruction set and every symbol of the document.
+    fn completion(&self, uri: &str) -> JsonValue {
+        let mut items: Vec<JsonValue> = self.isa.get_instructions().iter().map(|definition| JsonValue::object(vec![
+            ("label", definition.mnemonic.clone().into()),
+            ("kind", KEYWORD_COMPLETION.into()),
+            ("detail", format!("opcode {:#04X}, {} byte(s)", definition.opcode, 1 + definition.operands.len()).into()),
+        ])).collect();
+        if let Some(analysis) = self.analyze(uri) {
+            items.extend(analysis.assembler.symbols.iter().filter(|symbol| symbol.get_span().line > 0).map(|symbol| JsonValue::object(vec![
+                ("label", symbol.get_name().into()),
+                ("kind", CONSTANT_COMPLETION.into()),
+                ("detail", symbol.get_value().into()),
+            ])));
+        }
+        items.into()
+    }
+
+    fn rename(&self, uri: &str, params: &JsonValue) -> Result<JsonValue, (i64, String)> {
+        let new_name = params.get("newName").and_then(JsonValue::as_str).unwrap_or_default();
+        // Registers and numbers like r1 or x1F would be read as what they look like
+        if !Lexer::is_identifier(new_name) || new_name.starts_with('$') || ArgumentParser::looks_like_literal(new_name) {
+            return Err((INVALID_PARAMS, format!("{} isn't a valid name.", new_name)));
+        }
+        if self.isa.find(new_name).is_some() {
+            return Err((INVALID_PARAMS, format!("{} is an instruction.", new_name)));
+        }
+        let Some(analysis) = self.analyze(uri) else { return Ok(JsonValue::Null); };
+        let Some(lexeme) = Self::symbol_at(&analysis, params) else { return Ok(JsonValue::Null); };
+        if lexeme.text != new_name && analysis.assembler.symbols.get(new_name).is_some() {
+            return Err((INVALID_PARAMS, format!("Symbol {} is already declared.", new_name)));
+        }
+        // Included files get edits of their own
+        let mut changes: Vec<(String, Vec<JsonValue>)> = Vec::new();
+        for span in Self::occurrences(&analysis, &lexeme.text) {
+            let file_uri = Self::uri_of_file(&analysis, uri, span.file);
+            let edit = JsonValue::object(vec![("range", Self::range(&analysis, span)), ("newText", new_name.into())]);
+            match changes.iter_mut().find(|(changed, _)| *changed == file_uri) {
+                Some((_, edits)) => edits.push(edit),
+                None => changes.push((file_uri, vec![edit])),
+            }
+        }
+        let changes = changes.into_iter().map(|(file_uri, edits)| (file_uri, edits.into())).collect();
+        Ok(JsonValue::object(vec![("changes", JsonValue::Object(changes))]))
+    }
+
+    /// The declared symbol under the cursor.
+    fn symbol_at(analysis: &Analysis, params: &JsonValue) -> Option<Lexeme> {
+        let (lexeme, _) = Self::lexeme_at(analysis, params)?;
+        let symbol = analysis.assembler.symbols.get(&lexeme.text)?;
+        (lexeme.kind == LexemeKind::Identifier && symbol.get_span().line > 0).then_some(lexeme)
+    }
+
+    /// The lexeme under the cursor and whether it is the first one of its line.
+    fn lexeme_at(analysis: &Analysis, params: &JsonValue) -> Option<(Lexeme, bool)> {
+        let position = params.get("position")?;
+        let line = position.get("line").and_then(JsonValue::as_i64)? as u32 + 1;
+        let file = analysis.assembler.get_file();
+        let text = analysis.assembler.sources.get_line(Span::new(file, line, 1, 0)).unwrap_or_default();
+        let column = Self::char_column(text, position.get("character").and_then(JsonValue::as_i64)? as u32) + 1;
+        let lexemes = Self::lexemes(analysis, file).into_iter().filter(|lexeme| lexeme.span.line == line).collect::<Vec<Lexeme>>();
+        // The cursor may also be right behind the name
+        let index = lexemes.iter().position(|lexeme| lexeme.span.column <= column && column <= lexeme.span.column + lexeme.span.length)?;
+        Some((lexemes[index].clone(), index == 0))
+    }
+
+    /// Every place the symbol is declared or used, in the document and the files it includes.
+    fn occurrences(analysis: &Analysis, name: &str) -> Vec<Span> {
+        let sources = &analysis.assembler.sources;
+        let mut names = Vec::new();
+        let mut spans = Vec::new();
+        for file in sources.get_written_files() {
+            // A file included twice is still changed once
+            if names.contains(&sources.get_name(file)) { continue; }
+            names.push(sources.get_name(file));
+            spans.extend(Self::lexemes(analysis, file).into_iter()
+                .filter(|lexeme| lexeme.kind == LexemeKind::Identifier && lexeme.text == name)
+                .map(|lexeme| lexeme.span));
+        }
+        spans
+    }
+
+    /// The lexemes of every line of the file without comments. Declarations like `loop:` and `.size` contain the name as a lexeme as well.
+    fn lexemes(analysis: &Analysis, file: usize) -> Vec<Lexeme> {
+        analysis.assembler.sources.get_lines(file).iter()
+            .filter_map(ArgumentParser::strip_comment)
+            .flat_map(|line| Lexer::tokenize(&line.text, line.span))
+            .collect()
+    }
+
+    /// The path of a `file://` URI with its percent-encoded bytes decoded, other URIs stay as they are.
+    fn path_of(uri: &str) -> String {
+        let Some(path) = uri.strip_prefix("file://") else { return uri.to_string(); };
+        let mut bytes = Vec::new();
+        let mut rest = path.as_bytes();
+        while let Some((&byte, tail)) = rest.split_first() {
+            let decoded = tail.get(..2).filter(|_| byte == b'%')
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match decoded {
+                Some(decoded) => {
+                    bytes.push(decoded);
+                    rest = &tail[2..];
+                },
+                None => {
+                    bytes.push(byte);
+                    rest = tail;
+                },
+            }
+        }
+        let path = String::from_utf8_lossy(&bytes).into_owned();
+        // file:///C:/code/main.asm on Windows
+        match path.as_bytes() {
+            [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+            _ => path,
+        }
+    }
+
+    /// The `file://` URI of a path, the inverse of `path_of`.
+    fn uri_of(path: &str) -> String {
+        let path = path.replace('\\', "/");
+        let mut uri = if path.starts_with('/') { "file://".to_string() } else { "file:///".to_string() };
+        for byte in path.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b':' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+                _ => uri += &format!("%{:02X}", byte),
+            }
+        }
+        uri
+    }
+
+    /// The URI of the document for its own file and the one of the path for included files.
+    fn uri_of_file(analysis: &Analysis, uri: &str, file: usize) -> String {
+        match file == analysis.assembler.get_file() {
+            true => uri.to_string(),
+            false => Self::uri_of(analysis.assembler.sources.get_name(file)),
+        }
+    }
+
+    fn location(analysis: &Analysis, uri: &str, span: Span) -> JsonValue {
+        JsonValue::object(vec![("uri", Self::uri_of_file(analysis, uri, span.file).into()), ("range", Self::range(analysis, span))])
+    }
+
+    /// Spans count lines and columns from 1, the protocol from 0. Its columns are UTF-16 code units instead of characters.
+    fn range(analysis: &Analysis, span: Span) -> JsonValue {
+        let position = |line: u32, character: u32| JsonValue::object(vec![("line", (line as i64).into()), ("character", (character as i64).into())]);
+        let text = analysis.assembler.sources.get_line(span).unwrap_or_default();
+        let line = span.line.saturating_sub(1);
+        let column = span.column.saturating_sub(1);
+        JsonValue::object(vec![("start", position(line, Self::utf16_column(text, column))), ("end", position(line, Self::utf16_column(text, column + span.length)))])
+    }
+
+    /// The UTF-16 column of the character column, counting from 0.
+    fn utf16_column(text: &str, column: u32) -> u32 {
+        // Spans may reach behind the end of the line
+        text.chars().chain(std::iter::repeat(' ')).take(column as usize).map(|character| character.len_utf16() as u32).sum()
+    }
+
+    /// The character column of the UTF-16 column, counting from 0.
+    fn char_column(text: &str, character: u32) -> u32 {
+        let mut units = 0;
+        text.chars().take_while(|found| {
+            units += found.len_utf16() as u32;
+            units <= character
+        }).count() as u32 + character.saturating_sub(text.encode_utf16().count() as u32)
+    }
+}
@@ -1,75 +1,557 @@
+use std::fs;
 use std::fs::File;
 use rfd::FileDialog;
 use std::io::prelude::*;
 use colored::Colorize;
 use std::env;
-use std::path::PathBuf;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use crate::archive::Archive;
+use crate::dap::DapServer;
+use crate::debugger::Debugger;
+use crate::diagnostics::Diagnostics;
+use crate::disassembler::Disassembler;
+use crate::emulator::Emulator;
+use crate::error::AssembleError;
+use crate::gdb_stub::GdbStub;
+use crate::image::{ImageFormat, ImageOptions};
+use crate::isa::Isa;
+use crate::linker::{LinkedProgram, Linker};
+use crate::listing::Listing;
+use crate::lsp::LanguageServer;
+use crate::object::ObjectFile;
+use crate::preprocessor::Preprocessor;
+use crate::source::SourceMap;
+use crate::symbol_map::SymbolMap;
+use crate::symbol_table::SymbolTable;
 
+mod archive;
 mod assembler;
+mod dap;
+mod debugger;
+mod diagnostics;
+mod disassembler;
+mod emulator;
+mod error;
+mod gdb_stub;
+mod image;
+mod lexer;
+mod linker;
+mod listing;
+mod lsp;
+mod object;
+mod preprocessor;
+mod section;
+mod source;
+mod symbol_map;
+mod symbol_table;
 mod instruction;
+mod isa;
 mod argument_parser;
 mod utility;
 mod tests;
 
-fn main() {
+/// The options followed by their value as a separate argument.
+const OPTIONS_WITH_VALUE: [&str; 13] = ["--isa", "--max-steps", "--port", "--listing", "--map", "--format", "--load-address", "--depth", "--width", "-o", "-D", "-I", "-l"];
+/// Enough for any program that fits into memory and doesn't loop forever.
+const DEFAULT_MAX_STEPS: u64 = 10_000_000;
+/// The port gdb usually connects to.
+const DEFAULT_GDB_PORT: u16 = 1234;
+
+/// What the program was started for.
+#[derive(Default, PartialEq)]
+enum Mode {
+    #[default]
+    Assemble,
+    /// `sriscasm disasm file.bin` prints the assembly of a binary.
+    Disassemble,
+    /// `sriscasm emu file.bin` (or `sriscemu file.bin`) runs a binary.
+    Emulate,
+    /// `sriscasm debug file.asm` (or a .bin without labels) runs a program command by command.
+    Debug,
+    /// `sriscasm gdb file.asm` waits for gdb to connect and control the emulator.
+    GdbServer,
+    /// `sriscasm dap` lets an editor debug programs over the standard input and output.
+    Dap,
+    /// `sriscasm lsp` helps editors with .asm files over the standard input and output.
+    Lsp,
+    /// `sriscasm link a.o b.o lib.a` (or `sriscld ...`) links object files and the members of archives they need into one program.
+    Link,
+    /// `sriscasm ar lib.a a.o b.o` (or `sriscar ...`) bundles object files into an archive.
+    Archive,
+}
+
+/// Everything that can be chosen on the command line.
+#[derive(Default)]
+struct Options {
+    mode: Mode,
+    input_path: PathBuf,
+    perform_test: bool,
+    /// A TOML or JSON description of the instruction set to use instead of the built-in one.
+    isa_path: Option<PathBuf>,
+    /// How many instructions the emulator may execute before it gives up.
+    max_steps: u64,
+    /// The local TCP port the gdb stub listens on.
+    port: u16,
+    /// Where to write the listing of an assembled file.
+    listing_path: Option<PathBuf>,
+    /// Where to write the symbol map, as JSON if the file ends with .json.
+    map_path: Option<PathBuf>,
+    /// The output format given with `--format`: bin, ihex, srec, logisim, logisim-plain, readmemh, readmemb, mif or coe (see `ImageFormat::from_name`).
+    format: Option<String>,
+    /// Where the program is placed in an Intel HEX or S-record image.
+    load_address: Option<String>,
+    /// The number of words in the memory the image is for.
+    depth: Option<String>,
+    /// The bits per word of the memory the image is for.
+    width: Option<String>,
+    /// Whether code and data go into separate ROM and RAM images.
+    split: bool,
+    /// Constants given with `-D NAME=value`.
+    defines: Vec<String>,
+    /// Directories given with `-I` to look for included files in.
+    include_paths: Vec<PathBuf>,
+    /// Whether to write a relocatable object file instead of a program.
+    object: bool,
+    /// The object files to link or to put into an archive.
+    object_paths: Vec<PathBuf>,
+    /// The archives to link, or the one to write.
+    archive_paths: Vec<PathBuf>,
+    /// Archives given with `-l` that the assembled file is linked with.
+    libraries: Vec<PathBuf>,
+    /// Where the linked program goes, given with `-o`.
+    output_path: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
     let args: Vec<String> = env::args().collect();
-    let mut input_path: PathBuf = Default::default();
-    let mut perform_test = false;
-    get_inputs(args, &mut input_path, &mut perform_test);
+    let options = match get_inputs(args) {
+        Ok(options) => options,
+        Err(error) => return report(&error.into(), &SourceMap::new()),
+    };
 
-    if perform_test {
-        println!("{}", tests::test::test_all());
-        return;
+    if options.perform_test {
+        let passed = tests::test::test_all();
+        println!("{}", passed);
+        return if passed { ExitCode::SUCCESS } else { ExitCode::FAILURE };
     }
 
-    let display = input_path.display();
-
-    // Open the path in read-only mode, returns `io::Result<File>`
-    let mut file = match File::open(&input_path) {
-        Err(reason) => panic!("Couldn't open {}: {}", display, reason),
-        Ok(file) => file,
+    let isa = match options.isa_path.as_deref().map(Isa::load).unwrap_or_else(|| Ok(Isa::builtin())) {
+        Ok(isa) => isa,
+        Err(error) => return report(&error.into(), &SourceMap::new()),
+    };
+    let image_options = ImageOptions::default().with_format(options.format.as_deref())
+        .and_then(|image_options| image_options.with_load_address(options.load_address.as_deref()))
+        .and_then(|image_options| image_options.with_depth(options.depth.as_deref()))
+        .and_then(|image_options| image_options.with_width(options.width.as_deref()))
+        .map(|image_options| image_options.with_split(options.split));
+    let image_options = match image_options {
+        Ok(image_options) => image_options,
+        Err(error) => return report(&error.into(), &SourceMap::new()),
     };
+    let defines = match options.defines.iter().map(|define| Preprocessor::parse_define(define)).collect::<Result<Vec<(String, String)>, AssembleError>>() {
+        Ok(defines) => defines,
+        Err(error) => return report(&error.into(), &SourceMap::new()),
+    };
+    match options.mode {
+        Mode::Disassemble => return disassemble_file(&options.input_path, &isa),
+        Mode::Emulate => return emulate_file(&options.input_path, isa, options.max_steps),
+        Mode::Debug => return debug_file(&options.input_path, isa, defines, &options.include_paths, options.max_steps),
+        Mode::GdbServer => return serve_gdb(&options.input_path, isa, defines, &options.include_paths, options.max_steps, options.port),
+        Mode::Dap => return serve_dap(isa, options.max_steps),
+        Mode::Lsp => return serve_lsp(isa),
+        Mode::Link | Mode::Archive => {
+            let diagnostics = if options.mode == Mode::Link { link_files(&options, &image_options) } else { archive_files(&options) };
+            return report(&diagnostics, &SourceMap::new());
+        },
+        Mode::Assemble => {},
+    }
+    let (diagnostics, sources) = assemble_file(&options, &image_options, isa, defines);
+    report(&diagnostics, &sources)
+}
+
+/// Prints the errors and warnings with how many there were, the exit code fails if there was an error.
+fn report(diagnostics: &Diagnostics, sources: &SourceMap) -> ExitCode {
+    diagnostics.report(sources);
+    if diagnostics.has_errors() { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
 
-    // Read the file contents into a string, returns `io::Result<usize>`
+/// Assembles the file next to itself as .bin or one of the image formats, split into ROM and RAM if asked for, or as .o object file
+/// (and the listing and symbol map if asked for) and returns everything that was found on the way.
+/// With libraries given, the file is assembled as a module and linked with the members of the libraries it needs.
+fn assemble_file(options: &Options, image_options: &ImageOptions, isa: Isa, defines: Vec<(String, String)>) -> (Diagnostics, SourceMap) {
+    let input_path = &options.input_path;
+    let mut diagnostics = Diagnostics::new();
+
+    // Open the path in read-only mode and read the file contents into a string
     let mut asm = String::new();
-    match file.read_to_string(&mut asm) {
-        Err(reason) => panic!("Couldn't read {}: {}", display, reason),
-        Ok(_) => {/*Do nothing*/},
+    if let Err(reason) = File::open(input_path).and_then(|mut file| file.read_to_string(&mut asm)) {
+        diagnostics.error(AssembleError::Io { path: input_path.display().to_string(), reason: reason.to_string() });
+        return (diagnostics, SourceMap::new());
+    }
+
+    let mut assembler = assembler::Assembler::new(input_path.display().to_string(), asm).with_isa(isa).with_defines(defines)
+        .with_include_paths(options.include_paths.clone());
+    let (binary, mut outputs) = if options.object {
+        let Ok(object) = assembler.assemble_object() else { return (assembler.diagnostics, assembler.sources); };
+        let binary = [object.text.as_slice(), object.data.as_slice()].concat();
+        (binary, vec![(input_path.with_extension("o"), (object.to_json().to_string() + "\n").into_bytes())])
+    } else if !options.libraries.is_empty() {
+        // The listing and the map would only know the addresses of the file before it was linked
+        if let Some(option) = [("--listing", &options.listing_path), ("--map", &options.map_path)].iter().find(|(_, path)| path.is_some()).map(|(option, _)| option) {
+            assembler.diagnostics.error(AssembleError::BadOption { option: option.to_string(), reason: "can't be combined with -l".to_string() });
+            return (assembler.diagnostics, assembler.sources);
+        }
+        let Ok(object) = assembler.assemble_object() else { return (assembler.diagnostics, assembler.sources); };
+        let linked = link(vec![object], &options.libraries)
+            .and_then(|program| Ok((program.binary.clone(), image_options.outputs(input_path, &program.binary, program.text_length).map_err(|error| vec![error])?)));
+        match linked {
+            Ok(linked) => linked,
+            Err(errors) => {
+                errors.into_iter().for_each(|error| assembler.diagnostics.error(error));
+                return (assembler.diagnostics, assembler.sources);
+            },
+        }
+    } else {
+        let Ok(binary) = assembler.assemble() else { return (assembler.diagnostics, assembler.sources); };
+        match image_options.outputs(input_path, &binary, assembler.get_text_length()) {
+            Ok(outputs) => (binary, outputs),
+            Err(error) => {
+                assembler.diagnostics.error(error);
+                return (assembler.diagnostics, assembler.sources);
+            },
+        }
+    };
+    if let Some(listing_path) = &options.listing_path {
+        outputs.push((listing_path.clone(), Listing::render(&assembler, &binary).into_bytes()));
+    }
+    if let Some(map_path) = &options.map_path {
+        outputs.push((map_path.clone(), SymbolMap::render(&assembler, map_path).into_bytes()));
+    }
+    diagnostics = assembler.diagnostics;
+    for (path, content) in outputs {
+        if let Err(reason) = fs::write(&path, content) {
+            diagnostics.error(AssembleError::Io { path: path.display().to_string(), reason: reason.to_string() });
+        }
+    }
+    (diagnostics, assembler.sources)
+}
+
+/// Links the object files into a program written like an assembled one, next to the first object file unless `-o` says otherwise.
+fn link_files(options: &Options, image_options: &ImageOptions) -> Diagnostics {
+    let mut diagnostics = Diagnostics::new();
+    let Some(output_path) = options.output_path.as_ref().or(options.object_paths.first()) else {
+        diagnostics.error(AssembleError::BadOption { option: "link".to_string(), reason: "needs at least one .o file".to_string() });
+        return diagnostics;
+    };
+    let mut modules = Vec::new();
+    for path in options.object_paths.iter() {
+        match ObjectFile::read(path) {
+            Ok(module) => modules.push(module),
+            Err(error) => diagnostics.error(error),
+        }
+    }
+    if diagnostics.has_errors() { return diagnostics; }
+
+    let outputs = link(modules, &options.archive_paths)
+        .and_then(|program| image_options.outputs(output_path, &program.binary, program.text_length).map_err(|error| vec![error]));
+    match outputs {
+        Ok(outputs) => for (path, content) in outputs {
+            if let Err(reason) = fs::write(&path, content) {
+                diagnostics.error(AssembleError::Io { path: path.display().to_string(), reason: reason.to_string() });
+            }
+        },
+        Err(errors) => errors.into_iter().for_each(|error| diagnostics.error(error)),
+    }
+    diagnostics
+}
+
+/// Links the modules with the members of the archives they need, in the order they are needed.
+fn link(mut modules: Vec<ObjectFile>, archive_paths: &[PathBuf]) -> Result<LinkedProgram, Vec<AssembleError>> {
+    let archives = archive_paths.iter().map(|path| Archive::read(path)).collect::<Result<Vec<Archive>, AssembleError>>().map_err(|error| vec![error])?;
+    let members = Archive::select(&modules, &archives);
+    modules.extend(members);
+    Linker::link(&modules)
+}
+
+/// Writes the object files into the archive, members already in it are replaced when they have the same file name.
+fn archive_files(options: &Options) -> Diagnostics {
+    let mut diagnostics = Diagnostics::new();
+    let Some(archive_path) = options.archive_paths.first() else {
+        diagnostics.error(AssembleError::BadOption { option: "ar".to_string(), reason: "needs the .a file to write followed by the .o files".to_string() });
+        return diagnostics;
+    };
+    let mut archive = if archive_path.exists() {
+        match Archive::read(archive_path) {
+            Ok(archive) => archive,
+            Err(error) => {
+                diagnostics.error(error);
+                return diagnostics;
+            },
+        }
+    } else {
+        Archive::default()
+    };
+    for path in options.object_paths.iter() {
+        let name = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        match ObjectFile::read(path) {
+            Ok(object) => archive.add(name, object),
+            Err(error) => diagnostics.error(error),
+        }
     }
+    if diagnostics.has_errors() { return diagnostics; }
+    if let Err(reason) = fs::write(archive_path, archive.to_json().to_string() + "\n") {
+        diagnostics.error(AssembleError::Io { path: archive_path.display().to_string(), reason: reason.to_string() });
+    }
+    diagnostics
+}
+
+/// Prints the disassembly of the binary, Intel HEX or S-record image to the standard output.
+fn disassemble_file(input_path: &Path, isa: &Isa) -> ExitCode {
+    match image::read_image(input_path) {
+        Ok(binary) => {
+            print!("{}", Disassembler::disassemble(&binary, isa));
+            ExitCode::SUCCESS
+        },
+        Err(error) => report(&error.into(), &SourceMap::new()),
+    }
+}
+
+/// Runs the binary or image until it halts and prints what it wrote, followed by the registers.
+fn emulate_file(input_path: &Path, isa: Isa, max_steps: u64) -> ExitCode {
+    let binary = match image::read_image(input_path) {
+        Ok(binary) => binary,
+        Err(error) => return report(&error.into(), &SourceMap::new()),
+    };
+    let result = Emulator::new(&binary, isa).and_then(|mut emulator| {
+        let result = emulator.run(max_steps);
+        print!("{}", String::from_utf8_lossy(&emulator.output));
+        _ = std::io::stdout().flush();
+        let registers = emulator.registers.iter().enumerate().map(|(i, value)| format!("r{}={}", i, value)).collect::<Vec<String>>().join(" ");
+        eprintln!("\n{}", registers);
+        result.map(|_| emulator.steps)
+    });
+    match result {
+        Ok(steps) => {
+            eprintln!("{}", format!("Halted after {} instructions.", steps).green());
+            ExitCode::SUCCESS
+        },
+        Err(fault) => {
+            eprintln!("{} {}", "error:".red().bold(), fault);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+/// Reads a binary or image, or assembles the source to also know its symbols. Problems are reported right away.
+fn load_program(input_path: &Path, isa: &Isa, defines: Vec<(String, String)>, include_paths: &[PathBuf]) -> Option<(Vec<u8>, SymbolTable)> {
+    let mut diagnostics = Diagnostics::new();
+    let mut sources = SourceMap::new();
+    let is_image = ImageFormat::from_path(input_path) != ImageFormat::Binary || input_path.extension().is_some_and(|extension| extension == "bin");
+    let program = if is_image {
+        image::read_image(input_path).map(|binary| (binary, SymbolTable::new())).map_err(|error| diagnostics.error(error)).ok()
+    } else {
+        let program = fs::read_to_string(input_path).map(|asm| {
+            let mut assembler = assembler::Assembler::new(input_path.display().to_string(), asm).with_isa(isa.clone()).with_defines(defines)
+                .with_include_paths(include_paths.to_vec());
+            let result = assembler.assemble();
+            diagnostics = assembler.diagnostics;
+            sources = assembler.sources;
+            result.ok().map(|binary| (binary, assembler.symbols))
+        });
+        program.unwrap_or_else(|reason| {
+            diagnostics.error(AssembleError::Io { path: input_path.display().to_string(), reason: reason.to_string() });
+            None
+        })
+    };
+    diagnostics.report(&sources);
+    program
+}
+
+/// Loads the program into a new emulator, printing the fault if it doesn't fit.
+fn load_emulator(binary: &[u8], isa: Isa) -> Option<Emulator> {
+    Emulator::new(binary, isa).map_err(|fault| eprintln!("{} {}", "error:".red().bold(), fault)).ok()
+}
+
+/// Loads a binary, or assembles the source to know its labels, and reads debugger commands from the standard input.
+fn debug_file(input_path: &Path, isa: Isa, defines: Vec<(String, String)>, include_paths: &[PathBuf], max_steps: u64) -> ExitCode {
+    let Some((binary, symbols)) = load_program(input_path, &isa, defines, include_paths) else { return ExitCode::FAILURE; };
+    let Some(emulator) = load_emulator(&binary, isa) else { return ExitCode::FAILURE; };
+
+    let mut debugger = Debugger::new(emulator, symbols, binary.len(), max_steps);
+    println!("{}", debugger.current());
+    let mut last_command = String::new();
+    loop {
+        print!("(sdb) ");
+        _ = std::io::stdout().flush();
+        let mut command = String::new();
+        if std::io::stdin().read_line(&mut command).unwrap_or(0) == 0 { break; }
+        // An empty line repeats the last command, which makes stepping through code easier
+        if command.trim().is_empty() {
+            command = last_command.clone();
+        }
+        let Some(output) = debugger.execute(&command) else { break; };
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+        last_command = command;
+    }
+    ExitCode::SUCCESS
+}
 
-    let mut assembler = assembler::Assembler::new(asm);
-    assembler.assemble();
+/// Waits for a single gdb connection on the local port and lets it control the program.
+fn serve_gdb(input_path: &Path, isa: Isa, defines: Vec<(String, String)>, include_paths: &[PathBuf], max_steps: u64, port: u16) -> ExitCode {
+    let Some((binary, _)) = load_program(input_path, &isa, defines, include_paths) else { return ExitCode::FAILURE; };
+    let Some(emulator) = load_emulator(&binary, isa) else { return ExitCode::FAILURE; };
+    let result = TcpListener::bind(("127.0.0.1", port)).and_then(|listener| {
+        eprintln!("Waiting for gdb, connect with: target remote localhost:{}", port);
+        let (stream, address) = listener.accept()?;
+        eprintln!("gdb connected from {}.", address);
+        let mut reader = BufReader::new(stream.try_clone()?);
+        // gdb sends a single 0x03 byte when Ctrl-C is pressed, it is looked for without waiting while the program runs
+        let mut poller = stream.try_clone()?;
+        let interrupted = move || {
+            let mut byte = [0];
+            let pending = poller.set_nonblocking(true).and_then(|_| poller.peek(&mut byte));
+            _ = poller.set_nonblocking(false);
+            matches!(pending, Ok(1)) && byte[0] == 0x03 && poller.read_exact(&mut byte).is_ok()
+        };
+        let mut writer = stream;
+        GdbStub::new(emulator, max_steps).with_interrupt(interrupted).serve(&mut reader, &mut writer)
+    });
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(reason) => {
+            eprintln!("{} gdb connection on port {} failed: {}", "error:".red().bold(), port, reason);
+            ExitCode::FAILURE
+        },
+    }
+}
 
-    let binary: Vec<u8> = assembler.output;
-    let binding = input_path.to_str().unwrap().to_string().clone();
-    let characters = binding.chars();
-    let last_accepted_character = characters.clone().count() - 4;
-    let store_path = characters.collect::<Vec<char>>()[0..last_accepted_character].iter().collect::<String>() + ".bin";
-    let binary_file = File::create(store_path);
-    _ = binary_file.unwrap().write_all(&binary);
+/// Speaks the Debug Adapter Protocol on the standard input and output, the program is chosen by the launch request.
+fn serve_dap(isa: Isa, max_steps: u64) -> ExitCode {
+    let result = DapServer::new(isa, max_steps).serve(&mut std::io::stdin().lock(), &mut std::io::stdout().lock());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(reason) => {
+            eprintln!("{} {}", "error:".red().bold(), reason);
+            ExitCode::FAILURE
+        },
+    }
 }
 
-fn get_inputs(args: Vec<String>, input_path: &mut PathBuf, is_test: &mut bool) {
-    if args.contains(&String::from("--test")) {
-        *is_test = true;
+/// Speaks the Language Server Protocol on the standard input and output.
+fn serve_lsp(isa: Isa) -> ExitCode {
+    let result = LanguageServer::new(isa).serve(&mut std::io::stdin().lock(), &mut std::io::stdout().lock());
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(reason) => {
+            eprintln!("{} {}", "error:".red().bold(), reason);
+            ExitCode::FAILURE
+        },
     }
+}
+
+fn get_inputs(args: Vec<String>) -> Result<Options, AssembleError> {
+    let program_name = args.first().and_then(|path| PathBuf::from(path).file_stem().map(|name| name.to_string_lossy().to_string()));
+    let mode = match args.get(1).map(String::as_str) {
+        _ if program_name.as_deref() == Some("sriscemu") => Mode::Emulate,
+        _ if program_name.as_deref() == Some("sriscld") => Mode::Link,
+        _ if program_name.as_deref() == Some("sriscar") => Mode::Archive,
+        Some("disasm") => Mode::Disassemble,
+        Some("emu") => Mode::Emulate,
+        Some("debug") => Mode::Debug,
+        Some("gdb") => Mode::GdbServer,
+        Some("dap") => Mode::Dap,
+        Some("lsp") => Mode::Lsp,
+        Some("link") => Mode::Link,
+        Some("ar") => Mode::Archive,
+        _ => Mode::Assemble,
+    };
+    let mut options = Options{
+        mode,
+        max_steps: get_optional_parameter("--max-steps", &args).and_then(|steps| steps.parse().ok()).unwrap_or(DEFAULT_MAX_STEPS),
+        perform_test: args.contains(&String::from("--test")),
+        isa_path: get_optional_parameter("--isa", &args).map(PathBuf::from),
+        port: get_optional_parameter("--port", &args).and_then(|port| port.parse().ok()).unwrap_or(DEFAULT_GDB_PORT),
+        listing_path: get_optional_parameter("--listing", &args).map(PathBuf::from),
+        map_path: get_optional_parameter("--map", &args).map(PathBuf::from),
+        format: get_optional_parameter("--format", &args),
+        load_address: get_optional_parameter("--load-address", &args),
+        depth: get_optional_parameter("--depth", &args),
+        width: get_optional_parameter("--width", &args),
+        split: args.contains(&String::from("--split")),
+        defines: get_prefixed_parameters("-D", &args),
+        include_paths: get_prefixed_parameters("-I", &args).into_iter().map(PathBuf::from).collect(),
+        object: args.contains(&String::from("--object")),
+        object_paths: get_paths_with_extension("o", &args),
+        archive_paths: get_paths_with_extension("a", &args),
+        libraries: get_prefixed_parameters("-l", &args).into_iter().map(PathBuf::from).collect(),
+        output_path: get_optional_parameter("-o", &args).map(PathBuf::from),
+        ..Default::default()
+    };
     if args.contains(&"-v".to_string()){
         // Visual setup
         let dialog = FileDialog::new();
         if let Some(path) = dialog.pick_file(){
-            *input_path = path;
+            options.input_path = path;
         }
-        return;
+        return Ok(options);
     }
 
     // Command line args setup
-    *input_path = PathBuf::from(args.clone().last().unwrap());
-    /*if !input_path.(".asm"){
-        let error = "File name must end with .asm".red().to_string();
-        panic!("{}", error);
-    }*/
+    if options.perform_test || matches!(options.mode, Mode::Dap | Mode::Lsp | Mode::Link | Mode::Archive) {
+        return Ok(options);
+    }
+    // The word choosing the mode isn't a file, unless the program is named after the mode
+    let named_by_argument = options.mode != Mode::Assemble && !matches!(program_name.as_deref(), Some("sriscemu" | "sriscld" | "sriscar"));
+    let inputs = get_positional_arguments(if named_by_argument { &args[1..] } else { &args });
+    match inputs.as_slice() {
+        [input] => options.input_path = PathBuf::from(input),
+        _ => return Err(AssembleError::BadInputs { inputs }),
+    }
+    Ok(options)
+}
+
+/// Every value of an option that can be given more than once, like `-D NAME=value` and `-DNAME=value`.
+fn get_prefixed_parameters(prefix: &str, args: &[String]) -> Vec<String> {
+    let mut values = Vec::new();
+    let mut arguments = args.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.strip_prefix(prefix) {
+            Some("") => values.extend(arguments.next().cloned()),
+            Some(value) => values.push(value.to_string()),
+            None => {},
+        }
+    }
+    values
+}
 
+/// The arguments naming files with the extension, like the .o files to link.
+fn get_paths_with_extension(extension: &str, args: &[String]) -> Vec<PathBuf> {
+    get_positional_arguments(args).into_iter().map(PathBuf::from).filter(|path| path.extension().is_some_and(|found| found == extension)).collect()
+}
+
+/// The arguments that are neither an option nor the value of one, like `out.o` in `-o out.o`.
+fn get_positional_arguments(args: &[String]) -> Vec<String> {
+    let mut positional = Vec::new();
+    let mut arguments = args.iter().skip(1);
+    while let Some(argument) = arguments.next() {
+        if OPTIONS_WITH_VALUE.contains(&argument.as_str()) {
+            arguments.next();
+        } else if !argument.starts_with('-') {
+            positional.push(argument.clone());
+        }
+    }
+    positional
+}
 
+/// The value following the parameter, if the parameter was given.
+fn get_optional_parameter(name: &str, args: &[String]) -> Option<String> {
+    let position = args.iter().position(|argument| argument == name)?;
+    args.get(position + 1).cloned()
 }
 
 fn get_parameter(name: &str, args: Vec<String>) -> String {
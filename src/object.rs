@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::argument_parser::ArgumentParser;
+use crate::diagnostics::Diagnostics;
+use crate::error::AssembleError;
+use crate::isa::OperandKind;
+use crate::lexer::{LexemeKind, Lexer};
+use crate::section::{Endianness, FixupKind};
+use crate::source::{SourceLine, Token};
+use crate::symbol_map::SymbolKind;
+use crate::symbol_table::SymbolTable;
+use crate::utility::json::JsonValue;
+
+/// Written into every object file so other JSON files aren't mistaken for one.
+const FORMAT: &str = "sriscasm-object";
+const VERSION: i64 = 1;
+/// How often a constant may refer to another constant while looking for addresses inside of it.
+const MAX_DEPENDENCY_DEPTH: u32 = 64;
+
+/// The section a symbol or relocation belongs to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectSection {
+    Text,
+    Data,
+}
+
+impl ObjectSection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectSection::Text => "text",
+            ObjectSection::Data => "data",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<ObjectSection> {
+        match name {
+            "text" => Some(ObjectSection::Text),
+            "data" => Some(ObjectSection::Data),
+            _ => None,
+        }
+    }
+}
+
+/// A label, data name or constant of a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The offset into its section for labels and data, the value as written for constants.
+    pub value: String,
+    /// Declared with `.global`, so other modules can use it.
+    pub global: bool,
+    pub line: u32,
+}
+
+/// A value the linker has to fill in because it depends on where the sections end up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relocation {
+    pub section: ObjectSection,
+    /// Where the value goes, counted from the start of the section.
+    pub offset: usize,
+    pub kind: FixupKind,
+    /// The operand or data value as written, resolved with the symbols of the module.
+    pub expression: String,
+    /// The value of `$`, counted from the start of the data section.
+    pub location_counter: Option<i64>,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A separately assembled module: its sections with the relocatable values left at 0, its symbols and the relocations.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ObjectFile {
+    /// The assembled file, to tell where problems found while linking come from.
+    pub source: String,
+    pub text: Vec<u8>,
+    pub data: Vec<u8>,
+    pub symbols: Vec<ObjectSymbol>,
+    /// Symbols declared `.extern`, which another module has to declare `.global`.
+    pub externs: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl ObjectFile {
+    /// Whether the value of the expression depends on an address, which is only known once the modules are linked.
+    /// Constants are followed to the symbols they use.
+    pub fn is_relocatable(expression: &str, symbols: &SymbolTable, kinds: &HashMap<String, SymbolKind>, externs: &[String]) -> bool {
+        Self::depends_on_address(expression, symbols, kinds, externs, 0)
+    }
+
+    fn depends_on_address(expression: &str, symbols: &SymbolTable, kinds: &HashMap<String, SymbolKind>, externs: &[String], depth: u32) -> bool {
+        Lexer::tokenize(expression, Default::default()).iter().filter(|lexeme| lexeme.kind == LexemeKind::Identifier).any(|lexeme| {
+            let name = lexeme.text.as_str();
+            if name == "$" || name == "data_offset" || externs.iter().any(|external| external == name) { return true; }
+            match (kinds.get(name), symbols.get(name)) {
+                (Some(SymbolKind::Label | SymbolKind::Data), _) => true,
+                (Some(SymbolKind::Constant), Some(symbol)) => depth < MAX_DEPENDENCY_DEPTH && Self::depends_on_address(&symbol.get_value(), symbols, kinds, externs, depth + 1),
+                _ => false,
+            }
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let symbols: Vec<JsonValue> = self.symbols.iter().map(|symbol| JsonValue::object(vec![
+            ("name", symbol.name.clone().into()),
+            ("kind", symbol.kind.name().into()),
+            ("value", symbol.value.clone().into()),
+            ("global", symbol.global.into()),
+            ("line", (symbol.line as i64).into()),
+        ])).collect();
+        let relocations: Vec<JsonValue> = self.relocations.iter().map(|relocation| {
+            let (kind, width, endianness) = match relocation.kind {
+                FixupKind::Operand(OperandKind::Register) => ("register", 1, Endianness::Little),
+                FixupKind::Operand(_) => ("value", 1, Endianness::Little),
+                FixupKind::Data { width, endianness } => ("data", width, endianness),
+            };
+            JsonValue::object(vec![
+                ("section", relocation.section.name().into()),
+                ("offset", (relocation.offset as i64).into()),
+                ("kind", kind.into()),
+                ("width", (width as i64).into()),
+                ("endian", (if endianness == Endianness::Big { "big" } else { "little" }).into()),
+                ("expression", relocation.expression.clone().into()),
+                ("location", relocation.location_counter.map(JsonValue::from).unwrap_or(JsonValue::Null)),
+                ("line", (relocation.line as i64).into()),
+                ("column", (relocation.column as i64).into()),
+            ])
+        }).collect();
+        JsonValue::object(vec![
+            ("format", FORMAT.into()),
+            ("version", VERSION.into()),
+            ("source", self.source.clone().into()),
+            ("text", hex(&self.text).into()),
+            ("data", hex(&self.data).into()),
+            ("symbols", symbols.into()),
+            ("externs", self.externs.iter().map(|name| JsonValue::from(name.as_str())).collect::<Vec<JsonValue>>().into()),
+            ("relocations", relocations.into()),
+        ])
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<ObjectFile, String> {
+        if json.get("format").and_then(JsonValue::as_str) != Some(FORMAT) {
+            return Err("it isn't an object file written by sriscasm".to_string());
+        }
+        if json.get("version").and_then(JsonValue::as_i64) != Some(VERSION) {
+            return Err(format!("only version {} is supported", VERSION));
+        }
+        let text = |value: &JsonValue, key: &str| value.get(key).and_then(JsonValue::as_str).map(str::to_string).ok_or_else(|| format!("{} is missing", key));
+        let number = |value: &JsonValue, key: &str| value.get(key).and_then(JsonValue::as_i64).ok_or_else(|| format!("{} is missing", key));
+        let array = |key: &str| json.get(key).and_then(JsonValue::as_array).ok_or_else(|| format!("{} is missing", key));
+
+        let mut symbols = Vec::new();
+        for symbol in array("symbols")? {
+            let kind = match text(symbol, "kind")?.as_str() {
+                "label" => SymbolKind::Label,
+                "data" => SymbolKind::Data,
+                "constant" => SymbolKind::Constant,
+                other => return Err(format!("{} isn't a kind of symbol", other)),
+            };
+            let global = symbol.get("global").and_then(JsonValue::as_bool).unwrap_or(false);
+            symbols.push(ObjectSymbol{name: text(symbol, "name")?, kind, value: text(symbol, "value")?, global, line: number(symbol, "line")? as u32});
+        }
+        let mut relocations = Vec::new();
+        for relocation in array("relocations")? {
+            let section = ObjectSection::from_name(&text(relocation, "section")?).ok_or("a relocation has no valid section")?;
+            let endianness = if text(relocation, "endian")? == "big" { Endianness::Big } else { Endianness::Little };
+            let kind = match text(relocation, "kind")?.as_str() {
+                "register" => FixupKind::Operand(OperandKind::Register),
+                "value" => FixupKind::Operand(OperandKind::Value),
+                "data" => FixupKind::Data { width: number(relocation, "width")?.clamp(1, 4) as usize, endianness },
+                other => return Err(format!("{} isn't a kind of relocation", other)),
+            };
+            relocations.push(Relocation{
+                section,
+                offset: usize::try_from(number(relocation, "offset")?).map_err(|_| "a relocation has a negative offset")?,
+                kind,
+                expression: text(relocation, "expression")?,
+                location_counter: relocation.get("location").and_then(JsonValue::as_i64),
+                line: number(relocation, "line")? as u32,
+                column: number(relocation, "column")? as u32,
+            });
+        }
+        let object = ObjectFile{
+            source: text(json, "source")?,
+            text: unhex(&text(json, "text")?)?,
+            data: unhex(&text(json, "data")?)?,
+            symbols,
+            externs: array("externs")?.iter().filter_map(JsonValue::as_str).map(str::to_string).collect(),
+            relocations,
+        };
+        // A broken offset would otherwise only show up as a panic while linking
+        for relocation in object.relocations.iter() {
+            let length = if relocation.section == ObjectSection::Text { object.text.len() } else { object.data.len() };
+            if relocation.offset.checked_add(relocation.kind.width()).is_none_or(|end| end > length) {
+                return Err(format!("the relocation at line {} lies outside of the {} section", relocation.line, relocation.section.name()));
+            }
+        }
+        Ok(object)
+    }
+
+    /// Reads an object file written by `--object`.
+    pub fn read(path: &Path) -> Result<ObjectFile, AssembleError> {
+        let content = fs::read_to_string(path).map_err(|reason| AssembleError::Io { path: path.display().to_string(), reason: reason.to_string() })?;
+        JsonValue::parse(&content).and_then(|json| Self::from_json(&json))
+            .map_err(|reason| AssembleError::BadObject { path: path.display().to_string(), reason })
+    }
+}
+
+/// The names given to `.global` and `.extern`, which decide what a module shares with the others.
+#[derive(Debug, Clone, Default)]
+pub struct Linkage {
+    pub globals: Vec<Token>,
+    pub externs: Vec<Token>,
+}
+
+impl Linkage {
+    /// Takes the `.global name` and `.extern name` lines out of the code, they can be anywhere and name several symbols.
+    pub fn collect(lines: Vec<SourceLine>, diagnostics: &mut Diagnostics) -> (Vec<SourceLine>, Linkage) {
+        let mut linkage = Linkage::default();
+        let mut code = Vec::new();
+        for line in lines {
+            let tokens = ArgumentParser::strip_comment(&line).map(|line| ArgumentParser::line_to_tokens(&line)).unwrap_or_default();
+            let names = match tokens.first().map(|token| token.text.as_str()) {
+                Some(".global") => &mut linkage.globals,
+                Some(".extern") => &mut linkage.externs,
+                _ => {
+                    code.push(line);
+                    continue;
+                },
+            };
+            if tokens.len() < 2 {
+                diagnostics.error(AssembleError::BadDirective { directive: line.text.clone(), reason: format!("{} needs the names of the symbols.", tokens[0].text), span: tokens[0].span });
+            }
+            for token in tokens[1..].iter() {
+                let name = token.text.trim_end_matches(',');
+                if !Lexer::is_identifier(name) || ArgumentParser::looks_like_literal(name) {
+                    diagnostics.error(AssembleError::BadDirective { directive: line.text.clone(), reason: format!("{} isn't a valid symbol name.", name), span: token.span });
+                    continue;
+                }
+                names.push(Token{text: name.to_string(), span: token.span});
+            }
+        }
+        (code, linkage)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(2) {
+        return Err("a section has an odd number of hex digits".to_string());
+    }
+    (0..text.len()).step_by(2)
+        .map(|i| text.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| "a section isn't hexadecimal".to_string())
+}
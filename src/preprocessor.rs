@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::argument_parser::ArgumentParser;
+use crate::diagnostics::Diagnostics;
+use crate::error::AssembleError;
+use crate::isa::Isa;
+use crate::lexer::{LexemeKind, Lexer};
+use crate::source::{Expansion, SourceLine, SourceMap, Span, Token};
+use crate::symbol_table::SymbolTable;
+use crate::utility::expression::Expression;
+use crate::utility::replacement::Replacement;
+
+/// How deep macros may use other macros before they are considered to expand forever.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// A `.macro name parameter ... .endm` definition.
+struct Macro {
+    name: String,
+    parameters: Vec<String>,
+    body: Vec<SourceLine>,
+    span: Span,
+}
+
+/// An open `.if`, `.ifdef` or `.ifndef` block.
+struct Condition {
+    /// Whether the lines of the current branch are assembled.
+    active: bool,
+    /// Whether one of the branches so far was (or could have been) taken, so the following ones aren't.
+    taken: bool,
+    else_seen: bool,
+    span: Span,
+}
+
+/// Reads included files, expands macros and drops the branches of conditional blocks that aren't taken
+/// before the sections are laid out, so the sections only see plain instructions and data.
+pub struct Preprocessor<'a> {
+    isa: &'a Isa,
+    macros: HashMap<String, Macro>,
+    /// The macro whose body is being collected.
+    definition: Option<Macro>,
+    /// Counts the expansions, which gives the labels inside each one a unique suffix.
+    expansions: usize,
+    /// Constants declared so far and the ones given on the command line, for `.if` and `.ifdef`.
+    constants: SymbolTable,
+    conditions: Vec<Condition>,
+    /// The number of conditions that were open when the current macro expansion started, they can't be closed from inside of it.
+    base: usize,
+    /// Directories searched for included files that aren't next to the including file.
+    include_paths: Vec<PathBuf>,
+    /// The files being read, the assembled file first, to catch files that include themselves.
+    including: Vec<PathBuf>,
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(isa: &'a Isa) -> Preprocessor<'a> {
+        Preprocessor{isa, macros: HashMap::new(), definition: None, expansions: 0, constants: SymbolTable::new(), conditions: Vec::new(), base: 0, include_paths: Vec::new(), including: Vec::new()}
+    }
+
+    /// Directories given with `-I`, searched in order.
+    pub fn with_include_paths(mut self, include_paths: &[PathBuf]) -> Preprocessor<'a> {
+        self.include_paths = include_paths.to_vec();
+        self
+    }
+
+    /// Constants from the command line, known to conditions from the first line on.
+    pub fn with_defines(mut self, defines: &[(String, String)]) -> Preprocessor<'a> {
+        for (name, value) in defines {
+            _ = self.constants.declare(Replacement::new(name.clone(), value.clone(), false));
+        }
+        self
+    }
+
+    /// Reads `NAME=value` as given to `-D`, a name alone is defined as 1.
+    pub fn parse_define(argument: &str) -> Result<(String, String), AssembleError> {
+        let (name, value) = argument.split_once('=').unwrap_or((argument, "1"));
+        if name == "data_offset" {
+            return Err(AssembleError::BadOption { option: "-D".to_string(), reason: "can't define data_offset, it is where the data section starts".to_string() });
+        }
+        if !Lexer::is_identifier(name) || name == "$" || ArgumentParser::looks_like_literal(name) || value.trim().is_empty() {
+            return Err(AssembleError::BadOption { option: "-D".to_string(), reason: format!("expects NAME or NAME=value but got {}", argument) });
+        }
+        Ok((name.to_string(), value.trim().to_string()))
+    }
+
+    /// Removes macro definitions and skipped branches and replaces every use of a macro with its expanded body
+    /// and every `.include` with the lines of the file. Macros and the constants used in conditions have to be
+    /// defined before they are used.
+    pub fn process(&mut self, lines: Vec<SourceLine>, sources: &mut SourceMap, diagnostics: &mut Diagnostics) -> Vec<SourceLine> {
+        let mut output = Vec::new();
+        if let Some(line) = lines.first() {
+            self.including.push(Self::identity(Path::new(sources.get_name(line.span.file))));
+        }
+        for line in lines {
+            self.process_line(line, sources, diagnostics, 0, &mut output);
+        }
+        if let Some(unfinished) = self.definition.take() {
+            diagnostics.error(AssembleError::BadDirective { directive: ".macro".to_string(), reason: format!("Macro {} is missing its .endm.", unfinished.name), span: unfinished.span });
+        }
+        self.close_conditions(0, diagnostics);
+        output
+    }
+
+    /// Adds what the line stands for to the output.
+    fn process_line(&mut self, line: SourceLine, sources: &mut SourceMap, diagnostics: &mut Diagnostics, depth: usize, output: &mut Vec<SourceLine>) {
+        let tokens = ArgumentParser::strip_comment(&line).map(|line| ArgumentParser::line_to_tokens(&line)).unwrap_or_default();
+        let directive = tokens.first().map(|token| token.text.as_str()).unwrap_or_default();
+        if let Some(current) = &mut self.definition {
+            match directive {
+                ".endm" => {
+                    let finished = self.definition.take().expect("a macro is being defined");
+                    self.macros.insert(finished.name.clone(), finished);
+                },
+                ".macro" => diagnostics.error(AssembleError::BadDirective {
+                    directive: line.text.clone(),
+                    reason: format!("Macro {} has to end with .endm before another macro is defined.", current.name),
+                    span: tokens[0].span,
+                }),
+                _ => current.body.push(line),
+            }
+            return;
+        }
+        if self.conditional(&line, &tokens, diagnostics) { return; }
+        if self.conditions.last().is_some_and(|condition| !condition.active) { return; }
+        // Written code can't use the names labels inside of expanded macros get
+        if sources.root(line.span).file == line.span.file {
+            let lexemes = ArgumentParser::strip_comment(&line).map(|line| Lexer::tokenize(&line.text, line.span)).unwrap_or_default();
+            if let Some(reserved) = lexemes.iter().find(|lexeme| lexeme.kind == LexemeKind::Identifier && Self::is_local_label(&lexeme.text)) {
+                diagnostics.error(AssembleError::BadDeclaration {
+                    declaration: line.text.clone(),
+                    reason: format!("{} ends with __ and a number, which is reserved for the labels inside of expanded macros.", reserved.text),
+                    span: reserved.span,
+                });
+                return;
+            }
+        }
+        match directive {
+            ".macro" => match self.definition(&line, &tokens) {
+                Ok(started) => self.definition = Some(started),
+                Err(error) => diagnostics.error(error),
+            },
+            ".endm" => diagnostics.error(AssembleError::BadDirective { directive: line.text.clone(), reason: ".endm has no matching .macro.".to_string(), span: tokens[0].span }),
+            ".include" => self.include(&line, &tokens, sources, diagnostics, depth, output),
+            name if self.macros.contains_key(name) => self.expand(&tokens, sources, diagnostics, depth, output),
+            _ => {
+                self.remember_constant(&tokens);
+                output.push(line);
+            },
+        }
+    }
+
+    /// Handles `.if`, `.ifdef`, `.ifndef`, `.elif`, `.else` and `.endif`, returns false for every other line.
+    fn conditional(&mut self, line: &SourceLine, tokens: &[Token], diagnostics: &mut Diagnostics) -> bool {
+        let Some(first) = tokens.first() else { return false; };
+        let bad = |reason: String| AssembleError::BadDirective { directive: line.text.clone(), reason, span: first.span };
+        let enclosing_active = |conditions: &[Condition]| conditions.iter().all(|condition| condition.active);
+        match first.text.as_str() {
+            ".if" | ".ifdef" | ".ifndef" => {
+                let active = enclosing_active(&self.conditions) && self.evaluate(&first.text, tokens, diagnostics);
+                // Inside a skipped block no branch is taken
+                let taken = active || !enclosing_active(&self.conditions);
+                self.conditions.push(Condition{active, taken, else_seen: false, span: first.span});
+            },
+            ".elif" | ".else" => {
+                if self.conditions.len() <= self.base {
+                    diagnostics.error(bad(format!("{} has no matching .if.", first.text)));
+                    return true;
+                }
+                let condition = self.conditions.pop().expect("a condition is open");
+                if condition.else_seen {
+                    diagnostics.error(bad(format!("{} can't follow the .else of the same .if.", first.text)));
+                }
+                let active = !condition.taken && (first.text == ".else" || self.evaluate(&first.text, tokens, diagnostics));
+                self.conditions.push(Condition{active, taken: condition.taken || active, else_seen: condition.else_seen || first.text == ".else", span: condition.span});
+            },
+            ".endif" => {
+                if self.conditions.len() <= self.base {
+                    diagnostics.error(bad(".endif has no matching .if.".to_string()));
+                } else {
+                    self.conditions.pop();
+                }
+            },
+            _ => return false,
+        }
+        if tokens.len() > 1 && matches!(first.text.as_str(), ".else" | ".endif") {
+            diagnostics.error(bad(format!("{} doesn't take anything after it.", first.text)));
+        }
+        true
+    }
+
+    /// Whether the branch of `.if expression`, `.elif expression`, `.ifdef name` or `.ifndef name` is taken.
+    /// Conditions that can't be evaluated are reported and count as false.
+    fn evaluate(&self, directive: &str, tokens: &[Token], diagnostics: &mut Diagnostics) -> bool {
+        let Some(last) = tokens.last().filter(|_| tokens.len() > 1) else {
+            diagnostics.error(AssembleError::BadDirective { directive: directive.to_string(), reason: format!("{} needs a condition.", directive), span: tokens[0].span });
+            return false;
+        };
+        if directive == ".ifdef" || directive == ".ifndef" {
+            if tokens.len() != 2 || !Lexer::is_identifier(&tokens[1].text) {
+                diagnostics.error(AssembleError::BadDirective { directive: directive.to_string(), reason: format!("{} takes a single name.", directive), span: tokens[1].span.to(last.span) });
+                return false;
+            }
+            let defined = self.constants.get(&tokens[1].text).is_some();
+            return defined == (directive == ".ifdef");
+        }
+        let text = tokens[1..].iter().map(|token| token.text.as_str()).collect::<Vec<&str>>().join(" ");
+        let span = tokens[1].span.to(last.span);
+        match Expression::parse(&text, span).and_then(|expression| expression.evaluate(&self.constants, &text, span)) {
+            Ok(value) => value != 0,
+            Err(error) => {
+                diagnostics.error(error);
+                false
+            },
+        }
+    }
+
+    /// Reports the conditions that were opened after `base` and never closed.
+    fn close_conditions(&mut self, base: usize, diagnostics: &mut Diagnostics) {
+        for condition in self.conditions.drain(base.min(self.conditions.len())..) {
+            diagnostics.error(AssembleError::BadDirective { directive: ".if".to_string(), reason: "This block is missing its .endif.".to_string(), span: condition.span });
+        }
+    }
+
+    /// Keeps `.name value` declarations so later conditions can use them. Problems are reported when the sections are laid out.
+    fn remember_constant(&mut self, tokens: &[Token]) {
+        let Some(name) = tokens.first().and_then(|token| token.text.strip_prefix('.')) else { return; };
+        if tokens.len() < 2 || tokens[1].text.starts_with('#') { return; }
+        let value = match tokens.len() {
+            2 => tokens[1].text.clone(),
+            _ => format!("[{}]", tokens[1..].iter().take_while(|token| !token.text.starts_with('#')).map(|token| token.text.as_str()).collect::<Vec<&str>>().join(" ")),
+        };
+        _ = self.constants.declare(Replacement::new(name.to_string(), value, false));
+    }
+
+    /// Starts a definition from `.macro name parameter, parameter`.
+    fn definition(&self, line: &SourceLine, tokens: &[Token]) -> Result<Macro, AssembleError> {
+        let bad = |reason: String, span: Span| AssembleError::BadDirective { directive: line.text.clone(), reason, span };
+        let Some(name) = tokens.get(1) else { return Err(bad("A macro needs a name.".to_string(), tokens[0].span)); };
+        if !Lexer::is_identifier(&name.text) || ArgumentParser::looks_like_literal(&name.text) {
+            return Err(bad(format!("{} isn't a valid macro name.", name.text), name.span));
+        }
+        if self.isa.find(&name.text).is_some() {
+            return Err(bad(format!("Macro {} would hide the instruction of the same name.", name.text), name.span));
+        }
+        if self.macros.contains_key(&name.text) {
+            return Err(bad(format!("Macro {} is defined more than once.", name.text), name.span));
+        }
+        let mut parameters: Vec<String> = Vec::new();
+        for parameter in Self::arguments(&tokens[2..]) {
+            if !Lexer::is_identifier(&parameter) || ArgumentParser::looks_like_literal(&parameter) || parameters.contains(&parameter) {
+                return Err(bad(format!("{} isn't a valid parameter name or is used twice.", parameter), name.span));
+            }
+            parameters.push(parameter);
+        }
+        Ok(Macro{name: name.text.clone(), parameters, body: Vec::new(), span: tokens[0].span.to(name.span)})
+    }
+
+    /// Adds the lines the macro use expands to, with nested uses and conditions inside of them handled as well.
+    fn expand(&mut self, tokens: &[Token], sources: &mut SourceMap, diagnostics: &mut Diagnostics, depth: usize, output: &mut Vec<SourceLine>) {
+        let definition = &self.macros[&tokens[0].text];
+        let call_site = tokens[0].span.to(tokens[tokens.len() - 1].span);
+        if depth >= MAX_EXPANSION_DEPTH {
+            // Reported at the outermost use, the chain of expansions would only repeat itself
+            diagnostics.error(AssembleError::MacroRecursion { name: definition.name.clone(), limit: MAX_EXPANSION_DEPTH, span: sources.root(call_site) });
+            return;
+        }
+        let arguments = Self::arguments(&tokens[1..]);
+        if arguments.len() != definition.parameters.len() {
+            diagnostics.error(AssembleError::MacroArgumentCount { name: definition.name.clone(), expected: definition.parameters.len(), found: arguments.len(), span: call_site });
+            return;
+        }
+
+        self.expansions += 1;
+        let mut replacements: HashMap<String, String> = definition.parameters.iter().cloned().zip(arguments).collect();
+        // Labels declared inside the body are local to this expansion
+        for body_line in definition.body.iter() {
+            if let Some(label) = body_line.text.trim().strip_suffix(':').filter(|label| Lexer::is_identifier(label)) {
+                replacements.insert(label.to_string(), format!("{}__{}", label, self.expansions));
+            }
+        }
+        let expanded = definition.body.iter().map(|body_line| Self::substitute(&body_line.text, &replacements).trim_start().to_string()).collect();
+        let expansion = Expansion{macro_name: definition.name.clone(), call_site, body: definition.body.iter().map(Self::content_span).collect()};
+        let file = sources.add_expansion(expansion, expanded);
+        // Blocks opened inside the macro have to be closed inside of it
+        let outer_base = std::mem::replace(&mut self.base, self.conditions.len());
+        for expanded_line in sources.get_lines(file) {
+            self.process_line(expanded_line, sources, diagnostics, depth + 1, output);
+        }
+        self.close_conditions(self.base, diagnostics);
+        self.base = outer_base;
+    }
+
+    /// Adds the lines of the file named by `.include "path"`. The path is looked up next to the including file first,
+    /// then in the include paths.
+    fn include(&mut self, line: &SourceLine, tokens: &[Token], sources: &mut SourceMap, diagnostics: &mut Diagnostics, depth: usize, output: &mut Vec<SourceLine>) {
+        let span = tokens[0].span.to(tokens[tokens.len() - 1].span);
+        let path = tokens.get(1).filter(|_| tokens.len() == 2)
+            .and_then(|token| token.text.strip_prefix('"')?.strip_suffix('"'))
+            .filter(|path| !path.is_empty());
+        let Some(path) = path else {
+            diagnostics.error(AssembleError::BadDirective { directive: line.text.clone(), reason: ".include takes a quoted path like \"lib/print.asm\".".to_string(), span });
+            return;
+        };
+        let bad = |reason: String| AssembleError::BadInclude { path: path.to_string(), reason, span };
+        // Expanded lines are looked up from the file the macro was used in
+        let including = sources.get_name(sources.root(span).file);
+        let directory = Path::new(including).parent().map(Path::to_path_buf).unwrap_or_default();
+        let Some(resolved) = std::iter::once(&directory).chain(self.include_paths.iter()).map(|directory| directory.join(path)).find(|candidate| candidate.is_file()) else {
+            let place = if directory.as_os_str().is_empty() { "the current directory".to_string() } else { directory.display().to_string() };
+            diagnostics.error(bad(format!("it is neither in {} nor in a directory given with -I", place)));
+            return;
+        };
+        let identity = Self::identity(&resolved);
+        if self.including.contains(&identity) {
+            diagnostics.error(bad("it is already being included, so it would include itself forever".to_string()));
+            return;
+        }
+        let code = match fs::read_to_string(&resolved) {
+            Ok(code) => code,
+            Err(reason) => {
+                diagnostics.error(bad(reason.to_string()));
+                return;
+            },
+        };
+
+        let file = sources.add_include(resolved.display().to_string(), code, span);
+        self.including.push(identity);
+        // Blocks and macros started in the file have to end in it
+        let outer_base = std::mem::replace(&mut self.base, self.conditions.len());
+        for included_line in sources.get_lines(file) {
+            self.process_line(included_line, sources, diagnostics, depth, output);
+        }
+        if let Some(unfinished) = self.definition.take() {
+            diagnostics.error(AssembleError::BadDirective { directive: ".macro".to_string(), reason: format!("Macro {} is missing its .endm.", unfinished.name), span: unfinished.span });
+        }
+        self.close_conditions(self.base, diagnostics);
+        self.base = outer_base;
+        self.including.pop();
+    }
+
+    /// Whether the name has the form labels inside of an expansion are renamed to, like `loop__3`.
+    fn is_local_label(name: &str) -> bool {
+        name.rsplit_once("__").is_some_and(|(label, number)| !label.is_empty() && !number.is_empty() && number.chars().all(|character| character.is_ascii_digit()))
+    }
+
+    /// The same path for every way of naming a file, as far as the file system tells.
+    pub fn identity(path: &Path) -> PathBuf {
+        fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    /// The span of the line without its indentation.
+    fn content_span(line: &SourceLine) -> Span {
+        let indentation = line.text.chars().take_while(|character| character.is_whitespace()).count() as u32;
+        let length = line.text.trim().chars().count() as u32;
+        Span{column: line.span.column + indentation, length, ..line.span}
+    }
+
+    /// The arguments of a definition or use, separated by whitespace or commas outside of brackets and quotes.
+    fn arguments(tokens: &[Token]) -> Vec<String> {
+        let mut arguments = Vec::new();
+        for token in tokens {
+            let mut current = String::new();
+            let mut depth = 0;
+            let mut quote: Option<char> = None;
+            for character in token.text.chars() {
+                match (character, quote) {
+                    (',', None) if depth == 0 => {
+                        arguments.push(std::mem::take(&mut current));
+                        continue;
+                    },
+                    ('[', None) => depth += 1,
+                    (']', None) => depth -= 1,
+                    ('\'' | '"', None) => quote = Some(character),
+                    (_, Some(open)) if character == open => quote = None,
+                    _ => {},
+                }
+                current.push(character);
+            }
+            arguments.push(current);
+        }
+        arguments.retain(|argument| !argument.is_empty());
+        arguments
+    }
+
+    /// Replaces whole names in the code, quoted text and comments stay as they are.
+    fn substitute(text: &str, replacements: &HashMap<String, String>) -> String {
+        let characters: Vec<char> = text.chars().collect();
+        let mut output = String::new();
+        let mut i = 0;
+        while i < characters.len() {
+            let character = characters[i];
+            let start = i;
+            i += 1;
+            if character == '#' {
+                output.extend(&characters[start..]);
+                break;
+            }
+            if character == '\'' || character == '"' {
+                while i < characters.len() && characters[i] != character {
+                    // An escaped quote doesn't end the text
+                    if characters[i] == '\\' { i += 1; }
+                    i += 1;
+                }
+                i = (i + 1).min(characters.len());
+            } else if character.is_ascii_alphanumeric() || character == '_' || character == '$' {
+                while i < characters.len() && (characters[i].is_ascii_alphanumeric() || characters[i] == '_') { i += 1; }
+                let word: String = characters[start..i].iter().collect();
+                // Numbers like 5a aren't names
+                if !character.is_ascii_digit() {
+                    if let Some(replacement) = replacements.get(&word) {
+                        output += replacement;
+                        continue;
+                    }
+                }
+            }
+            output.extend(&characters[start..i]);
+        }
+        output
+    }
+}
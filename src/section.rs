@@ -0,0 +1,98 @@
+use crate::argument_parser::ArgumentParser;
+use crate::diagnostics::Diagnostics;
+use crate::isa::OperandKind;
+use crate::source::{Span, Token};
+use crate::symbol_table::SymbolTable;
+
+/// The order in which the bytes of a 16 or 32 bit value are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Endianness {
+    /// Least significant byte first.
+    #[default]
+    Little,
+    Big,
+}
+
+/// How the value of a fixup is encoded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FixupKind {
+    /// An instruction operand of the given kind.
+    Operand(OperandKind),
+    /// A data value of the given amount of bytes, negative values in two's complement.
+    Data { width: usize, endianness: Endianness },
+}
+
+impl FixupKind {
+    /// Bytes taken by the value.
+    pub fn width(&self) -> usize {
+        match self {
+            FixupKind::Operand(_) => 1,
+            FixupKind::Data { width, .. } => *width,
+        }
+    }
+}
+
+/// A value that is left as a placeholder in pass 1 because it may refer to a symbol that is declared further down.
+#[derive(Debug, Clone)]
+pub struct Fixup {
+    /// Where the value goes, counted from the start of the section.
+    pub offset: usize,
+    pub token: Token,
+    pub kind: FixupKind,
+    /// The value of `$` where the value was written.
+    pub location_counter: Option<i64>,
+}
+
+/// Which bytes a source line was turned into.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEntry {
+    /// The first byte, counted from the start of the section (or the binary once assembled).
+    pub address: usize,
+    pub length: usize,
+    /// The line without its comment.
+    pub span: Span,
+}
+
+/// The bytes of the text or data section together with the places that still have to be filled in.
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    pub bytes: Vec<u8>,
+    pub fixups: Vec<Fixup>,
+    /// The lines that emitted bytes, in the order of their addresses.
+    pub lines: Vec<LineEntry>,
+}
+
+impl Section {
+    pub fn new() -> Section {
+        Section{bytes: Vec::new(), fixups: Vec::new(), lines: Vec::new()}
+    }
+
+    /// Adds placeholder bytes that are filled in by `resolve_fixups` once every symbol is known.
+    pub fn push_fixup(&mut self, token: Token, kind: FixupKind, location_counter: Option<i64>) {
+        self.fixups.push(Fixup{offset: self.bytes.len(), token, kind, location_counter});
+        self.bytes.resize(self.bytes.len() + kind.width(), 0);
+    }
+
+    /// Records that the line emitted everything added since `start`.
+    pub fn record_line(&mut self, start: usize, span: Span) {
+        if self.bytes.len() > start {
+            self.lines.push(LineEntry{address: start, length: self.bytes.len() - start, span});
+        }
+    }
+
+    /// Pass 2: every symbol has its final value now, so forward and backward references resolve alike.
+    pub fn resolve_fixups(&mut self, symbols: &mut SymbolTable, diagnostics: &mut Diagnostics) {
+        for fixup in self.fixups.iter() {
+            symbols.set_location_counter(fixup.location_counter);
+            let value = match fixup.kind {
+                FixupKind::Operand(kind) => ArgumentParser::resolve_operand(&fixup.token, kind, symbols).map(|byte| vec![byte]),
+                FixupKind::Data { width, endianness } => ArgumentParser::resolve_data(&fixup.token, width, endianness, symbols),
+            };
+            match value {
+                Ok(bytes) => self.bytes[fixup.offset..fixup.offset + bytes.len()].copy_from_slice(&bytes),
+                Err(error) => diagnostics.error(error),
+            }
+        }
+        symbols.set_location_counter(None);
+    }
+}
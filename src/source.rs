@@ -0,0 +1,144 @@
+/// Points at a piece of the original source code. Lines and columns start at 1, columns count characters.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub file: usize,
+    pub line: u32,
+    pub column: u32,
+    pub length: u32,
+}
+
+impl Span {
+    pub fn new(file: usize, line: u32, column: u32, length: u32) -> Span {
+        Span{file, line, column, length}
+    }
+
+    /// The smallest span covering both spans (which have to be on the same line).
+    pub fn to(&self, other: Span) -> Span {
+        let start = self.column.min(other.column);
+        let end = (self.column + self.length).max(other.column + other.length);
+        Span::new(self.file, self.line, start, end - start)
+    }
+}
+
+pub struct SourceFile {
+    pub name: String,
+    pub code: String,
+    /// Set for the lines a macro call was expanded to.
+    pub expansion: Option<Expansion>,
+    /// The `.include` line that brought in the file.
+    pub included_from: Option<Span>,
+}
+
+/// Where the lines of an expanded macro came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expansion {
+    pub macro_name: String,
+    /// The line that used the macro.
+    pub call_site: Span,
+    /// The line of the macro body each expanded line was made from.
+    pub body: Vec<Span>,
+}
+
+/// Owns every file taking part in an assembly so spans can be turned back into text.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap{files: Vec::new()}
+    }
+
+    /// Registers the file and returns the id used in its spans.
+    pub fn add_file(&mut self, name: String, code: String) -> usize {
+        self.files.push(SourceFile{name, code, expansion: None, included_from: None});
+        self.files.len() - 1
+    }
+
+    /// Registers a file read because of the `.include` at the span.
+    pub fn add_include(&mut self, name: String, code: String, included_from: Span) -> usize {
+        self.files.push(SourceFile{name, code, expansion: None, included_from: Some(included_from)});
+        self.files.len() - 1
+    }
+
+    /// Registers the lines of a macro expansion as a file of its own, so spans can point into them.
+    pub fn add_expansion(&mut self, expansion: Expansion, lines: Vec<String>) -> usize {
+        let name = format!("<expansion of {}>", expansion.macro_name);
+        self.files.push(SourceFile{name, code: lines.join("\n"), expansion: Some(expansion), included_from: None});
+        self.files.len() - 1
+    }
+
+    /// The place in a file that was actually written, i.e. the outermost macro call for expanded code.
+    pub fn root(&self, mut span: Span) -> Span {
+        while let Some(expansion) = self.files.get(span.file).and_then(|file| file.expansion.as_ref()) {
+            span = expansion.call_site;
+        }
+        span
+    }
+
+    /// The line of the assembled file that led to the span, i.e. the `.include` for code of included files.
+    pub fn outermost(&self, span: Span) -> Span {
+        let mut span = self.root(span);
+        while let Some(included_from) = self.files.get(span.file).and_then(|file| file.included_from) {
+            span = self.root(included_from);
+        }
+        span
+    }
+
+    /// Points from expanded code back to the macro body and every call it went through,
+    /// and from included code to every `.include` on the way.
+    pub fn origin_notes(&self, mut span: Span) -> Vec<(Span, String)> {
+        let mut notes = Vec::new();
+        while let Some(file) = self.files.get(span.file) {
+            if let Some(expansion) = &file.expansion {
+                if let Some(body_line) = expansion.body.get(span.line.saturating_sub(1) as usize) {
+                    notes.push((*body_line, format!("in this line of macro {}", expansion.macro_name)));
+                }
+                notes.push((expansion.call_site, format!("{} used here", expansion.macro_name)));
+                span = expansion.call_site;
+            } else if let Some(included_from) = file.included_from {
+                notes.push((included_from, format!("{} included here", file.name)));
+                span = included_from;
+            } else {
+                break;
+            }
+        }
+        notes
+    }
+
+    /// The ids of the files that were read as they are, i.e. every file but macro expansions.
+    pub fn get_written_files(&self) -> Vec<usize> {
+        (0..self.files.len()).filter(|&file| self.files[file].expansion.is_none()).collect()
+    }
+
+    pub fn get_name(&self, file: usize) -> &str {
+        self.files.get(file).map(|file| file.name.as_str()).unwrap_or("<unknown>")
+    }
+
+    pub fn get_line(&self, span: Span) -> Option<&str> {
+        self.files.get(span.file)?.code.lines().nth(span.line.checked_sub(1)? as usize)
+    }
+
+    /// Splits the file into lines which remember where they came from.
+    pub fn get_lines(&self, file: usize) -> Vec<SourceLine> {
+        let Some(source_file) = self.files.get(file) else { return Vec::new(); };
+        source_file.code.lines().enumerate().map(|(i, text)| {
+            SourceLine{text: text.to_string(), span: Span::new(file, i as u32 + 1, 1, text.chars().count() as u32)}
+        }).collect()
+    }
+}
+
+/// A line of code together with the span of the whole line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub text: String,
+    pub span: Span,
+}
+
+/// A whitespace separated part of a line, e.g. a mnemonic, an operand or a bracketed expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub text: String,
+    pub span: Span,
+}
@@ -0,0 +1,133 @@
+use std::path::Path;
+use crate::assembler::Assembler;
+use crate::source::Span;
+use crate::utility::expression::Expression;
+use crate::utility::json::JsonValue;
+use crate::utility::replacement::Replacement;
+
+/// What a symbol was declared as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SymbolKind {
+    /// `name:` in the text section.
+    Label,
+    /// `name type values` in the data section.
+    Data,
+    /// `.name value` in either section.
+    Constant,
+}
+
+impl SymbolKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SymbolKind::Label => "label",
+            SymbolKind::Data => "data",
+            SymbolKind::Constant => "constant",
+        }
+    }
+}
+
+/// A declared symbol with its final value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolEntry {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The address of labels and data, the evaluated value of constants. `None` if the constant can't be evaluated.
+    pub value: Option<i64>,
+    /// Bytes up to the next label or the end of the text section for labels, the bytes of the entry for data, 0 for constants.
+    pub size: usize,
+    /// Line 0 for constants given on the command line, which have no location.
+    pub span: Span,
+}
+
+/// Lists every symbol of an assembled file so tools can look up addresses by name.
+pub struct SymbolMap;
+
+impl SymbolMap {
+    /// The symbols sorted by value, then name. data_offset is left out, the program doesn't declare it.
+    pub fn collect(assembler: &Assembler) -> Vec<SymbolEntry> {
+        let text_length = assembler.get_text_length() as i64;
+        let mut entries: Vec<SymbolEntry> = assembler.symbols.iter()
+            .filter(|symbol| symbol.get_span().line > 0 || symbol.get_name() != "data_offset")
+            .map(|symbol| {
+                let kind = Self::kind(assembler, symbol);
+                let name = symbol.get_name();
+                let value = Expression::parse(&name, Span::default())
+                    .and_then(|expression| expression.evaluate(&assembler.symbols, &name, Span::default()))
+                    .ok();
+                let size = match kind {
+                    SymbolKind::Data => assembler.line_table.iter()
+                        .find(|entry| entry.span.file == symbol.get_span().file && entry.span.line == symbol.get_span().line)
+                        .map(|entry| entry.length).unwrap_or_default(),
+                    _ => 0,
+                };
+                // Symbols declared by a macro are located at its use
+                SymbolEntry{name, kind, value, size, span: assembler.sources.root(symbol.get_span())}
+            })
+            .collect();
+
+        let label_addresses: Vec<i64> = entries.iter().filter(|entry| entry.kind == SymbolKind::Label).filter_map(|entry| entry.value).collect();
+        for entry in entries.iter_mut().filter(|entry| entry.kind == SymbolKind::Label) {
+            let address = entry.value.unwrap_or_default();
+            let end = label_addresses.iter().copied().filter(|other| *other > address).min().unwrap_or(text_length).max(address);
+            entry.size = (end - address) as usize;
+        }
+        entries.sort_by(|a, b| (a.value, &a.name).cmp(&(b.value, &b.name)));
+        entries
+    }
+
+    /// Tells labels, data names and constants apart by the line they were declared on, symbols without one are constants from the command line.
+    pub fn kind(assembler: &Assembler, symbol: &Replacement) -> SymbolKind {
+        let declaration = assembler.sources.get_line(symbol.get_span()).map(str::trim_start).unwrap_or_default();
+        if symbol.get_is_function() {
+            SymbolKind::Label
+        } else if symbol.get_span().line == 0 || declaration.starts_with('.') {
+            SymbolKind::Constant
+        } else {
+            SymbolKind::Data
+        }
+    }
+
+    /// One symbol per line: name, kind, value, size and where it was declared, which may be an included file.
+    pub fn render_text(assembler: &Assembler) -> String {
+        let mut output = format!("{:<24}{:<10}{:<10}{:<6}{}\n", "NAME", "KIND", "VALUE", "SIZE", "LOCATION");
+        for entry in Self::collect(assembler) {
+            let value = match entry.value {
+                Some(value) if value >= 0 => format!("{:#06X}", value),
+                Some(value) => value.to_string(),
+                None => "?".to_string(),
+            };
+            let location = match entry.span.line {
+                0 => String::new(),
+                line => format!("{}:{}:{}", assembler.sources.get_name(entry.span.file), line, entry.span.column),
+            };
+            output += format!("{:<24}{:<10}{:<10}{:<6}{}", entry.name, entry.kind.name(), value, entry.size, location).trim_end();
+            output += "\n";
+        }
+        output
+    }
+
+    pub fn render_json(assembler: &Assembler) -> String {
+        let symbols: Vec<JsonValue> = Self::collect(assembler).into_iter().map(|entry| {
+            let declared = entry.span.line > 0;
+            let location = |value: JsonValue| if declared { value } else { JsonValue::Null };
+            JsonValue::object(vec![
+                ("name", entry.name.into()),
+                ("kind", entry.kind.name().into()),
+                ("value", entry.value.map(JsonValue::from).unwrap_or(JsonValue::Null)),
+                ("size", (entry.size as i64).into()),
+                ("file", location(assembler.sources.get_name(entry.span.file).into())),
+                ("line", location((entry.span.line as i64).into())),
+                ("column", location((entry.span.column as i64).into())),
+            ])
+        }).collect();
+        JsonValue::object(vec![("symbols", symbols.into())]).to_string() + "\n"
+    }
+
+    /// A .json path gets the JSON variant, everything else the text one.
+    pub fn render(assembler: &Assembler, path: &Path) -> String {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Self::render_json(assembler),
+            _ => Self::render_text(assembler),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::cell::Cell;
+use crate::argument_parser::ArgumentParser;
+use crate::error::AssembleError;
+use crate::lexer::Lexer;
+use crate::source::Span;
+use crate::utility::replacement::Replacement;
+
+/// How often a symbol may refer to another symbol before it is considered to refer to itself.
+const MAX_RESOLVING_DEPTH: u32 = 64;
+
+/// All labels, constants and data names of a program. Names are only ever matched as whole identifiers.
+#[derive(Default, Clone)]
+pub struct SymbolTable {
+    symbols: Vec<Replacement>,
+    /// The value of `$` while compiling the data section.
+    location_counter: Option<i64>,
+    resolving_depth: Cell<u32>,
+}
+
+impl SymbolTable {
+    pub fn new() -> SymbolTable {
+        SymbolTable{symbols: Vec::new(), location_counter: None, resolving_depth: Cell::new(0)}
+    }
+
+    /// Adds the symbol unless its name is already taken or would hide a register or number.
+    pub fn declare(&mut self, symbol: Replacement) -> Result<(), AssembleError> {
+        let name = symbol.get_name();
+        if !Lexer::is_identifier(&name) || name == "$" {
+            return Err(AssembleError::BadDeclaration { declaration: name.clone(), reason: format!("{} isn't a valid name.", name), span: symbol.get_span() });
+        }
+        if ArgumentParser::looks_like_literal(&name) {
+            return Err(AssembleError::SymbolShadowsLiteral { name, span: symbol.get_span() });
+        }
+        // The assembler declares data_offset itself, without a line
+        if name == "data_offset" && symbol.get_span().line > 0 {
+            return Err(AssembleError::BadDeclaration { declaration: name, reason: "data_offset is reserved for the address where the data section starts.".to_string(), span: symbol.get_span() });
+        }
+        if let Some(previous) = self.get(&name) {
+            return Err(AssembleError::SymbolRedeclared { name, span: symbol.get_span(), previous: previous.get_span() });
+        }
+        self.symbols.push(symbol);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Replacement> {
+        self.symbols.iter().find(|symbol| symbol.get_name() == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Replacement> {
+        self.symbols.iter()
+    }
+
+    /// The labels of the text section with their addresses.
+    pub fn labels(&self) -> Vec<(String, usize)> {
+        self.symbols.iter()
+            .filter(|symbol| symbol.get_is_function())
+            .filter_map(|symbol| Some((symbol.get_name(), symbol.get_value().parse().ok()?)))
+            .collect()
+    }
+
+    pub fn set_location_counter(&mut self, location_counter: Option<i64>) {
+        self.location_counter = location_counter;
+    }
+
+    /// Resolves the name to the text of its value, with all symbols and expressions inside of it resolved as well.
+    /// Returns None if there is no such symbol.
+    pub fn resolve(&self, name: &str, span: Span) -> Result<Option<String>, AssembleError> {
+        if name == "$" { return Ok(self.location_counter.map(|location| location.to_string())); }
+        let Some(symbol) = self.get(name) else { return Ok(None); };
+
+        let depth = self.resolving_depth.get();
+        if depth >= MAX_RESOLVING_DEPTH {
+            return Err(AssembleError::BadExpression { expression: name.to_string(), reason: "the symbol refers to itself".to_string(), span });
+        }
+        self.resolving_depth.set(depth + 1);
+        // The value has no place in the line it is used in, so errors inside of it are reported where it is used
+        let resolved = ArgumentParser::resolve_all_math_ops_in_line(symbol.get_value(), self, span).map_err(|error| error.at(span));
+        self.resolving_depth.set(depth);
+        resolved.map(Some)
+    }
+}
@@ -0,0 +1,50 @@
+use crate::assembler::Assembler;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct AssemblerTest;
+
+impl Test for AssemblerTest {
+    fn execute() -> bool{
+        let start_message = "Starting assembler tests.".green();
+        println!("{}\n", start_message);
+        // Symbols have to resolve the same way no matter if they are declared before or after their use
+        let assembler_tests = [
+            "<text>\njmp end\nadd r1 1\nend:\nhalt",
+            "<text>\ninc r1\ndec r2\nhalt",
+            "<text>\n.len [end - start]\nstart:\nsow len\nend:\nhalt",
+            "<text>\nldb r1 value\nhalt\n<data>\nvalue 8b 9",
+            "<data>\nptr 8b later\nlater 8b 7\n<text>\nhalt",
+            "<data>\n.size [end - start]\nstart 8b 1 2 3\nend 8b size\n<text>\nhalt",
+            "<data>\nv 8b 1 2\n.len [$ - v]\nx 8b len\n<text>\nhalt",
+            "<data>\na 16b x1234 -1\nb 32b 1\nc 8b b -128 'a'\n<text>\nhalt",
+            "<data>\n.endian big\na 16b x1234\nb 32le x12345678\nc 32b -2\n<text>\nhalt",
+            "<data>\na 16be 1\n.endian big\n.endian little\nb 16b 1\n<text>\nhalt",
+        ];
+        let assembler_results: [&[u8]; 10] = [
+            &[0x62, 5, 0x40, 0x81, 1, 0x60],
+            &[0x40, 0x81, 1, 0x41, 0x82, 1, 0x60],
+            &[0x01, 2, 0x60],
+            &[0x64, 0x81, 4, 0x60, 9],
+            &[0x60, 2, 7],
+            &[0x60, 1, 2, 3, 3],
+            &[0x60, 1, 2, 1],
+            &[0x60, 0x34, 0x12, 0xFF, 0xFF, 1, 0, 0, 0, 5, 0x80, 0x61],
+            &[0x60, 0x12, 0x34, 0x78, 0x56, 0x34, 0x12, 0xFF, 0xFF, 0xFF, 0xFE],
+            &[0x60, 0, 1, 1, 0],
+        ];
+
+        for test in assembler_tests.iter().enumerate(){
+            let result = Assembler::new("test.asm".to_string(), test.1.to_string()).assemble();
+            if result.as_deref() == Ok(assembler_results[test.0]) {
+                let message = format!("{:?} assembles to {:02X?}", test.1, assembler_results[test.0]).green();
+                println!("{}", message);
+            }else{
+                let message = format!("{:?} should assemble to {:02X?} but resulted in {:02X?}", test.1, assembler_results[test.0], result).red();
+                println!("{}", message);
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use crate::dap::DapServer;
+use crate::isa::Isa;
+use crate::tests::test::Test;
+use crate::utility::json::JsonValue;
+use colored::*;
+
+pub struct DapTest;
+
+impl Test for DapTest {
+    fn execute() -> bool{
+        let start_message = "Starting DAP tests.".green();
+        println!("{}\n", start_message);
+        let path = std::env::temp_dir().join("sriscasm_dap_test.asm");
+        let program = "<data>\nvalue 8b 7\n<text>\nmov r1 2\n# a comment\nloop:\ndec r1\njmpz r1 end\njmp loop\nend:\nsow 'k'\nhalt";
+        if let Err(reason) = fs::write(&path, program) {
+            println!("{}", format!("Couldn't write {}: {}", path.display(), reason).red());
+            return false;
+        }
+        let path = path.display().to_string().replace('\\', "\\\\");
+
+        // Requests are sent one after the other, the replies (response and events) have to contain every text
+        let dap_tests: [(String, Vec<&str>); 10] = [
+            (r#"{"seq":1,"type":"request","command":"initialize","arguments":{}}"#.to_string(), vec![r#""command":"initialize","success":true"#, r#""event":"initialized""#]),
+            (format!(r#"{{"seq":2,"type":"request","command":"launch","arguments":{{"program":"{}","stopOnEntry":true}}}}"#, path), vec![r#""success":true"#]),
+            (format!(r#"{{"seq":3,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":5}},{{"line":20}}]}}}}"#, path), vec![r#"{"verified":true,"line":7}"#, r#"{"verified":false,"line":20"#]),
+            (r#"{"seq":4,"type":"request","command":"configurationDone"}"#.to_string(), vec![r#""reason":"entry""#]),
+            (r#"{"seq":5,"type":"request","command":"continue","arguments":{"threadId":1}}"#.to_string(), vec![r#""reason":"breakpoint""#]),
+            (r#"{"seq":6,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#.to_string(), vec![r#""name":"loop (0x03)""#, r#""line":7"#]),
+            (r#"{"seq":7,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#.to_string(), vec![r#"{"name":"r1","value":"0x02 (2)","variablesReference":0}"#]),
+            (r#"{"seq":8,"type":"request","command":"next","arguments":{"threadId":1}}"#.to_string(), vec![r#""reason":"step""#]),
+            (r#"{"seq":9,"type":"request","command":"variables","arguments":{"variablesReference":2}}"#.to_string(), vec![r#"{"name":"zero","value":"false""#]),
+            (r#"{"seq":10,"type":"request","command":"continue","arguments":{"threadId":1}}"#.to_string(), vec![r#""reason":"breakpoint""#]),
+        ];
+        let mut server = DapServer::new(Isa::builtin(), 1000);
+        for (request, expected) in dap_tests.iter() {
+            let replies = server.handle(&JsonValue::parse(request).unwrap()).iter().map(ToString::to_string).collect::<Vec<String>>().join("\n");
+            if expected.iter().all(|text| replies.contains(text)) {
+                println!("{}", format!("{} is answered with {:?}", request, expected).green());
+            }else{
+                println!("{}", format!("{} should be answered with {:?} but got {}", request, expected, replies).red());
+                return false;
+            }
+        }
+
+        // Once the breakpoint is gone the program runs until it halts
+        let request = format!(r#"{{"seq":11,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[]}}}}"#, path);
+        _ = server.handle(&JsonValue::parse(&request).unwrap());
+        let request = r#"{"seq":12,"type":"request","command":"continue"}"#;
+        let replies = server.handle(&JsonValue::parse(request).unwrap()).iter().map(ToString::to_string).collect::<Vec<String>>().join("\n");
+        let expected = [r#""output":"k""#, r#""event":"exited""#, r#""event":"terminated""#];
+        if expected.iter().all(|text| replies.contains(text)) {
+            println!("{}", format!("{} is answered with {:?}", request, expected).green());
+        }else{
+            println!("{}", format!("{} should be answered with {:?} but got {}", request, expected, replies).red());
+            return false;
+        }
+
+        // Breakpoints only go on lines of their own source, included files have lines of their own
+        let main = std::env::temp_dir().join("sriscasm_dap_main.asm");
+        let part = std::env::temp_dir().join("sriscasm_dap_part.asm");
+        let written = fs::write(&main, "<text>\nmov r1 3\n.include \"sriscasm_dap_part.asm\"").and_then(|_| fs::write(&part, "twice:\nadd r1 r1\nadd r1 r1\nhalt"));
+        if let Err(reason) = written {
+            println!("{}", format!("Couldn't write {}: {}", main.display(), reason).red());
+            return false;
+        }
+        let (main, part) = (main.display().to_string().replace('\\', "\\\\"), part.display().to_string().replace('\\', "\\\\"));
+        let source_tests = [
+            (format!(r#"{{"seq":1,"type":"request","command":"launch","arguments":{{"program":"{}"}}}}"#, main), vec![r#""success":true"#.to_string()]),
+            (format!(r#"{{"seq":2,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":3}}]}}}}"#, main), vec![r#"{"verified":false,"line":3,"message":"There is no instruction on or after this line."}"#.to_string()]),
+            (format!(r#"{{"seq":3,"type":"request","command":"setBreakpoints","arguments":{{"source":{{"path":"{}"}},"breakpoints":[{{"line":3}}]}}}}"#, part), vec![r#"{"verified":true,"line":3}"#.to_string()]),
+            (r#"{"seq":4,"type":"request","command":"configurationDone"}"#.to_string(), vec![r#""reason":"breakpoint""#.to_string()]),
+            (r#"{"seq":5,"type":"request","command":"stackTrace","arguments":{"threadId":1}}"#.to_string(), vec![format!(r#""path":"{}"}},"line":3"#, part)]),
+            (r#"{"seq":6,"type":"request","command":"variables","arguments":{"variablesReference":1}}"#.to_string(), vec![r#"{"name":"r1","value":"0x06 (6)","variablesReference":0}"#.to_string()]),
+        ];
+        let mut server = DapServer::new(Isa::builtin(), 1000);
+        for (request, expected) in source_tests.iter() {
+            let replies = server.handle(&JsonValue::parse(request).unwrap()).iter().map(ToString::to_string).collect::<Vec<String>>().join("\n");
+            if expected.iter().all(|text| replies.contains(text)) {
+                println!("{}", format!("{} is answered with {:?}", request, expected).green());
+            }else{
+                println!("{}", format!("{} should be answered with {:?} but got {}", request, expected, replies).red());
+                return false;
+            }
+        }
+
+        // Defines and include paths of the launch configuration work like -D and -I
+        let board = std::env::temp_dir().join("sriscasm_dap_board.asm");
+        let library = std::env::temp_dir().join("sriscasm_dap_library");
+        let written = fs::write(&board, "<text>\n.ifndef BOARD\nmissing_board\n.endif\n.include \"step.asm\"\nadd r1 STEP\nhalt")
+            .and_then(|_| fs::create_dir_all(&library)).and_then(|_| fs::write(library.join("step.asm"), ".STEP 2"));
+        if let Err(reason) = written {
+            println!("{}", format!("Couldn't write {}: {}", board.display(), reason).red());
+            return false;
+        }
+        let escape = |path: &std::path::Path| path.display().to_string().replace('\\', "\\\\");
+        let launch = |extra: &str| format!(r#"{{"seq":1,"type":"request","command":"launch","arguments":{{"program":"{}"{}}}}}"#, escape(&board), extra);
+        let launch_tests = [
+            (launch(&format!(r#","defines":["BOARD","SPEED=3"],"includePaths":["{}"]"#, escape(&library))), r#""success":true"#),
+            (launch(&format!(r#","includePaths":["{}"]"#, escape(&library))), "Unknown instruction missing_board."),
+            (launch(r#","defines":["BOARD"]"#), "Couldn't include step.asm"),
+            (launch(r#","defines":["r1=2"]"#), "expects NAME or NAME=value but got r1=2"),
+        ];
+        for (request, expected) in launch_tests {
+            let replies = DapServer::new(Isa::builtin(), 1000).handle(&JsonValue::parse(&request).unwrap()).iter().map(ToString::to_string).collect::<Vec<String>>().join("\n");
+            if replies.contains(expected) {
+                println!("{}", format!("{} is answered with {:?}", request, expected).green());
+            }else{
+                println!("{}", format!("{} should be answered with {:?} but got {}", request, expected, replies).red());
+                return false;
+            }
+        }
+        true
+    }
+}
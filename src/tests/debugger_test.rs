@@ -0,0 +1,54 @@
+use crate::assembler::Assembler;
+use crate::debugger::Debugger;
+use crate::emulator::Emulator;
+use crate::isa::Isa;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct DebuggerTest;
+
+impl Test for DebuggerTest {
+    fn execute() -> bool{
+        let start_message = "Starting debugger tests.".green();
+        println!("{}\n", start_message);
+        let program = "<data>\ncounter 8b 0\n<text>\nmov r2 2\nloop:\ninc r1\nstb r1 counter\ndec r2\njmpz r2 end\njmp loop\nend:\npushb back\njmp function\nback:\nhalt\nfunction:\npopb r3\njmp r3";
+        // Commands are run one after the other on the same program, each output has to contain the text
+        let debugger_tests = [
+            ("break loop", "Breakpoint at 0x03 <loop>."),
+            ("continue", "0x03 <loop>: inc r1"),
+            ("watch counter", "Watching 0x1A, currently 0."),
+            ("c", "0x1A changed from 0 to 1."),
+            ("c", "0x03 <loop>: inc r1"),
+            ("registers", "r2              0x01 1"),
+            ("delete loop", "Deleted the breakpoint at 0x03 <loop>."),
+            ("unwatch counter", "No longer watching 0x1A."),
+            ("step 4", "0x11 <end>: pushb 21"),
+            ("next", "0x15 <back>: halt"),
+            ("memory counter 1", "0x1A: 02"),
+            ("stack", "The stack is empty."),
+            ("disassemble", "function:\n    popb r3"),
+            ("break nowhere", "Symbol nowhere was never declared."),
+            ("break 0x0A", "Breakpoint at 0x0A"),
+            ("delete x0A", "Deleted the breakpoint at 0x0A"),
+            ("s", "Halted at 0x16 <function> after 15 instructions."),
+        ];
+
+        let mut assembler = Assembler::new("test.asm".to_string(), program.to_string());
+        let binary = assembler.assemble().unwrap_or_default();
+        let mut debugger = Debugger::new(Emulator::new(&binary, Isa::builtin()).unwrap(), assembler.symbols, binary.len(), 1000);
+        for (command, expected) in debugger_tests {
+            let output = debugger.execute(command).unwrap_or_default();
+            if output.contains(expected) {
+                println!("{}", format!("{:?} prints {:?}", command, expected).green());
+            }else{
+                println!("{}", format!("{:?} should print {:?} but printed {:?}", command, expected, output).red());
+                return false;
+            }
+        }
+        if debugger.execute("quit").is_some() {
+            println!("{}", "quit should end the debugger".red());
+            return false;
+        }
+        true
+    }
+}
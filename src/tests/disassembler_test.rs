@@ -0,0 +1,63 @@
+use crate::assembler::Assembler;
+use crate::disassembler::Disassembler;
+use crate::isa::Isa;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct DisassemblerTest;
+
+impl Test for DisassemblerTest {
+    fn execute() -> bool{
+        let start_message = "Starting disassembler tests.".green();
+        println!("{}\n", start_message);
+        let isa = Isa::builtin();
+
+        // Code has to read like the source again, with labels for jump targets and named registers
+        let source = "<data>\nv 8b 1 2\n<text>\nstart:\nadd r1 b11\njmpz r1 done\ninc r13\njmp start\ndone:\nsow r15\nhalt";
+        let binary = Assembler::new("test.asm".to_string(), source.to_string()).assemble().unwrap_or_default();
+        let expected = "<text>\n\
+            label_00:\n\
+            \x20   add r1 3                # 00: 40 81 03\n\
+            \x20   jmpz r1 label_0B        # 03: 63 81 0B\n\
+            \x20   inc frame_ptr           # 06: 40 8D 01\n\
+            \x20   jmp label_00            # 09: 62 00\n\
+            label_0B:\n\
+            \x20   sow exec_ptr            # 0B: 01 8F\n\
+            \x20   halt                    # 0D: 60\n\
+            \x20   .8b x01 x02             # 0E: 01 02\n";
+        if !Self::check("Assembled program", &Disassembler::disassemble(&binary, &isa), expected) { return false; }
+
+        // Unknown opcodes, cut off instructions and unreachable bytes are data
+        let expected = "<text>\n\
+            \x20   .8b x20 x62             # 00: 20 62\n";
+        if !Self::check("Unknown opcode", &Disassembler::disassemble(&[0x20, 0x62], &isa), expected) { return false; }
+        let expected = "<text>\n\
+            \x20   jmp 4                   # 00: 62 04\n\
+            \x20   .8b x60 x60 x40 x81     # 02: 60 60 40 81\n";
+        if !Self::check("Jump to a cut off instruction", &Disassembler::disassemble(&[0x62, 4, 0x60, 0x60, 0x40, 0x81], &isa), expected) { return false; }
+
+        // Jumps are found by their opcode, whatever an ISA file calls them
+        let isa = Isa::from_json(r#"{"instructions": [
+            {"mnemonic": "goto", "opcode": 98, "operands": ["value"]},
+            {"mnemonic": "jmp", "opcode": 1, "operands": ["value"]},
+            {"mnemonic": "stop", "opcode": 96, "operands": []}
+        ]}"#).unwrap_or_default();
+        let expected = "<text>\n\
+            \x20   goto label_04           # 00: 62 04\n\
+            \x20   .8b x01 x05             # 02: 01 05\n\
+            label_04:\n\
+            \x20   stop                    # 04: 60\n";
+        Self::check("Renamed jump", &Disassembler::disassemble(&[0x62, 4, 0x01, 0x05, 0x60], &isa), expected)
+    }
+}
+
+impl DisassemblerTest {
+    fn check(name: &str, disassembly: &str, expected: &str) -> bool {
+        if disassembly == expected {
+            println!("{}", format!("{} disassembles to\n{}", name, disassembly).green());
+            return true;
+        }
+        println!("{}", format!("{} should disassemble to\n{}but resulted in\n{}", name, expected, disassembly).red());
+        false
+    }
+}
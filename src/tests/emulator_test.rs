@@ -0,0 +1,97 @@
+use crate::assembler::Assembler;
+use crate::emulator::{Emulator, Fault, CARRY_FLAG, ZERO_FLAG};
+use crate::isa::Isa;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct EmulatorTest;
+
+impl Test for EmulatorTest {
+    fn execute() -> bool{
+        let start_message = "Starting emulator tests.".green();
+        println!("{}\n", start_message);
+        // Programs have to leave the registers, memory and output as the hardware would
+        let emulator_tests = [
+            "<text>\nmov r1 5\nadd r1 3\nmul r1 r1\nsub r1 4\ndiv r1 6\nmod r1 4\nhalt",
+            "<text>\nmov r1 100\nadd r1 r1\nadd r1 100\nhalt",
+            "<text>\nmov r1 1\ndec r1\nhalt",
+            "<text>\npushb 7\npushb 9\npopb r1\npopb r2\nhalt",
+            "<text>\nstb 42 100\nldb r1 100\nhalt",
+            "<text>\nmov r2 3\nloop:\ninc r1\ndec r2\njmpz r2 end\njmp loop\nend:\nhalt",
+            "<data>\nmsg str \"Hi\"\n<text>\nldb r1 msg\nsow r1\nsow 'i'\nsoc\nsow 'o'\nhalt",
+        ];
+        // r1, r2, flags, output
+        let emulator_results: [(u8, u8, u8, &str); 7] = [
+            (2, 0, 0, ""),
+            (44, 0, CARRY_FLAG, ""),
+            (0, 0, ZERO_FLAG, ""),
+            (9, 7, 0, ""),
+            (42, 0, 0, ""),
+            (3, 0, ZERO_FLAG, ""),
+            (b'H', 0, 0, "o"),
+        ];
+
+        for test in emulator_tests.iter().enumerate(){
+            let binary = Assembler::new("test.asm".to_string(), test.1.to_string()).assemble().unwrap_or_default();
+            let mut emulator = Emulator::new(&binary, Isa::builtin()).unwrap();
+            let result = emulator.run(1000);
+            let found = (emulator.registers[1], emulator.registers[2], emulator.registers[12], String::from_utf8_lossy(&emulator.output).to_string());
+            let (r1, r2, flags, output) = emulator_results[test.0];
+            if result.is_ok() && found == (r1, r2, flags, output.to_string()) {
+                println!("{}", format!("{:?} ends with r1={} r2={} flags={} output={:?}", test.1, r1, r2, flags, output).green());
+            }else{
+                println!("{}", format!("{:?} should end with r1={} r2={} flags={} output={:?} but resulted in {:?} with {:?}", test.1, r1, r2, flags, output, result, found).red());
+                return false;
+            }
+        }
+
+        // Programs that can't continue have to stop with a fault instead of a panic
+        let fault_tests: [(&[u8], Fault); 5] = [
+            (&[0x43, 0x81, 0x00], Fault::DivisionByZero { address: 0 }),
+            (&[0x62, 0x00], Fault::StepLimitReached { steps: 1000 }),
+            (&[0x20], Fault::UnknownInstruction { address: 0, opcode: 0x20 }),
+            (&[0x61, 0x05, 0x01], Fault::ExpectedRegister { address: 0, operand: 5 }),
+            (&[0x61, 0x81, 0xA0], Fault::NoSuchRegister { address: 0, register: 0xA0 }),
+        ];
+        for (binary, fault) in fault_tests {
+            let result = Emulator::new(binary, Isa::builtin()).and_then(|mut emulator| emulator.run(1000));
+            if result == Err(fault.clone()) {
+                println!("{}", format!("{:02X?} stops with {}", binary, fault).green());
+            }else{
+                println!("{}", format!("{:02X?} should stop with {} but resulted in {:?}", binary, fault, result).red());
+                return false;
+            }
+        }
+        // Instructions of an ISA file do what their opcode does, whatever they are called
+        let isa = Isa::from_json(r#"{"instructions": [
+            {"mnemonic": "set", "opcode": 97, "operands": ["register", "value"]},
+            {"mnemonic": "plus", "opcode": 64, "operands": ["register", "value"]},
+            {"mnemonic": "sub", "opcode": 66, "operands": ["register", "value"]},
+            {"mnemonic": "mul", "opcode": 65, "operands": ["register", "value"]},
+            {"mnemonic": "stop", "opcode": 96, "operands": []},
+            {"mnemonic": "swap", "opcode": 128, "operands": ["register", "register"]}
+        ]}"#).unwrap_or_default();
+        let code = "<text>\nset r1 5\nplus r1 2\nsub r1 3\nmul r1 1\nstop";
+        let binary = Assembler::new("test.asm".to_string(), code.to_string()).with_isa(isa.clone()).assemble().unwrap_or_default();
+        let result = Emulator::new(&binary, isa.clone()).and_then(|mut emulator| emulator.run(1000).map(|_| emulator.registers[1]));
+        if result == Ok(20) {
+            println!("{}", format!("{:?} ends with r1=20 with renamed and swapped mnemonics", code).green());
+        }else{
+            println!("{}", format!("{:?} should end with r1=20 with renamed and swapped mnemonics but resulted in {:?}", code, result).red());
+            return false;
+        }
+        let result = Emulator::new(&[0x80, 0x81, 0x82], isa).and_then(|mut emulator| emulator.run(1000));
+        let fault = Fault::UnsupportedInstruction { address: 0, mnemonic: "swap".to_string() };
+        if result == Err(fault.clone()) {
+            println!("{}", format!("An instruction without a built-in opcode stops with {}", fault).green());
+        }else{
+            println!("{}", format!("An instruction without a built-in opcode should stop with {} but resulted in {:?}", fault, result).red());
+            return false;
+        }
+        if Emulator::new(&[0; 257], Isa::builtin()).is_ok() {
+            println!("{}", "Binaries bigger than the memory should be rejected".red());
+            return false;
+        }
+        true
+    }
+}
@@ -0,0 +1,105 @@
+use crate::assembler::Assembler;
+use crate::diagnostics::Diagnostics;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct ErrorTest;
+
+impl Test for ErrorTest {
+    fn execute() -> bool{
+        let start_message = "Starting error tests.".green();
+        println!("{}\n", start_message);
+        // Every broken source has to be reported as an error at the right place instead of a panic
+        let error_tests = [
+            "<text>\nfoo r1 r2",
+            "<text>\nadd r1",
+            "<text>\nadd r1 200",
+            "<text>\nadd r1 x-1",
+            "<text>\n\n# jump\njmp nowhere",
+            "<text>\n.x 1 2",
+            "<data>\nvalue 64b 1\n<text>\nhalt",
+            "<data>\nvalue 8b 1 300\n<text>\nhalt",
+            "<text>\n  add r1 [5 / 0]",
+            "<text>\nloop:\nadd r1 1\nloop:\njmp loop",
+            "<data>\nloop 8b 1\n<text>\nloop:\njmp loop",
+            "<text>\n.r1 5\nhalt",
+            "<text>\n.a b\n.b a\njmp a",
+            "<text>\n.big x7FFFFFFFFFFFFFFF\nadd r1 [big + 1]",
+            "<text>\n.size 4 * (2 + missing)\nadd r1 size",
+            "<text>\nadd 5 r1",
+            "<text>\ninc r1 1",
+            "<data>\nvalue 16b 65536\n<text>\nhalt",
+            "<data>\nvalue 32b -2147483649\n<text>\nhalt",
+            "<data>\nvalue 16b \"hi\"\n<text>\nhalt",
+            "<data>\n.endian middle\n<text>\nhalt",
+            "<text>\ndata_offset:\njmp data_offset",
+        ];
+        // Message, line and column of the first error
+        let error_results = [
+            ("Unknown instruction foo.", 2, 1),
+            ("Instruction add doesn't take 1 operand(s).", 2, 1),
+            ("Argument 200 should be within range 0...127. Please define it in the data section instead.", 2, 8),
+            ("Argument x-1 isn't a valid base 16 number.", 2, 8),
+            ("Symbol nowhere was never declared.", 4, 5),
+            ("Couldn't resolve [1 2]: unexpected 2 after the expression.", 2, 6),
+            ("Unknown data type 64b.", 2, 7),
+            ("Argument 300 should be within range -128...255.", 2, 12),
+            ("Couldn't resolve [5 / 0]: division by zero.", 2, 11),
+            ("Symbol loop is declared more than once.", 4, 1),
+            ("Symbol loop is declared more than once.", 2, 1),
+            ("Symbol r1 can't be declared because it would hide a register or number.", 2, 2),
+            ("Couldn't resolve [a]: the symbol refers to itself.", 4, 5),
+            ("Couldn't resolve [big + 1]: the result doesn't fit into 64 bits.", 3, 9),
+            ("Symbol missing was never declared.", 3, 8),
+            ("Argument 5 has to be a register.", 2, 5),
+            ("Instruction inc doesn't take 2 operand(s).", 2, 1),
+            ("Argument 65536 should be within range -32768...65535.", 2, 11),
+            ("Argument -2147483649 should be within range -2147483648...4294967295.", 2, 11),
+            ("Argument \"hi\" is text, which can only be stored as 8b, str or char.", 2, 11),
+            ("Endianness has to be little or big.", 2, 1),
+            ("data_offset is reserved for the address where the data section starts.", 2, 1),
+        ];
+
+        for test in error_tests.iter().enumerate(){
+            let result = Assembler::new("test.asm".to_string(), test.1.to_string()).assemble();
+            let first_error = result.as_ref().err().and_then(|errors| errors.first().cloned());
+            let found = first_error.as_ref().map(|error| (error.to_string(), error.span().map(|span| (span.line, span.column))));
+            let (message, line, column) = error_results[test.0];
+            if found == Some((message.to_string(), Some((line, column)))) {
+                let message = format!("{:?} fails with {} at {}:{}", test.1, message, line, column).green();
+                println!("{}", message);
+            }else{
+                let message = format!("{:?} should fail with {} at {}:{} but resulted in {:?}", test.1, message, line, column, result).red();
+                println!("{}", message);
+                return false;
+            }
+        }
+
+        // All problems of a run have to be collected instead of stopping at the first one
+        let mut assembler = Assembler::new("test.asm".to_string(), "stray line\n<data>\nvalue 64b 1\n<text>\nfoo\nadd r1 200\nadd r1 1".to_string());
+        let errors = assembler.assemble().err().unwrap_or_default();
+        let warnings = assembler.diagnostics.get_warnings();
+        if errors.len() == 3 && warnings.len() == 2 {
+            let message = format!("Collected {} errors and {} warnings in one run", errors.len(), warnings.len()).green();
+            println!("{}", message);
+        }else{
+            let message = format!("Expected 3 errors and 2 warnings but got {:?} and {:?}", errors, warnings).red();
+            println!("{}", message);
+            return false;
+        }
+
+        // The offending part of the line has to be underlined
+        colored::control::set_override(false);
+        let rendered = Diagnostics::render("error", Color::Red, &errors[2].to_string(), errors[2].span(), &errors[2].label(), &assembler.sources);
+        colored::control::unset_override();
+        let expected = "error: Argument 200 should be within range 0...127. Please define it in the data section instead.\n --> test.asm:6:8\n  |\n6 | add r1 200\n  |        ^^^ not within 0...127\n";
+        if rendered == expected {
+            println!("{}", "Rendered the error with the offending source underlined".green());
+        }else{
+            let message = format!("Expected the rendered error\n{}but got\n{}", expected, rendered).red();
+            println!("{}", message);
+            return false;
+        }
+        true
+    }
+}
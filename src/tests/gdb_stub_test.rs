@@ -0,0 +1,83 @@
+use std::io::Cursor;
+use crate::assembler::Assembler;
+use crate::emulator::Emulator;
+use crate::gdb_stub::GdbStub;
+use crate::isa::Isa;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct GdbStubTest;
+
+impl Test for GdbStubTest {
+    fn execute() -> bool{
+        let start_message = "Starting gdb stub tests.".green();
+        println!("{}\n", start_message);
+        let program = "<text>\nmov r1 5\nloop:\ndec r1\njmpz r1 end\njmp loop\nend:\nhalt";
+        let binary = Assembler::new("test.asm".to_string(), program.to_string()).assemble().unwrap_or_default();
+
+        // Packets are sent one after the other to the same program, None means there is no reply
+        let packet_tests: [(&str, Option<&str>); 21] = [
+            ("?", Some("S05")),
+            ("g", Some("00000000000000000000000000000000")),
+            ("s", Some("S05")),
+            ("p1", Some("05")),
+            ("pf", Some("03")),
+            ("Z0,6,1", Some("OK")),
+            ("c", Some("S05")),
+            ("pf", Some("06")),
+            ("p1", Some("04")),
+            ("z0,6,1", Some("OK")),
+            ("M20,2:abcd", Some("OK")),
+            ("m20,2", Some("abcd")),
+            ("mff,2", Some("E01")),
+            ("P1=01", Some("OK")),
+            ("c", Some("W00")),
+            ("qSupported:multiprocess+", Some("PacketSize=1000;qXfer:features:read+")),
+            ("qXfer:features:read:target.xml:46,28", Some("m<architecture>stupid-risc</architecture>")),
+            ("qXfer:features:read:target.xml:ffffffffffffffff,10", Some("l")),
+            ("vMustReplyEmpty", Some("")),
+            ("Z2,6,1", Some("")),
+            ("k", None),
+        ];
+        let mut stub = GdbStub::new(Emulator::new(&binary, Isa::builtin()).unwrap(), 1000);
+        for (packet, expected) in packet_tests {
+            let reply = stub.handle_packet(packet);
+            if reply.as_deref() == expected {
+                println!("{}", format!("{:?} is answered with {:?}", packet, expected).green());
+            }else{
+                println!("{}", format!("{:?} should be answered with {:?} but got {:?}", packet, expected, reply).red());
+                return false;
+            }
+        }
+
+        // An interrupt stops a program that loops forever
+        let endless = Assembler::new("test.asm".to_string(), "<text>\nloop:\njmp loop".to_string()).assemble().unwrap_or_default();
+        let mut checks = 0;
+        let mut stub = GdbStub::new(Emulator::new(&endless, Isa::builtin()).unwrap(), u64::MAX).with_interrupt(move || {
+            checks += 1;
+            checks == 3
+        });
+        let reply = stub.handle_packet("c");
+        if reply.as_deref() == Some("S02") && stub.emulator.steps == 3000 {
+            println!("{}", "\"c\" is interrupted with \"S02\"".green());
+        }else{
+            println!("{}", format!("\"c\" should be interrupted with \"S02\" after 3000 steps but got {:?} after {}", reply, stub.emulator.steps).red());
+            return false;
+        }
+
+        // Packets with a wrong checksum have to be rejected, everything else acknowledged and answered
+        let input = format!("+{}$g#00{}", GdbStub::frame("?"), GdbStub::frame("D"));
+        let expected = format!("+{}-+{}", GdbStub::frame("S05"), GdbStub::frame("OK"));
+        let mut stub = GdbStub::new(Emulator::new(&binary, Isa::builtin()).unwrap(), 1000);
+        let mut output = Vec::new();
+        let result = stub.serve(&mut Cursor::new(input.as_bytes()), &mut output);
+        let output = String::from_utf8_lossy(&output);
+        if result.is_ok() && output == expected && stub.finished {
+            println!("{}", format!("{:?} is answered with {:?}", input, expected).green());
+        }else{
+            println!("{}", format!("{:?} should be answered with {:?} but got {:?}", input, expected, output).red());
+            return false;
+        }
+        true
+    }
+}
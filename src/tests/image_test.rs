@@ -0,0 +1,166 @@
+use crate::image::{ImageFormat, ImageOptions};
+use crate::tests::test::Test;
+use std::path::Path;
+use colored::*;
+
+pub struct ImageTest;
+
+impl Test for ImageTest {
+    fn execute() -> bool{
+        let start_message = "Starting image tests.".green();
+        println!("{}\n", start_message);
+        let program = [0x41, 0x81, 0x01, 0xFF];
+        // Records crossing a 64 KiB boundary are split and the upper address half announced first
+        let encode_tests = [
+            (ImageFormat::IntelHex, 0, ":0400000041810 1FF3A\n:00000001FF\n"),
+            (ImageFormat::IntelHex, 0x1FFFE, ":020000040001F9\n:02FFFE0041813F\n:020000040002F8\n:0200000001FFFE\n:00000001FF\n"),
+            (ImageFormat::SRecord, 0x100, "S00B0000737269736361736D8F\nS10701004181 01FF35\nS5030001FB\nS9030100FB\n"),
+            (ImageFormat::SRecord, 0x12345, "S00B0000737269736361736D8F\nS2080123454181 01FFCC\nS5030001FB\nS80401234592\n"),
+        ];
+        for (format, load_address, expected) in encode_tests {
+            let expected = expected.replace(' ', "");
+            let encoded = String::from_utf8(format.encode(&program, load_address, 8)).unwrap_or_default();
+            if encoded == expected {
+                println!("{}", format!("{:?} at {:#X} is written as\n{}", format, load_address, expected).green());
+            }else{
+                println!("{}", format!("{:?} at {:#X} should be written as\n{}but resulted in\n{}", format, load_address, expected, encoded).red());
+                return false;
+            }
+            match format.decode(expected.as_bytes()) {
+                Ok(decoded) if decoded == program => println!("{}", format!("{:?} at {:#X} is read back", format, load_address).green()),
+                result => {
+                    println!("{}", format!("{:?} at {:#X} should be read back as {:?} but resulted in {:?}", format, load_address, program, result).red());
+                    return false;
+                },
+            }
+        }
+
+        let decode_tests = [
+            (ImageFormat::IntelHex, ":0200000041813C\n:0100040001FA\n:00000001FF\n", Ok(vec![0x41, 0x81, 0x00, 0x00, 0x01])),
+            (ImageFormat::IntelHex, ":0200000041813D\n", Err("the record on line 1 has a wrong checksum".to_string())),
+            (ImageFormat::IntelHex, ":03000000418106\n", Err("the record on line 1 has the wrong length".to_string())),
+            (ImageFormat::SRecord, "S1050000418138\nS9030000FC\n", Ok(vec![0x41, 0x81])),
+            (ImageFormat::SRecord, "S1050000418139\n", Err("the record on line 1 has a wrong checksum".to_string())),
+            (ImageFormat::SRecord, "41810\n", Err("line 1 doesn't start with 'S'".to_string())),
+            (ImageFormat::Logisim { addressed: false }, "v3.0 hex words plain\n41 81 2*0 # comment\n1\n", Ok(vec![0x41, 0x81, 0x00, 0x00, 0x01])),
+            (ImageFormat::Logisim { addressed: true }, "v3.0 hex words addressed\n0: 41 81\n4: 01\n", Ok(vec![0x41, 0x81, 0x00, 0x00, 0x01])),
+            (ImageFormat::Logisim { addressed: true }, "v3.0 hex words addressed\n0: 8141\n2: 0060\n", Ok(vec![0x41, 0x81, 0x00, 0x00, 0x60, 0x00])),
+            (ImageFormat::Logisim { addressed: true }, "v2.0 raw\n41\n", Err("it has to start with 'v3.0 hex words plain' or 'v3.0 hex words addressed'".to_string())),
+        ];
+        for (format, content, expected) in decode_tests {
+            let decoded = format.decode(content.as_bytes());
+            if decoded == expected {
+                println!("{}", format!("{:?} is read as {:?}", content, expected).green());
+            }else{
+                println!("{}", format!("{:?} should be read as {:?} but resulted in {:?}", content, expected, decoded).red());
+                return false;
+            }
+        }
+
+        let option_tests = [
+            (Some("ihex"), Some("x100"), None, Some(ImageOptions{format: ImageFormat::IntelHex, load_address: 0x100, ..Default::default()})),
+            (Some("SREC"), Some("4096"), None, Some(ImageOptions{format: ImageFormat::SRecord, load_address: 4096, ..Default::default()})),
+            (Some("logisim"), None, Some("x100"), Some(ImageOptions{format: ImageFormat::Logisim { addressed: true }, depth: Some(256), ..Default::default()})),
+            (None, None, None, Some(ImageOptions::default())),
+            (Some("elf"), None, None, None),
+            (Some("bin"), Some("-1"), None, None),
+            (None, None, Some("0"), None),
+        ];
+        for (format, load_address, depth, expected) in option_tests {
+            let options = ImageOptions::default().with_format(format)
+                .and_then(|options| options.with_load_address(load_address))
+                .and_then(|options| options.with_depth(depth)).ok();
+            if options == expected {
+                println!("{}", format!("--format {:?} --load-address {:?} --depth {:?} gives {:?}", format, load_address, depth, expected).green());
+            }else{
+                println!("{}", format!("--format {:?} --load-address {:?} --depth {:?} should give {:?} but resulted in {:?}", format, load_address, depth, expected, options).red());
+                return false;
+            }
+        }
+
+        // Code goes into the ROM and data into the RAM, both padded to the depth
+        let options = ImageOptions{format: ImageFormat::Logisim { addressed: false }, depth: Some(20), split: true, ..Default::default()};
+        let binary: Vec<u8> = (1..=20).collect();
+        let expected = vec![
+            ("file.rom.txt".to_string(), "v3.0 hex words plain\n01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10\n11 12 00 00\n".to_string()),
+            ("file.ram.txt".to_string(), "v3.0 hex words plain\n13 14 00 00 00 00 00 00 00 00 00 00 00 00 00 00\n00 00 00 00\n".to_string()),
+        ];
+        let outputs = options.outputs(Path::new("file.asm"), &binary, 18).map(|outputs| outputs.into_iter()
+            .map(|(path, content)| (path.display().to_string(), String::from_utf8_lossy(&content).to_string())).collect::<Vec<_>>());
+        if outputs.as_ref() == Ok(&expected) {
+            println!("{}", format!("Splitting is written as {:?}", expected).green());
+        }else{
+            println!("{}", format!("Splitting should be written as {:?} but resulted in {:?}", expected, outputs).red());
+            return false;
+        }
+        let addressed = ImageFormat::Logisim { addressed: true }.encode(&binary, 0, 8);
+        let expected = "v3.0 hex words addressed\n00: 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f 10\n10: 11 12 13 14\n";
+        if addressed == expected.as_bytes() {
+            println!("{}", format!("Addressed Logisim images are written as {:?}", expected).green());
+        }else{
+            println!("{}", format!("Addressed Logisim images should be written as {:?} but resulted in {:?}", expected, String::from_utf8_lossy(&addressed)).red());
+            return false;
+        }
+        // Wide words are read back as the bytes they were made of
+        let program = [0x41, 0x81, 0x01, 0xFF, 0x60, 0x00];
+        for addressed in [false, true] {
+            let format = ImageFormat::Logisim { addressed };
+            let decoded = format.decode(&format.encode(&program, 0, 16));
+            if decoded.as_deref() == Ok(&program[..]) {
+                println!("{}", format!("{:?} with 16 bit words is read back", format).green());
+            }else{
+                println!("{}", format!("{:?} with 16 bit words should be read back as {:02X?} but resulted in {:02X?}", format, program, decoded).red());
+                return false;
+            }
+        }
+        // Bytes are grouped into little endian words and padded to the depth
+        let program = [0x41, 0x81, 0x01, 0xFF, 0x60];
+        let fpga_tests = [
+            (ImageFormat::ReadMemH, 16, Some(4), "// 4 words of 16 bits\n8141\nff01\n0060\n0000\n"),
+            (ImageFormat::ReadMemB, 8, None, "// 5 words of 8 bits\n01000001\n10000001\n00000001\n11111111\n01100000\n"),
+            (ImageFormat::Mif, 16, Some(6), "WIDTH=16;\nDEPTH=6;\n\nADDRESS_RADIX=HEX;\nDATA_RADIX=HEX;\n\nCONTENT BEGIN\n\t0 : 8141;\n\t1 : FF01;\n\t2 : 0060;\n\t[3..5] : 0000;\nEND;\n"),
+            (ImageFormat::Coe, 8, None, "; 5 words of 8 bits\nmemory_initialization_radix=16;\nmemory_initialization_vector=\n41,\n81,\n01,\nFF,\n60;\n"),
+        ];
+        for (format, width, depth, expected) in fpga_tests {
+            let options = ImageOptions{format, width, depth, ..Default::default()};
+            let outputs = options.outputs(Path::new("file.asm"), &program, program.len())
+                .map(|outputs| outputs.into_iter().map(|(_, content)| String::from_utf8_lossy(&content).to_string()).collect::<Vec<_>>());
+            if outputs == Ok(vec![expected.to_string()]) {
+                println!("{}", format!("{:?} with {} bit words is written as\n{}", format, width, expected).green());
+            }else{
+                println!("{}", format!("{:?} with {} bit words should be written as\n{}but resulted in {:?}", format, width, expected, outputs).red());
+                return false;
+            }
+        }
+        let width_tests = [(Some("32"), Some(32)), (None, Some(8)), (Some("12"), None), (Some("128"), None)];
+        for (width, expected) in width_tests {
+            let options = ImageOptions::default().with_width(width).ok().map(|options| options.width);
+            if options == expected {
+                println!("{}", format!("--width {:?} gives {:?}", width, expected).green());
+            }else{
+                println!("{}", format!("--width {:?} should give {:?} but resulted in {:?}", width, expected, options).red());
+                return false;
+            }
+        }
+        let too_wide = ImageOptions{width: 16, depth: Some(2), ..Default::default()}.outputs(Path::new("file.asm"), &program, 5).map_err(|error| error.to_string());
+        let expected = Err("The program needs 3 words but the memory only holds 2.".to_string());
+        if too_wide == expected {
+            println!("{}", format!("Images with more words than the depth fail with {:?}", expected).green());
+        }else{
+            println!("{}", format!("Images with more words than the depth should fail with {:?} but resulted in {:?}", expected, too_wide).red());
+            return false;
+        }
+
+        let too_large = ImageOptions{depth: Some(19), ..Default::default()}.outputs(Path::new("file.asm"), &binary, 20).map_err(|error| error.to_string());
+        let expected = Err("The program needs 20 words but the memory only holds 19.".to_string());
+        if too_large == expected {
+            println!("{}", format!("Images larger than the depth fail with {:?}", expected).green());
+        }else{
+            println!("{}", format!("Images larger than the depth should fail with {:?} but resulted in {:?}", expected, too_large).red());
+            return false;
+        }
+
+        println!();
+        true
+    }
+}
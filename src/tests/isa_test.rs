@@ -0,0 +1,83 @@
+use crate::assembler::Assembler;
+use crate::isa::Isa;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct IsaTest;
+
+const TOML_ISA: &str = r#"
+# The ALU moved to 0b0101_XXXX
+[[instructions]]
+mnemonic = "add"
+opcode = 0b0101_0000
+operands = ["register", "value"]
+class = "alu"
+size = 3
+
+[[instructions]]
+mnemonic = "inc"
+opcode = 0x50
+operands = ["register", 1]
+
+[[instructions]]
+mnemonic = "halt"
+opcode = 0b0110_0000
+"#;
+
+const JSON_ISA: &str = r#"{"instructions": [
+    {"mnemonic": "add", "opcode": 80, "operands": ["register", "value"], "class": "alu"},
+    {"mnemonic": "inc", "opcode": 80, "operands": ["register", 1]},
+    {"mnemonic": "halt", "opcode": 96, "operands": []}
+]}"#;
+
+impl Test for IsaTest {
+    fn execute() -> bool{
+        let start_message = "Starting ISA tests.".green();
+        println!("{}\n", start_message);
+        if Isa::new(Isa::builtin().get_instructions().clone()).is_err() {
+            println!("{}", "The built-in ISA should be valid".red());
+            return false;
+        }
+
+        // Loaded instruction sets have to be used for encoding
+        let code = "<text>\nadd r1 2\ninc r2\nhalt";
+        let expected: &[u8] = &[0x50, 0x81, 2, 0x50, 0x82, 1, 0x60];
+        for (format, isa) in [("TOML", Isa::from_toml(TOML_ISA)), ("JSON", Isa::from_json(JSON_ISA))] {
+            let result = isa.map(|isa| Assembler::new("test.asm".to_string(), code.to_string()).with_isa(isa).assemble());
+            if matches!(&result, Ok(Ok(binary)) if binary == expected) {
+                println!("{}", format!("{} ISA assembles to {:02X?}", format, expected).green());
+            }else{
+                println!("{}", format!("{} ISA should assemble to {:02X?} but resulted in {:02X?}", format, expected, result).red());
+                return false;
+            }
+        }
+        let unknown = Isa::from_toml(TOML_ISA).map(|isa| Assembler::new("test.asm".to_string(), "<text>\nsub r1 1".to_string()).with_isa(isa).assemble());
+        if !matches!(unknown, Ok(Err(_))) {
+            println!("{}", "Mnemonics missing from the loaded ISA should be unknown".red());
+            return false;
+        }
+
+        // Inconsistent descriptions have to be rejected
+        let invalid_isas = [
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 64\n[[instructions]]\nmnemonic = \"b\"\nopcode = 0x40", "a and b both use opcode 0b01000000"),
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 0b0010_0001", "a uses opcode 0b00100001, but class 001 is reserved"),
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 0b0110_0000\nclass = \"alu\"", "a is declared as \"alu\" but opcode 0b01100000 has the class bits of Internal"),
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 1\noperands = [\"value\"]\nsize = 1", "a has size 1 but its opcode and operands take 2 bytes"),
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 1\n[[instructions]]\nmnemonic = \"A\"\nopcode = 2", "A is defined more than once"),
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 256", "a needs an opcode within 0...255"),
+            ("[[instructions]]\nmnemonic = \"a\"\nopcode = 1\noperands = [\"memory\"]", "a has operand \"memory\", expected \"register\", \"value\" or a byte"),
+            ("instructions = 5", "the list of instructions is missing"),
+            ("[[instructions]\nmnemonic = \"a\"", "line 1: expected ] after the table name"),
+        ];
+        for (description, reason) in invalid_isas {
+            match Isa::from_toml(description) {
+                Err(error) if error == reason => println!("{}", format!("{:?} is rejected: {}", description, error).green()),
+                result => {
+                    println!("{}", format!("{:?} should be rejected with {} but resulted in {:?}", description, reason, result).red());
+                    return false;
+                },
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,114 @@
+use crate::archive::Archive;
+use crate::assembler::Assembler;
+use crate::linker::Linker;
+use crate::object::ObjectFile;
+use crate::tests::test::Test;
+use crate::utility::json::JsonValue;
+use colored::*;
+
+pub struct LinkerTest;
+
+impl LinkerTest {
+    fn object(name: &str, code: &str) -> Result<ObjectFile, String> {
+        Assembler::new(name.to_string(), code.to_string()).assemble_object()
+            .map_err(|errors| errors.iter().map(ToString::to_string).collect::<Vec<String>>().join(" "))
+    }
+}
+
+impl Test for LinkerTest {
+    fn execute() -> bool{
+        let start_message = "Starting linker tests.".green();
+        println!("{}\n", start_message);
+        let main = ".extern double, value\n.global back\n<text>\nmov r1 value\njmp double\nback:\nhalt\n<data>\nptr 8b value";
+        let library = ".global double, value\n.extern back\n<text>\ndouble:\nadd r1 r1\njmp back\n<data>\npad 8b 0\nvalue 8b 21\nmsg str \"hi\"\n.len [$ - msg + 1]\nsize 8b len data_offset";
+
+        // Text sections first, then the data sections, each module's data_offset is where its own data starts
+        let modules = Self::object("main.asm", main).and_then(|main| Ok(vec![main, Self::object("library.asm", library)?]));
+        let linked = modules.as_ref().map_err(Clone::clone).and_then(|modules| Linker::link(modules).map_err(|errors| format!("{:?}", errors)));
+        let expected: &[u8] = &[0x61, 0x81, 0x0D, 0x62, 0x06, 0x60, 0x40, 0x81, 0x81, 0x62, 0x05, 0x0D, 0x00, 0x15, 0x68, 0x69, 0x02, 0x0C];
+        if linked.as_ref().is_ok_and(|program| program.binary == expected && program.text_length == 11) {
+            println!("{}", format!("{:?} and {:?} link to {:02X?}", main, library, expected).green());
+        }else{
+            println!("{}", format!("{:?} and {:?} should link to {:02X?} but resulted in {:02X?}", main, library, expected, linked).red());
+            return false;
+        }
+
+        // Object files are written as JSON and read back unchanged
+        let Ok(modules) = modules else { return false; };
+        for module in modules.iter() {
+            let read = ObjectFile::from_json(&JsonValue::parse(&module.to_json().to_string()).unwrap_or(JsonValue::Null));
+            if read.as_ref() == Ok(module) {
+                println!("{}", format!("The object file of {} is read back unchanged", module.source).green());
+            }else{
+                println!("{}", format!("The object file of {} should be read back unchanged but resulted in {:?}", module.source, read).red());
+                return false;
+            }
+        }
+
+        // Offsets that don't fit into the section are rejected instead of panicking while linking
+        let json = modules[0].to_json().to_string();
+        for (offset, expected) in [("-1", "a relocation has a negative offset"), ("9223372036854775807", "the relocation at line 4 lies outside of the text section")] {
+            let Some((before, after)) = json.split_once("\"offset\":") else { return false; };
+            let broken = format!("{}\"offset\":{}{}", before, offset, after.trim_start_matches(|c: char| c.is_ascii_digit()));
+            let read = ObjectFile::from_json(&JsonValue::parse(&broken).unwrap_or(JsonValue::Null));
+            if read.as_ref().err().map(String::as_str) == Some(expected) {
+                println!("{}", format!("A relocation at offset {} is rejected with {:?}", offset, expected).green());
+            }else{
+                println!("{}", format!("A relocation at offset {} should be rejected with {:?} but resulted in {:?}", offset, expected, read).red());
+                return false;
+            }
+        }
+
+        // Only the members declaring a missing symbol are taken from the archive, together with the members they need in turn
+        let program = ".extern double\n.global back\n<text>\njmp double\nback:\nhalt";
+        let members = [
+            ("unused.asm", ".global unused\n<text>\nunused:\nhalt"),
+            ("double.asm", ".global double\n.extern step, back\n<text>\ndouble:\nadd r1 step\njmp back"),
+            ("step.asm", ".global step\n<data>\nstep 8b 2"),
+        ];
+        let mut archive = Archive::default();
+        for (name, code) in members {
+            match Self::object(name, code) {
+                Ok(object) => archive.add(name.replace(".asm", ".o"), object),
+                Err(error) => {
+                    println!("{}", format!("{:?} should assemble but resulted in {}", code, error).red());
+                    return false;
+                },
+            }
+        }
+        let archive = Archive::from_json(&JsonValue::parse(&archive.to_json().to_string()).unwrap_or(JsonValue::Null)).unwrap_or_default();
+        let mut modules = Self::object("main.asm", program).into_iter().collect::<Vec<ObjectFile>>();
+        modules.extend(Archive::select(&modules, &[archive]));
+        let linked = Linker::link(&modules).map(|program| program.binary);
+        let sources: Vec<&str> = modules.iter().map(|module| module.source.as_str()).collect();
+        let expected: &[u8] = &[0x62, 0x03, 0x60, 0x40, 0x81, 0x08, 0x62, 0x02, 0x02];
+        if sources == ["main.asm", "double.asm", "step.asm"] && linked.as_deref() == Ok(expected) {
+            println!("{}", format!("{:?} takes {:?} from the archive and links to {:02X?}", program, &sources[1..], expected).green());
+        }else{
+            println!("{}", format!("{:?} should take double.asm and step.asm from the archive and link to {:02X?} but took {:?} and resulted in {:02X?}", program, expected, sources, linked).red());
+            return false;
+        }
+
+        // The first problem of assembling the modules or of linking them
+        let error_tests: [(&[(&str, &str)], &str); 5] = [
+            (&[("a.asm", ".extern f\n<text>\njmp f"), ("b.asm", ".global g\n<text>\ng:\nhalt")], "Symbol f is used by a.asm but no module declares it .global."),
+            (&[("a.asm", ".global f\n<text>\nf:\nhalt"), ("b.asm", ".global f\n<text>\nf:\nhalt")], "Symbol f is declared .global by both a.asm and b.asm."),
+            (&[("a.asm", ".extern far\n<text>\nmov r1 [far + 125]\nhalt"), ("b.asm", ".global far\n<text>\nhalt\nfar:\nhalt")], "Couldn't link line 3 of a.asm: Argument 130 should be within range 0...127. Please define it in the data section instead."),
+            (&[("a.asm", ".global size\n<text>\n.size 4\nhalt")], "Symbol size is a constant, only labels and data names can be .global."),
+            (&[("a.asm", ".extern start\n<text>\nstart:\nhalt")], "Symbol start is declared more than once."),
+        ];
+        for (files, message) in error_tests {
+            let result = files.iter().map(|(name, code)| Self::object(name, code)).collect::<Result<Vec<ObjectFile>, String>>()
+                .and_then(|modules| Linker::link(&modules).map_err(|errors| errors.first().map(ToString::to_string).unwrap_or_default()));
+            if result.as_ref().is_err_and(|error| error.starts_with(message)) {
+                println!("{}", format!("{:?} fails with {}", files, message).green());
+            }else{
+                println!("{}", format!("{:?} should fail with {} but resulted in {:?}", files, message, result).red());
+                return false;
+            }
+        }
+
+        println!();
+        true
+    }
+}
@@ -0,0 +1,57 @@
+use crate::assembler::Assembler;
+use crate::listing::Listing;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct ListingTest;
+
+impl Test for ListingTest {
+    fn execute() -> bool{
+        let start_message = "Starting listing tests.".green();
+        println!("{}\n", start_message);
+        // Every source line, the bytes it became and the resolved operands, followed by the symbols
+        let listing_tests = [
+            ("<data>\nmsg str \"Hello\"\n<text>\n.count 2\nmov r1 count\nloop:\njmp loop # back\nldb r2 msg",
+"ADDR  BYTES         SOURCE                                  RESOLVED
+                    <data>
+08    48 65 6C 6C   msg str \"Hello\"
+0C    6F
+                    <text>
+                    .count 2
+00    61 81 02      mov r1 count                            mov r1 2
+                    loop:
+03    62 03         jmp loop # back                         jmp 3
+05    64 82 08      ldb r2 msg                              ldb r2 8
+
+SYMBOLS
+NAME                    KIND      VALUE
+count                   constant  2 (0x02)
+loop                    label     3 (0x03)
+msg                     data      8 (0x08)
+"),
+            // The mnemonic as written, even if an alias encodes the same
+            ("<text>\nadd r1 1\ninc r1\nhalt",
+"ADDR  BYTES         SOURCE                                  RESOLVED
+                    <text>
+00    40 81 01      add r1 1                                add r1 1
+03    40 81 01      inc r1                                  inc r1
+06    60            halt                                    halt
+
+SYMBOLS
+NAME                    KIND      VALUE
+"),
+        ];
+
+        for (code, expected) in listing_tests {
+            let mut assembler = Assembler::new("test.asm".to_string(), code.to_string());
+            let listing = assembler.assemble().map(|binary| Listing::render(&assembler, &binary));
+            if listing.as_deref() == Ok(expected) {
+                println!("{}", format!("{:?} is listed as expected", code).green());
+            }else{
+                println!("{}", format!("{:?} should be listed as\n{}\nbut resulted in\n{}", code, expected, listing.unwrap_or_default()).red());
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use crate::isa::Isa;
+use crate::lsp::LanguageServer;
+use crate::tests::test::Test;
+use crate::utility::json::JsonValue;
+use colored::*;
+
+pub struct LspTest;
+
+impl Test for LspTest {
+    fn execute() -> bool{
+        let start_message = "Starting LSP tests.".green();
+        println!("{}\n", start_message);
+        let document = r#"<data>\nmsg str \"Hi\"\n<text>\n.count 2\nmov r1 count\nloop:\ndec r1\njmpz r1 end\njmp loop # back\nend:\nldb r2 msg\nhalt"#;
+        let position = |method: &str, line: i64, character: i64, extra: &str| format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"textDocument/{}","params":{{"textDocument":{{"uri":"file:///test.asm"}},"position":{{"line":{},"character":{}}}{}}}}}"#,
+            method, line, character, extra);
+
+        // Messages are sent one after the other, the replies have to contain every text
+        let lsp_tests: [(String, Vec<&str>); 16] = [
+            (format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"file:///test.asm","languageId":"sriscasm","version":1,"text":"{}"}}}}}}"#, document),
+                vec![r#""uri":"file:///test.asm","diagnostics":[]"#]),
+            (position("definition", 8, 5, ""), vec![r#""result":{"uri":"file:///test.asm","range":{"start":{"line":5,"character":0},"end":{"line":5,"character":4}}}"#]),
+            (position("references", 4, 8, r#","context":{"includeDeclaration":true}"#),
+                vec![r#"{"start":{"line":3,"character":1},"end":{"line":3,"character":6}}"#, r#"{"start":{"line":4,"character":7},"end":{"line":4,"character":12}}"#]),
+            (position("hover", 6, 1, ""), vec!["**dec** register", "opcode 0x41", "encoded at 0x03 as `41 81 01`"]),
+            (position("hover", 10, 8, ""), vec!["**msg** = 15"]),
+            (r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/completion","params":{"textDocument":{"uri":"file:///test.asm"},"position":{"line":11,"character":0}}}"#.to_string(),
+                vec![r#"{"label":"pushb","kind":14"#, r#"{"label":"loop","kind":21,"detail":"3"}"#]),
+            (position("rename", 8, 4, r#","newName":"again""#),
+                vec![r#"{"range":{"start":{"line":5,"character":0},"end":{"line":5,"character":4}},"newText":"again"}"#, r#"{"range":{"start":{"line":8,"character":4},"end":{"line":8,"character":8}},"newText":"again"}"#]),
+            (position("rename", 8, 4, r#","newName":"1x""#), vec![r#""error":{"code":-32602"#]),
+            (position("rename", 8, 4, r#","newName":"r1""#), vec![r#""error":{"code":-32602,"message":"r1 isn't a valid name."#]),
+            (position("rename", 8, 4, r#","newName":"x1F""#), vec![r#""error":{"code":-32602,"message":"x1F isn't a valid name."#]),
+            (position("rename", 8, 4, r#","newName":"end""#), vec![r#""error":{"code":-32602,"message":"Symbol end is already declared."#]),
+            (position("rename", 8, 4, r#","newName":"halt""#), vec![r#""error":{"code":-32602,"message":"halt is an instruction."#]),
+            (r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///test.asm","version":2},"contentChanges":[{"text":"<text>\nfoo r1\nhalt"}]}}"#.to_string(),
+                vec![r#"{"range":{"start":{"line":1,"character":0},"end":{"line":1,"character":3}},"severity":1,"source":"sriscasm","message":"Unknown instruction foo."}"#]),
+            (r#"{"jsonrpc":"2.0","id":3,"method":"workspace/symbol","params":{}}"#.to_string(), vec![r#""id":3,"error":{"code":-32601"#]),
+            // Columns of the protocol count UTF-16 code units, so the emoji (sent as a surrogate pair) takes two of them
+            (r#"{"jsonrpc":"2.0","method":"textDocument/didChange","params":{"textDocument":{"uri":"file:///test.asm","version":3},"contentChanges":[{"text":"<data>\nsmile 8b 1\nwide 8b \"\ud83d\ude00\" smile\n<text>\nhalt"}]}}"#.to_string(),
+                vec![r#"{"range":{"start":{"line":2,"character":8},"end":{"line":2,"character":12}},"severity":1"#]),
+            (position("references", 2, 14, ""), vec![r#"{"start":{"line":2,"character":13},"end":{"line":2,"character":18}}"#]),
+        ];
+        let mut server = LanguageServer::new(Isa::builtin());
+        for (message, expected) in lsp_tests.iter() {
+            let replies = server.handle(&JsonValue::parse(message).unwrap()).iter().map(ToString::to_string).collect::<Vec<String>>().join("\n");
+            if expected.iter().all(|text| replies.contains(text)) {
+                println!("{}", format!("{} is answered with {:?}", message, expected).green());
+            }else{
+                println!("{}", format!("{} should be answered with {:?} but got {}", message, expected, replies).red());
+                return false;
+            }
+        }
+
+        // Included files are looked up next to the document, whose URI is turned into a path
+        let directory = std::env::temp_dir().join("sriscasm lsp_test");
+        let files = [("lib.asm", ".STEP 2"), ("self.asm", "<text>\n.include \"self.asm\"\nhalt"), ("library/speed.asm", ".SPEED 3")];
+        for (name, code) in files {
+            if let Err(reason) = fs::create_dir_all(directory.join("library")).and_then(|_| fs::write(directory.join(name), code)) {
+                println!("{}", format!("Couldn't write {}: {}", directory.join(name).display(), reason).red());
+                return false;
+            }
+        }
+        let uri = |name: &str| format!("file://{}", directory.join(name).display()).replace(' ', "%20");
+        let open = |name: &str, text: &str| format!(r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{}","languageId":"sriscasm","version":1,"text":"{}"}}}}}}"#, uri(name), text);
+        let include_tests = [
+            (open("main.asm", r#"<text>\n.include \"lib.asm\"\nadd r1 STEP\nhalt"#), r#""diagnostics":[]"#.to_string()),
+            // Renaming changes the included file as well
+            (format!(r#"{{"jsonrpc":"2.0","id":6,"method":"textDocument/rename","params":{{"textDocument":{{"uri":"{}"}},"position":{{"line":2,"character":8}},"newName":"STRIDE"}}}}"#, uri("main.asm")),
+                format!(r#""changes":{{"{}":[{{"range":{{"start":{{"line":2,"character":7}},"end":{{"line":2,"character":11}}}},"newText":"STRIDE"}}],"{}":[{{"range":{{"start":{{"line":0,"character":1}},"end":{{"line":0,"character":5}}}},"newText":"STRIDE"}}]}}"#, uri("main.asm"), uri("lib.asm"))),
+            (open("self.asm", r#"<text>\n.include \"self.asm\"\nhalt"#), "it is already being included, so it would include itself forever.".to_string()),
+            (open("board.asm", r#"<text>\n.ifndef BOARD\nmissing_board\n.endif\n.include \"speed.asm\"\nadd r1 SPEED\nhalt"#), "Unknown instruction missing_board.".to_string()),
+            // Defines and include paths of the initialization options work like -D and -I
+            (format!(r#"{{"jsonrpc":"2.0","id":4,"method":"initialize","params":{{"initializationOptions":{{"defines":["BOARD"],"includePaths":["{}"]}}}}}}"#, directory.join("library").display().to_string().replace('\\', "\\\\")), r#""id":4,"result":{"capabilities""#.to_string()),
+            (open("board.asm", r#"<text>\n.ifndef BOARD\nmissing_board\n.endif\n.include \"speed.asm\"\nadd r1 SPEED\nhalt"#), r#""diagnostics":[]"#.to_string()),
+            (r#"{"jsonrpc":"2.0","id":5,"method":"initialize","params":{"initializationOptions":{"defines":["r1=2"]}}}"#.to_string(), r#""id":5,"error":{"code":-32602,"message":"Option -D expects NAME or NAME=value but got r1=2"#.to_string()),
+        ];
+        for (message, expected) in include_tests {
+            let replies = server.handle(&JsonValue::parse(&message).unwrap()).iter().map(ToString::to_string).collect::<Vec<String>>().join("\n");
+            if replies.contains(&expected) {
+                println!("{}", format!("{} is answered with {:?}", message, expected).green());
+            }else{
+                println!("{}", format!("{} should be answered with {:?} but got {}", message, expected, replies).red());
+                return false;
+            }
+        }
+        true
+    }
+}
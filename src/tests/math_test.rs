@@ -2,6 +2,8 @@ use crate::utility::math;
 use crate::tests::test::Test;
 use crate::utility::replacement::Replacement;
 use crate::argument_parser::ArgumentParser;
+use crate::source::Span;
+use crate::symbol_table::SymbolTable;
 use colored::*;
 
 pub struct MathTest;
@@ -11,12 +13,18 @@ impl Test for MathTest {
         let start_message = "Starting math tests.".green();
         println!("{}\n", start_message);
         // Test resolving
-        let resolving_tests = ["1 + 5", "3 - 2", "8 / 4", "8 * 3", "x % 2", "y % 2"];
-        let resolving_replacements = vec![Replacement::new("x".to_string(), "11".to_string(), false), Replacement::new("y".to_string(), "10".to_string(), true)];
-        let resolving_results = ["6", "1", "2", "24", "1", "0"];
+        let resolving_tests = ["1 + 5", "3 - 2", "8 / 4", "8 * 3", "x % 2", "y % 2", "-3 + x", "xy - x",
+            "1 + 2 * 3", "(1 + 2) * 3", "[1 + 2] * 3", "10 - 4 - 3", "-(x - 1)", "--5", "~0", "!0 + !7",
+            "x & 3", "x | 4", "x ^ 1", "1 << 4", "x >> 1", "xFF & b1010", "o17 + 'a'",
+            "x > 10", "x <= 10", "x == 11 && y != 0", "0 || x < 0", "1 + 1 == 2", "x - 1 >> 1"];
+        let resolving_replacements = Self::symbols(&[("x", "11", false), ("y", "10", true), ("xy", "20", false)]);
+        let resolving_results = ["6", "1", "2", "24", "1", "0", "8", "9",
+            "7", "9", "9", "3", "-10", "5", "-1", "1",
+            "3", "15", "10", "16", "5", "10", "112",
+            "1", "0", "1", "0", "1", "5"];
 
         for test in resolving_tests.iter().enumerate(){
-            let calculated_result = math::resolve_string(test.1.to_string(), resolving_replacements.clone());
+            let calculated_result = math::resolve_string(test.1.to_string(), &resolving_replacements, Span::default()).unwrap_or_default();
             if calculated_result == resolving_results[test.0]{
                 let message = format!("{} is {}", test.1, calculated_result).green();
                 println!("{}", message);
@@ -27,13 +35,25 @@ impl Test for MathTest {
             }
         }
 
+        // Test expressions that can't be resolved
+        let failing_tests = ["5 / 0", "x % 0", "1 << 64", "1 << 63", "3 << 62", "9223372036854775807 + 1", "-9223372036854775807 - 2", "z + 1", "(1 + 2", "1 +", "1 2"];
+        for test in failing_tests{
+            match math::resolve_string(test.to_string(), &resolving_replacements, Span::default()) {
+                Err(error) => println!("{}", format!("{} fails with {}", test, error).green()),
+                Ok(result) => {
+                    println!("{}", format!("{} should fail but is {}", test, result).red());
+                    return false;
+                },
+            }
+        }
+
         // Test resolving in strings via argument parser
-        let string_resolving_tests = ["add R1 [1 + 2]", "add R1 [x + 5]", "asdf[5 - x]", "sto [x * y]"];
-        let string_resolving_replacements = vec![Replacement::new("x".to_string(), "11".to_string(), false), Replacement::new("y".to_string(), "10".to_string(), true)];
-        let string_resolving_solutions = ["add R1 3", "add R1 16", "asdf-6", "sto 110"];
+        let string_resolving_tests = ["add R1 [1 + 2]", "add R1 [x + 5]", "asdf[5 - x]", "sto [x * y]", "add r10 a", "mov ra adder", "jmp [c + 1]", "jmp [[c + 1] * 2]"];
+        let string_resolving_replacements = Self::symbols(&[("x", "11", false), ("y", "10", true), ("a", "5", false), ("adder", "7", true), ("c", "a", false)]);
+        let string_resolving_solutions = ["add R1 3", "add R1 16", "asdf-6", "sto 110", "add r10 5", "mov ra 7", "jmp 6", "jmp 12"];
 
         for test in string_resolving_tests.iter().enumerate(){
-            let calculated_result = ArgumentParser::resolve_all_math_ops_in_line(test.1.to_string(), string_resolving_replacements.clone());
+            let calculated_result = ArgumentParser::resolve_all_math_ops_in_line(test.1.to_string(), &string_resolving_replacements, Span::default()).unwrap_or_default();
             let solution = string_resolving_solutions[test.0];
             if calculated_result == solution{
                 let message = format!("{:?} is {}", test.1, calculated_result).green();
@@ -41,8 +61,19 @@ impl Test for MathTest {
             }else {
                 let message = format!("{:?} shouldn't be {}", test.1, calculated_result).red();
                 println!("{}", message);
+                return false;
             }
         }
         true
     }
 }
+
+impl MathTest {
+    fn symbols(declarations: &[(&str, &str, bool)]) -> SymbolTable {
+        let mut symbols = SymbolTable::new();
+        for (name, value, is_function) in declarations {
+            _ = symbols.declare(Replacement::new(name.to_string(), value.to_string(), *is_function));
+        }
+        symbols
+    }
+}
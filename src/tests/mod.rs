@@ -1,2 +1,16 @@
 pub mod test;
-mod math_test;
\ No newline at end of file
+mod math_test;
+mod error_test;
+mod assembler_test;
+mod isa_test;
+mod disassembler_test;
+mod emulator_test;
+mod debugger_test;
+mod gdb_stub_test;
+mod dap_test;
+mod lsp_test;
+mod listing_test;
+mod symbol_map_test;
+mod image_test;
+mod preprocessor_test;
+mod linker_test;
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::assembler::Assembler;
+use crate::preprocessor::Preprocessor;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct PreprocessorTest;
+
+/// Code, constants given on the command line and the expected binary.
+type ConditionalTest<'a> = (&'a str, &'a [(&'a str, &'a str)], &'a [u8]);
+
+impl Test for PreprocessorTest {
+    fn execute() -> bool{
+        let start_message = "Starting preprocessor tests.".green();
+        println!("{}\n", start_message);
+        // Arguments replace whole names only and every expansion gets its own labels
+        let macro_tests: [(&str, &[u8]); 5] = [
+            (".macro bump reg, amount\n    add reg amount\n.endm\n<text>\nbump r1, 1\nbump r2 [2 * 3]\nhalt", &[0x40, 0x81, 1, 0x40, 0x82, 6, 0x60]),
+            (".macro skip\njmp over\nover:\n.endm\n<text>\nskip\nskip\nhalt", &[0x62, 2, 0x62, 4, 0x60]),
+            (".macro one r\ninc r\n.endm\n.macro two r\none r # twice\none r\n.endm\n<text>\ntwo r3\nhalt", &[0x40, 0x83, 1, 0x40, 0x83, 1, 0x60]),
+            (".macro m a\nadd r1 [a + ab]\n.endm\n<text>\n.ab 2\nm 3\nhalt", &[0x40, 0x81, 5, 0x60]),
+            (".macro values name, first\nname 8b 'a' first\n.endm\n<data>\nvalues a 7\n<text>\nhalt", &[0x60, 0x61, 7]),
+        ];
+        for (code, expected) in macro_tests {
+            let result = Assembler::new("test.asm".to_string(), code.to_string()).assemble();
+            if result.as_deref() == Ok(expected) {
+                println!("{}", format!("{:?} assembles to {:02X?}", code, expected).green());
+            }else{
+                println!("{}", format!("{:?} should assemble to {:02X?} but resulted in {:02X?}", code, expected, result).red());
+                return false;
+            }
+        }
+
+        // Only the lines of the taken branch are assembled, constants come from the code or the command line
+        let board = "<text>\n.if BOARD == 2\ninc r1\n.elif BOARD == 3\ninc r2\n.else\ninc r3\n.endif\nhalt";
+        let conditional_tests: [ConditionalTest; 8] = [
+            (board, &[("BOARD", "2")], &[0x40, 0x81, 1, 0x60]),
+            (board, &[("BOARD", "3")], &[0x40, 0x82, 1, 0x60]),
+            (board, &[("BOARD", "x4")], &[0x40, 0x83, 1, 0x60]),
+            ("<text>\n.ifndef SPEED\n.SPEED 4\n.endif\n.ifdef SPEED\nadd r1 SPEED\n.endif\nhalt", &[], &[0x40, 0x81, 4, 0x60]),
+            ("<text>\n.ifndef SPEED\n.SPEED 4\n.endif\nadd r1 SPEED\nhalt", &[("SPEED", "[1 + 1]")], &[0x40, 0x81, 2, 0x60]),
+            ("<text>\n.if 0\n.if 1\ninc r1\n.else\ninc r2\n.endif\n.endif\nhalt", &[], &[0x60]),
+            ("<text>\njmp end\n.if 0\ninc r1\n.endif\nend:\nhalt", &[], &[0x62, 2, 0x60]),
+            (".macro m n\n.if n > 1\ninc r1\n.endif\n.endm\n<text>\nm 1\nm 2\nhalt", &[], &[0x40, 0x81, 1, 0x60]),
+        ];
+        for (code, defines, expected) in conditional_tests {
+            let defines: Vec<(String, String)> = defines.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+            let result = Assembler::new("test.asm".to_string(), code.to_string()).with_defines(defines.clone()).assemble();
+            if result.as_deref() == Ok(expected) {
+                println!("{}", format!("{:?} with {:?} assembles to {:02X?}", code, defines, expected).green());
+            }else{
+                println!("{}", format!("{:?} with {:?} should assemble to {:02X?} but resulted in {:02X?}", code, defines, expected, result).red());
+                return false;
+            }
+        }
+        let define_tests = [
+            ("BOARD=2", Some(("BOARD".to_string(), "2".to_string()))),
+            ("FAST", Some(("FAST".to_string(), "1".to_string()))),
+            ("2X=1", None),
+            ("r1=3", None),
+            ("EMPTY=", None),
+            ("data_offset=3", None),
+        ];
+        for (argument, expected) in define_tests {
+            let define = Preprocessor::parse_define(argument).ok();
+            if define == expected {
+                println!("{}", format!("-D {} defines {:?}", argument, expected).green());
+            }else{
+                println!("{}", format!("-D {} should define {:?} but resulted in {:?}", argument, expected, define).red());
+                return false;
+            }
+        }
+
+        // Message, line and column of the first error, located where the macro is used
+        let error_tests = [
+            (".macro m a\nadd r1 a\n.endm\n<text>\nm 1 2\nhalt", "Macro m takes 1 argument(s) but 2 were given.", 5, 1),
+            (".macro m\nm\n.endm\n<text>\nm\nhalt", "Macro m is nested more than 64 expansions deep.", 5, 1),
+            (".macro m\n<text>\nhalt", "Macro m is missing its .endm.", 1, 1),
+            ("<text>\n.endm\nhalt", ".endm has no matching .macro.", 2, 1),
+            (".macro add r\n.endm\n<text>\nhalt", "Macro add would hide the instruction of the same name.", 1, 8),
+            (".macro m\nloop:\njmp loop\n.endm\n<text>\nm\nloop__1:\nhalt", "loop__1 ends with __ and a number, which is reserved for the labels inside of expanded macros.", 7, 1),
+            (".macro m v\nadd r1 v\n.endm\n<text>\nm 200\nhalt", "Argument 200 should be within range 0...127. Please define it in the data section instead.", 5, 1),
+            ("<text>\n.if 1\nhalt", "This block is missing its .endif.", 2, 1),
+            ("<text>\n.else\nhalt", ".else has no matching .if.", 2, 1),
+            ("<text>\n.if 1\n.else\n.else\n.endif\nhalt", ".else can't follow the .else of the same .if.", 4, 1),
+            ("<text>\n.if LATER\n.endif\n.LATER 1\nhalt", "Symbol LATER was never declared.", 2, 5),
+            (".macro m\n.endif\n.endm\n<text>\n.if 1\nm\n.endif\nhalt", ".endif has no matching .if.", 6, 1),
+            ("<text>\n.include lib.asm\nhalt", ".include takes a quoted path like \"lib/print.asm\".", 2, 1),
+            ("<text>\n.include \"missing.asm\"\nhalt", "Couldn't include missing.asm: it is neither in the current directory nor in a directory given with -I.", 2, 1),
+        ];
+        for (code, message, line, column) in error_tests {
+            let mut assembler = Assembler::new("test.asm".to_string(), code.to_string());
+            _ = assembler.assemble();
+            let error = assembler.diagnostics.get_errors().first().cloned();
+            let found = error.map(|error| (error.to_string(), error.span().map(|span| assembler.sources.root(span)).unwrap_or_default()));
+            if found.as_ref().is_some_and(|(text, span)| text == message && span.line == line && span.column == column) {
+                println!("{}", format!("{:?} fails with {} at {}:{}", code, message, line, column).green());
+            }else{
+                println!("{}", format!("{:?} should fail with {} at {}:{} but resulted in {:?}", code, message, line, column, found).red());
+                return false;
+            }
+        }
+
+        // Errors inside an expansion also point at the macro body and the use
+        let code = ".macro m v\n  add r1 v\n.endm\n<text>\nm 200\nhalt";
+        let mut assembler = Assembler::new("test.asm".to_string(), code.to_string());
+        _ = assembler.assemble();
+        let notes = assembler.diagnostics.get_errors().first().and_then(|error| error.span())
+            .map(|span| assembler.sources.origin_notes(span).into_iter().map(|(span, note)| (span.line, span.column, note)).collect::<Vec<_>>());
+        let expected = vec![(2, 3, "in this line of macro m".to_string()), (5, 1, "m used here".to_string())];
+        if notes.as_ref() == Some(&expected) {
+            println!("{}", format!("{:?} notes {:?}", code, expected).green());
+        }else{
+            println!("{}", format!("{:?} should note {:?} but resulted in {:?}", code, expected, notes).red());
+            return false;
+        }
+
+        // Included files are looked up next to the including file, then in the include paths
+        let directory = std::env::temp_dir().join("sriscasm_include_test");
+        let files = [
+            ("lib/bump.asm", ".include \"step.asm\"\n.macro bump r\ninc r\n.endm"),
+            ("lib/step.asm", ".STEP 2"),
+            ("shared/twice.asm", "inc r2\ninc r2"),
+            ("cycle.asm", ".include \"lib/../cycle.asm\""),
+        ];
+        for (name, code) in files {
+            let path = directory.join(name);
+            if let Err(reason) = fs::create_dir_all(path.parent().unwrap_or(Path::new("."))).and_then(|_| fs::write(&path, code)) {
+                println!("{}", format!("Couldn't write {}: {}", path.display(), reason).red());
+                return false;
+            }
+        }
+        let include_tests: [(&str, &[PathBuf], &[u8]); 3] = [
+            ("<text>\n.include \"lib/bump.asm\"\nbump r1\nadd r1 STEP\nhalt", &[], &[0x40, 0x81, 1, 0x40, 0x81, 2, 0x60]),
+            ("<text>\n.include \"twice.asm\"\nhalt", &[directory.join("lib"), directory.join("shared")], &[0x40, 0x82, 1, 0x40, 0x82, 1, 0x60]),
+            ("<text>\n.if 0\n.include \"missing.asm\"\n.endif\nhalt", &[], &[0x60]),
+        ];
+        for (code, include_paths, expected) in include_tests {
+            let name = directory.join("main.asm").display().to_string();
+            let result = Assembler::new(name, code.to_string()).with_include_paths(include_paths.to_vec()).assemble();
+            if result.as_deref() == Ok(expected) {
+                println!("{}", format!("{:?} with {:?} assembles to {:02X?}", code, include_paths, expected).green());
+            }else{
+                println!("{}", format!("{:?} with {:?} should assemble to {:02X?} but resulted in {:02X?}", code, include_paths, expected, result).red());
+                return false;
+            }
+        }
+
+        // A file including itself is reported where the cycle closes, with every .include on the way as note
+        let code = "<text>\n.include \"cycle.asm\"\nhalt";
+        let mut assembler = Assembler::new(directory.join("main.asm").display().to_string(), code.to_string());
+        _ = assembler.assemble();
+        let error = assembler.diagnostics.get_errors().first().cloned();
+        let found = error.map(|error| (error.to_string(), error.span().map(|span| assembler.sources.origin_notes(span).into_iter().map(|(span, _)| span.line).collect::<Vec<u32>>())));
+        let expected = ("Couldn't include lib/../cycle.asm: it is already being included, so it would include itself forever.".to_string(), Some(vec![2]));
+        if found.as_ref() == Some(&expected) {
+            println!("{}", format!("{:?} fails with {:?}", code, expected).green());
+        }else{
+            println!("{}", format!("{:?} should fail with {:?} but resulted in {:?}", code, expected, found).red());
+            return false;
+        }
+
+        println!();
+        true
+    }
+}
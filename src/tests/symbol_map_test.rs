@@ -0,0 +1,46 @@
+use crate::assembler::Assembler;
+use crate::symbol_map::SymbolMap;
+use crate::tests::test::Test;
+use colored::*;
+
+pub struct SymbolMapTest;
+
+impl Test for SymbolMapTest {
+    fn execute() -> bool{
+        let start_message = "Starting symbol map tests.".green();
+        println!("{}\n", start_message);
+        let code = "<data>\nvalues 16b 1 2\n<text>\n.minus -3\nstart:\nmov r1 values\nloop:\njmp loop\nhalt";
+        let mut assembler = Assembler::new("test.asm".to_string(), code.to_string()).with_defines(vec![("BOARD".to_string(), "2".to_string())]);
+        if assembler.assemble().is_err() {
+            println!("{}", format!("{:?} should assemble", code).red());
+            return false;
+        }
+        // Labels reach up to the next label or the end of the text section, data as far as its values, -D constants have no location
+        let map_tests = [
+            (SymbolMap::render_text(&assembler),
+"NAME                    KIND      VALUE     SIZE  LOCATION
+minus                   constant  -3        0     test.asm:4:2
+start                   label     0x0000    3     test.asm:5:1
+BOARD                   constant  0x0002    0
+loop                    label     0x0003    3     test.asm:7:1
+values                  data      0x0006    4     test.asm:2:1
+"),
+            (SymbolMap::render_json(&assembler),
+"{\"symbols\":[{\"name\":\"minus\",\"kind\":\"constant\",\"value\":-3,\"size\":0,\"file\":\"test.asm\",\"line\":4,\"column\":2},\
+{\"name\":\"start\",\"kind\":\"label\",\"value\":0,\"size\":3,\"file\":\"test.asm\",\"line\":5,\"column\":1},\
+{\"name\":\"BOARD\",\"kind\":\"constant\",\"value\":2,\"size\":0,\"file\":null,\"line\":null,\"column\":null},\
+{\"name\":\"loop\",\"kind\":\"label\",\"value\":3,\"size\":3,\"file\":\"test.asm\",\"line\":7,\"column\":1},\
+{\"name\":\"values\",\"kind\":\"data\",\"value\":6,\"size\":4,\"file\":\"test.asm\",\"line\":2,\"column\":1}]}
+"),
+        ];
+        for (map, expected) in map_tests {
+            if map == expected {
+                println!("{}", format!("{:?} is mapped as\n{}", code, expected).green());
+            }else{
+                println!("{}", format!("{:?} should be mapped as\n{}\nbut resulted in\n{}", code, expected, map).red());
+                return false;
+            }
+        }
+        true
+    }
+}
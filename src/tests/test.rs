@@ -1,4 +1,4 @@
-use crate::tests::math_test;
+use crate::tests::{assembler_test, dap_test, debugger_test, disassembler_test, emulator_test, error_test, gdb_stub_test, image_test, isa_test, linker_test, listing_test, lsp_test, math_test, preprocessor_test, symbol_map_test};
 
 pub trait Test{
     fn execute() -> bool;
@@ -6,5 +6,19 @@ pub trait Test{
 
 pub fn test_all() -> bool{
     let math_tests_pass = math_test::MathTest::execute();
-    return math_tests_pass;
-}
\ No newline at end of file
+    let error_tests_pass = error_test::ErrorTest::execute();
+    let assembler_tests_pass = assembler_test::AssemblerTest::execute();
+    let isa_tests_pass = isa_test::IsaTest::execute();
+    let disassembler_tests_pass = disassembler_test::DisassemblerTest::execute();
+    let emulator_tests_pass = emulator_test::EmulatorTest::execute();
+    let debugger_tests_pass = debugger_test::DebuggerTest::execute();
+    let gdb_stub_tests_pass = gdb_stub_test::GdbStubTest::execute();
+    let dap_tests_pass = dap_test::DapTest::execute();
+    let lsp_tests_pass = lsp_test::LspTest::execute();
+    let listing_tests_pass = listing_test::ListingTest::execute();
+    let symbol_map_tests_pass = symbol_map_test::SymbolMapTest::execute();
+    let image_tests_pass = image_test::ImageTest::execute();
+    let preprocessor_tests_pass = preprocessor_test::PreprocessorTest::execute();
+    let linker_tests_pass = linker_test::LinkerTest::execute();
+    math_tests_pass && error_tests_pass && assembler_tests_pass && isa_tests_pass && disassembler_tests_pass && emulator_tests_pass && debugger_tests_pass && gdb_stub_tests_pass && dap_tests_pass && lsp_tests_pass && listing_tests_pass && symbol_map_tests_pass && image_tests_pass && preprocessor_tests_pass && linker_tests_pass
+}
@@ -0,0 +1,149 @@
+use crate::argument_parser::ArgumentParser;
+use crate::error::AssembleError;
+use crate::lexer::{Lexeme, LexemeKind, Lexer};
+use crate::source::Span;
+use crate::symbol_table::SymbolTable;
+use crate::utility::operation::{Operation, UnaryOperation};
+
+/// A parsed expression like `(start + 3) << 1`, evaluated once all symbols are known.
+pub enum Expression {
+    Number(i64),
+    Symbol(Lexeme),
+    Unary(UnaryOperation, Box<Expression>),
+    Binary(Operation, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// Parses the whole text as a single expression. The span is where the text starts in the source.
+    pub fn parse(text: &str, span: Span) -> Result<Expression, AssembleError> {
+        let lexemes = Lexer::tokenize(text, span);
+        let mut parser = ExpressionParser{lexemes: &lexemes, position: 0, text, span};
+        let expression = parser.parse_binary(0)?;
+        if let Some(unexpected) = lexemes.get(parser.position) {
+            return Err(parser.error(&format!("unexpected {} after the expression", unexpected.text), Some(unexpected)));
+        }
+        Ok(expression)
+    }
+
+    /// Calculates the value, resolving names through the symbol table.
+    pub fn evaluate(&self, symbols: &SymbolTable, text: &str, span: Span) -> Result<i64, AssembleError> {
+        let undefined = |reason: &str| AssembleError::BadExpression { expression: text.to_string(), reason: reason.to_string(), span };
+        match self {
+            Expression::Number(value) => Ok(*value),
+            Expression::Symbol(lexeme) => Self::evaluate_symbol(lexeme, symbols),
+            Expression::Unary(operation, operand) => {
+                let value = operand.evaluate(symbols, text, span)?;
+                operation.perform(value).ok_or_else(|| undefined("the result doesn't fit into 64 bits"))
+            },
+            Expression::Binary(operation, left, right) => {
+                let left = left.evaluate(symbols, text, span)?;
+                // Logical operations only look at the right side if they have to
+                match operation {
+                    Operation::LOGICAL_AND if left == 0 => return Ok(0),
+                    Operation::LOGICAL_OR if left != 0 => return Ok(1),
+                    _ => {},
+                }
+                let right = right.evaluate(symbols, text, span)?;
+                match operation.perform(left, right) {
+                    Some(result) => Ok(result),
+                    None if right == 0 && matches!(operation, Operation::DIVISION | Operation::MODULO) => Err(undefined("division by zero")),
+                    None if matches!(operation, Operation::SHIFT_LEFT | Operation::SHIFT_RIGHT) && !(0..64).contains(&right) => Err(undefined("can't shift by a negative amount or more than 63 bits")),
+                    None => Err(undefined("the result doesn't fit into 64 bits")),
+                }
+            },
+        }
+    }
+
+    /// Names are looked up in the symbol table first and read as prefixed numbers (x1F, o17, b101) otherwise.
+    fn evaluate_symbol(lexeme: &Lexeme, symbols: &SymbolTable) -> Result<i64, AssembleError> {
+        if let Some(value) = symbols.resolve(&lexeme.text, lexeme.span)? {
+            // Constants like `.mask xFF` keep the number system they were written in
+            let value = value.trim();
+            return value.parse::<i64>().ok().or_else(|| ArgumentParser::prefixed_number(value)).ok_or_else(|| AssembleError::BadOperand {
+                operand: lexeme.text.clone(),
+                reason: format!("is {}, which can't be used in an expression", value),
+                span: lexeme.span,
+            });
+        }
+        if let Some(value) = ArgumentParser::prefixed_number(&lexeme.text) {
+            return Ok(value);
+        }
+        if ArgumentParser::looks_like_literal(&lexeme.text) {
+            return Err(AssembleError::BadOperand { operand: lexeme.text.clone(), reason: "is a register, which can't be used in an expression".to_string(), span: lexeme.span });
+        }
+        Err(AssembleError::UndefinedSymbol { name: lexeme.text.clone(), span: lexeme.span })
+    }
+}
+
+struct ExpressionParser<'a> {
+    lexemes: &'a [Lexeme],
+    position: usize,
+    text: &'a str,
+    span: Span,
+}
+
+impl ExpressionParser<'_> {
+    /// Precedence climbing: parses operations binding at least as strong as min_precedence.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, AssembleError> {
+        let mut left = self.parse_unary()?;
+        while let Some(lexeme) = self.lexemes.get(self.position) {
+            let Some(operation) = Operation::from_string(&lexeme.text).filter(|_| lexeme.kind == LexemeKind::Operator) else { break; };
+            let precedence = operation.precedence();
+            if precedence < min_precedence { break; }
+            self.position += 1;
+            // All binary operations are left associative
+            let right = self.parse_binary(precedence + 1)?;
+            left = Expression::Binary(operation, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, AssembleError> {
+        if let Some(lexeme) = self.lexemes.get(self.position).filter(|lexeme| lexeme.kind == LexemeKind::Operator) {
+            if lexeme.text == "+" {
+                self.position += 1;
+                return self.parse_unary();
+            }
+            if let Some(operation) = UnaryOperation::from_string(&lexeme.text) {
+                self.position += 1;
+                return Ok(Expression::Unary(operation, Box::new(self.parse_unary()?)));
+            }
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, AssembleError> {
+        let Some(lexeme) = self.lexemes.get(self.position) else {
+            return Err(self.error("expected a value but the expression ended", None));
+        };
+        self.position += 1;
+        match lexeme.kind {
+            LexemeKind::Number => lexeme.text.parse::<i64>().map(Expression::Number)
+                .map_err(|_| self.error("the number doesn't fit into 64 bits", Some(lexeme))),
+            LexemeKind::Identifier => Ok(Expression::Symbol(lexeme.clone())),
+            LexemeKind::Character => {
+                let characters: Vec<char> = lexeme.text.chars().collect();
+                match characters.as_slice() {
+                    ['\'', character, '\''] if character.is_ascii() => Ok(Expression::Number(*character as i64)),
+                    _ => Err(self.error("isn't a single ASCII character", Some(lexeme))),
+                }
+            },
+            LexemeKind::OpenParenthesis | LexemeKind::OpenBracket => {
+                let closing = if lexeme.kind == LexemeKind::OpenParenthesis { LexemeKind::CloseParenthesis } else { LexemeKind::CloseBracket };
+                let inner = self.parse_binary(0)?;
+                match self.lexemes.get(self.position) {
+                    Some(next) if next.kind == closing => {
+                        self.position += 1;
+                        Ok(inner)
+                    },
+                    next => Err(self.error(&format!("{} isn't closed", lexeme.text), next.or(Some(lexeme)))),
+                }
+            },
+            _ => Err(self.error(&format!("expected a value but found {}", lexeme.text), Some(lexeme))),
+        }
+    }
+
+    fn error(&self, reason: &str, at: Option<&Lexeme>) -> AssembleError {
+        AssembleError::BadExpression { expression: self.text.to_string(), reason: reason.to_string(), span: at.map(|lexeme| lexeme.span).unwrap_or(self.span) }
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+
+/// A parsed JSON document. TOML files are read into the same structure, so both can be handled alike.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Integers are kept apart from other numbers so big values don't lose precision.
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    /// Keys keep the order they were written in.
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /// Parses a whole JSON document. The error tells the line and what was expected.
+    pub fn parse(text: &str) -> Result<JsonValue, String> {
+        let mut parser = JsonParser{characters: text.chars().collect(), position: 0};
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.position < parser.characters.len() {
+            return Err(parser.error("unexpected characters after the document"));
+        }
+        Ok(value)
+    }
+
+    /// An object with the entries in the given order.
+    pub fn object(entries: Vec<(&str, JsonValue)>) -> JsonValue {
+        JsonValue::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            JsonValue::Integer(value) => Some(*value),
+            JsonValue::Float(value) if value.fract() == 0.0 => Some(*value as i64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<JsonValue>> {
+        match self {
+            JsonValue::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for JsonValue {
+    fn from(text: &str) -> JsonValue { JsonValue::String(text.to_string()) }
+}
+
+impl From<String> for JsonValue {
+    fn from(text: String) -> JsonValue { JsonValue::String(text) }
+}
+
+impl From<i64> for JsonValue {
+    fn from(value: i64) -> JsonValue { JsonValue::Integer(value) }
+}
+
+impl From<bool> for JsonValue {
+    fn from(value: bool) -> JsonValue { JsonValue::Bool(value) }
+}
+
+impl From<Vec<JsonValue>> for JsonValue {
+    fn from(values: Vec<JsonValue>) -> JsonValue { JsonValue::Array(values) }
+}
+
+impl fmt::Display for JsonValue {
+    /// Writes the value as compact JSON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(value) => write!(f, "{}", value),
+            JsonValue::Integer(value) => write!(f, "{}", value),
+            JsonValue::Float(value) => write!(f, "{}", value),
+            JsonValue::String(text) => write_escaped(f, text),
+            JsonValue::Array(values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            },
+            JsonValue::Object(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write_escaped(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+fn write_escaped(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for character in text.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct JsonParser {
+    characters: Vec<char>,
+    position: usize,
+}
+
+impl JsonParser {
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.characters.get(self.position).copied() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => self.parse_string().map(JsonValue::String),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some(_) if self.consume_word("true") => Ok(JsonValue::Bool(true)),
+            Some(_) if self.consume_word("false") => Ok(JsonValue::Bool(false)),
+            Some(_) if self.consume_word("null") => Ok(JsonValue::Null),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of the document")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, String> {
+        let mut entries = Vec::new();
+        self.position += 1;
+        self.skip_whitespace();
+        if self.consume('}') { return Ok(JsonValue::Object(entries)); }
+        loop {
+            self.skip_whitespace();
+            if self.characters.get(self.position) != Some(&'"') {
+                return Err(self.error("expected a key in quotes"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            if !self.consume(':') { return Err(self.error("expected : after the key")); }
+            entries.push((key, self.parse_value()?));
+            self.skip_whitespace();
+            if self.consume('}') { return Ok(JsonValue::Object(entries)); }
+            if !self.consume(',') { return Err(self.error("expected , or }")); }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<JsonValue, String> {
+        let mut values = Vec::new();
+        self.position += 1;
+        self.skip_whitespace();
+        if self.consume(']') { return Ok(JsonValue::Array(values)); }
+        loop {
+            values.push(self.parse_value()?);
+            self.skip_whitespace();
+            if self.consume(']') { return Ok(JsonValue::Array(values)); }
+            if !self.consume(',') { return Err(self.error("expected , or ]")); }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        let mut text = String::new();
+        self.position += 1;
+        loop {
+            let Some(character) = self.characters.get(self.position).copied() else {
+                return Err(self.error("the closing \" is missing"));
+            };
+            self.position += 1;
+            match character {
+                '"' => return Ok(text),
+                '\\' => {
+                    let Some(escaped) = self.characters.get(self.position).copied() else { continue; };
+                    self.position += 1;
+                    match escaped {
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        'b' => text.push('\u{8}'),
+                        'f' => text.push('\u{c}'),
+                        'u' => {
+                            let mut code = self.hex_escape();
+                            // Characters outside the Basic Multilingual Plane are written as a pair of UTF-16 surrogates
+                            if code.is_some_and(|high| (0xD800..0xDC00).contains(&high)) && self.characters.get(self.position..self.position + 2) == Some(&['\\', 'u']) {
+                                self.position += 2;
+                                let low = self.hex_escape().filter(|low| (0xDC00..0xE000).contains(low));
+                                code = code.zip(low).map(|(high, low)| 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00));
+                            }
+                            let Some(decoded) = code.and_then(char::from_u32) else {
+                                return Err(self.error("invalid \\u escape"));
+                            };
+                            text.push(decoded);
+                        },
+                        other => text.push(other),
+                    }
+                },
+                other => text.push(other),
+            }
+        }
+    }
+
+    /// The four hex digits of a `\u` escape.
+    fn hex_escape(&mut self) -> Option<u32> {
+        let code: String = self.characters.iter().skip(self.position).take(4).collect();
+        self.position += 4;
+        u32::from_str_radix(&code, 16).ok()
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, String> {
+        let start = self.position;
+        while self.characters.get(self.position).is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.position += 1;
+        }
+        let text: String = self.characters[start..self.position].iter().collect();
+        if let Ok(value) = text.parse::<i64>() { return Ok(JsonValue::Integer(value)); }
+        text.parse::<f64>().map(JsonValue::Float).map_err(|_| self.error(&format!("{} isn't a number", text)))
+    }
+
+    fn consume(&mut self, expected: char) -> bool {
+        if self.characters.get(self.position) == Some(&expected) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn consume_word(&mut self, word: &str) -> bool {
+        let length = word.chars().count();
+        if self.characters.iter().skip(self.position).take(length).copied().eq(word.chars()) {
+            self.position += length;
+            return true;
+        }
+        false
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.characters.get(self.position).is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn error(&self, reason: &str) -> String {
+        let line = self.characters[..self.position.min(self.characters.len())].iter().filter(|c| **c == '\n').count() + 1;
+        format!("line {}: {}", line, reason)
+    }
+}
@@ -0,0 +1,26 @@
+use std::io::{BufRead, Write};
+use crate::utility::json::JsonValue;
+
+/// Reads the body of the next message, which is preceded by a Content-Length header as in the Debug Adapter and
+/// Language Server Protocol. `None` means the stream ended.
+pub fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 { return Ok(None); }
+        let header = header.trim();
+        if header.is_empty() && length.is_some() { break; }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length.unwrap_or_default()];
+    reader.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+/// Writes the message with its Content-Length header.
+pub fn write_message(writer: &mut impl Write, message: &JsonValue) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+}
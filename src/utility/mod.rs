@@ -1,3 +1,7 @@
+pub mod expression;
+pub mod json;
 pub mod math;
+pub mod message;
 pub mod operation;
-pub mod replacement;
\ No newline at end of file
+pub mod replacement;
+pub mod toml;
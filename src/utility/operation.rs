@@ -1,9 +1,23 @@
+#[allow(non_camel_case_types)]
 pub enum Operation {
     ADDITION,
     SUBTRACTION,
     MULTIPLICATION,
     DIVISION,
-    MODULO
+    MODULO,
+    BITWISE_AND,
+    BITWISE_OR,
+    BITWISE_XOR,
+    SHIFT_LEFT,
+    SHIFT_RIGHT,
+    EQUAL,
+    NOT_EQUAL,
+    LESS,
+    LESS_OR_EQUAL,
+    GREATER,
+    GREATER_OR_EQUAL,
+    LOGICAL_AND,
+    LOGICAL_OR,
 }
 
 impl Operation {
@@ -14,17 +28,89 @@ impl Operation {
             "*" => Some(Operation::MULTIPLICATION),
             "/" => Some(Operation::DIVISION),
             "%" => Some(Operation::MODULO),
+            "&" => Some(Operation::BITWISE_AND),
+            "|" => Some(Operation::BITWISE_OR),
+            "^" => Some(Operation::BITWISE_XOR),
+            "<<" => Some(Operation::SHIFT_LEFT),
+            ">>" => Some(Operation::SHIFT_RIGHT),
+            "==" => Some(Operation::EQUAL),
+            "!=" => Some(Operation::NOT_EQUAL),
+            "<" => Some(Operation::LESS),
+            "<=" => Some(Operation::LESS_OR_EQUAL),
+            ">" => Some(Operation::GREATER),
+            ">=" => Some(Operation::GREATER_OR_EQUAL),
+            "&&" => Some(Operation::LOGICAL_AND),
+            "||" => Some(Operation::LOGICAL_OR),
             _ => None,
         }
     }
 
-    pub fn perform(&self, op1: i64, op2: i64) -> i64 {
+    /// Operations with a higher precedence are performed first, like in C.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Operation::MULTIPLICATION | Operation::DIVISION | Operation::MODULO => 10,
+            Operation::ADDITION | Operation::SUBTRACTION => 9,
+            Operation::SHIFT_LEFT | Operation::SHIFT_RIGHT => 8,
+            Operation::LESS | Operation::LESS_OR_EQUAL | Operation::GREATER | Operation::GREATER_OR_EQUAL => 7,
+            Operation::EQUAL | Operation::NOT_EQUAL => 6,
+            Operation::BITWISE_AND => 5,
+            Operation::BITWISE_XOR => 4,
+            Operation::BITWISE_OR => 3,
+            Operation::LOGICAL_AND => 2,
+            Operation::LOGICAL_OR => 1,
+        }
+    }
+
+    /// Returns None if the result isn't defined (division by zero, shifting by more than 63 bits) or doesn't fit into an i64,
+    /// which includes shifting bits out to the left.
+    /// Comparisons and logical operations result in 1 (true) or 0 (false).
+    pub fn perform(&self, op1: i64, op2: i64) -> Option<i64> {
         match self{
-            Operation::ADDITION => op1 + op2,
-            Operation::SUBTRACTION => op1 - op2,
-            Operation::MULTIPLICATION => op1 * op2,
-            Operation::DIVISION => op1 / op2,
-            Operation::MODULO => op1 % op2,
+            Operation::ADDITION => op1.checked_add(op2),
+            Operation::SUBTRACTION => op1.checked_sub(op2),
+            Operation::MULTIPLICATION => op1.checked_mul(op2),
+            Operation::DIVISION => op1.checked_div(op2),
+            Operation::MODULO => op1.checked_rem(op2),
+            Operation::BITWISE_AND => Some(op1 & op2),
+            Operation::BITWISE_OR => Some(op1 | op2),
+            Operation::BITWISE_XOR => Some(op1 ^ op2),
+            // checked_shl only checks the shift, the bits shifted out are lost unless shifting back gives op1 again
+            Operation::SHIFT_LEFT => u32::try_from(op2).ok().and_then(|shift| op1.checked_shl(shift)).filter(|result| result >> op2 == op1),
+            Operation::SHIFT_RIGHT => u32::try_from(op2).ok().and_then(|shift| op1.checked_shr(shift)),
+            Operation::EQUAL => Some((op1 == op2) as i64),
+            Operation::NOT_EQUAL => Some((op1 != op2) as i64),
+            Operation::LESS => Some((op1 < op2) as i64),
+            Operation::LESS_OR_EQUAL => Some((op1 <= op2) as i64),
+            Operation::GREATER => Some((op1 > op2) as i64),
+            Operation::GREATER_OR_EQUAL => Some((op1 >= op2) as i64),
+            Operation::LOGICAL_AND => Some((op1 != 0 && op2 != 0) as i64),
+            Operation::LOGICAL_OR => Some((op1 != 0 || op2 != 0) as i64),
         }
     }
-}
\ No newline at end of file
+}
+
+#[allow(non_camel_case_types)]
+pub enum UnaryOperation {
+    NEGATION,
+    BITWISE_NOT,
+    LOGICAL_NOT,
+}
+
+impl UnaryOperation {
+    pub fn from_string(s: &str) -> Option<UnaryOperation> {
+        match s {
+            "-" => Some(UnaryOperation::NEGATION),
+            "~" => Some(UnaryOperation::BITWISE_NOT),
+            "!" => Some(UnaryOperation::LOGICAL_NOT),
+            _ => None,
+        }
+    }
+
+    pub fn perform(&self, op: i64) -> Option<i64> {
+        match self {
+            UnaryOperation::NEGATION => op.checked_neg(),
+            UnaryOperation::BITWISE_NOT => Some(!op),
+            UnaryOperation::LOGICAL_NOT => Some((op == 0) as i64),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::utility::json::JsonValue;
+
+/// Reads the part of TOML that configuration files need: tables, arrays of tables, strings, integers
+/// (also with 0x, 0o and 0b prefixes), floats, booleans, arrays and inline tables. Dates aren't supported.
+pub fn parse(text: &str) -> Result<JsonValue, String> {
+    let mut parser = TomlParser{characters: text.chars().collect(), position: 0};
+    let mut root = JsonValue::Object(Vec::new());
+    // The path of the table that key/value pairs currently go to
+    let mut current_table: Vec<String> = Vec::new();
+
+    loop {
+        parser.skip_whitespace_and_comments(true);
+        let Some(character) = parser.peek() else { break; };
+        if character == '[' {
+            let is_array = parser.peek_at(1) == Some('[');
+            parser.position += if is_array { 2 } else { 1 };
+            let path = parser.parse_key()?;
+            parser.skip_whitespace_and_comments(false);
+            if !parser.consume(']') || (is_array && !parser.consume(']')) {
+                return Err(parser.error("expected ] after the table name"));
+            }
+            let (last, parents) = path.split_last().expect("keys aren't empty");
+            let parent = table_at(&mut root, parents, &parser)?;
+            if is_array {
+                match object_entry(parent, last) {
+                    Some(JsonValue::Array(tables)) => tables.push(JsonValue::Object(Vec::new())),
+                    Some(_) => return Err(parser.error(&format!("{} is already declared as something else", last))),
+                    None => insert(parent, last, JsonValue::Array(vec![JsonValue::Object(Vec::new())])),
+                }
+            } else if object_entry(parent, last).is_none() {
+                insert(parent, last, JsonValue::Object(Vec::new()));
+            }
+            current_table = path;
+        } else {
+            let path = parser.parse_key()?;
+            parser.skip_whitespace_and_comments(false);
+            if !parser.consume('=') { return Err(parser.error("expected = after the key")); }
+            let value = parser.parse_value()?;
+            let (last, parents) = path.split_last().expect("keys aren't empty");
+            let full_path: Vec<String> = current_table.iter().chain(parents.iter()).cloned().collect();
+            let table = table_at(&mut root, &full_path, &parser)?;
+            if object_entry(table, last).is_some() {
+                return Err(parser.error(&format!("{} is declared more than once", last)));
+            }
+            insert(table, last, value);
+        }
+        parser.skip_whitespace_and_comments(false);
+        if parser.peek().is_some_and(|c| c != '\n' && c != '\r') {
+            return Err(parser.error("expected the end of the line"));
+        }
+    }
+    Ok(root)
+}
+
+/// The table at the path, creating missing tables. For arrays of tables the last one is used.
+fn table_at<'a>(root: &'a mut JsonValue, path: &[String], parser: &TomlParser) -> Result<&'a mut JsonValue, String> {
+    let mut table = root;
+    for key in path {
+        if object_entry(table, key).is_none() {
+            insert(table, key, JsonValue::Object(Vec::new()));
+        }
+        table = match object_entry(table, key) {
+            Some(JsonValue::Array(tables)) => tables.last_mut().ok_or_else(|| parser.error(&format!("{} is empty", key)))?,
+            Some(entry @ JsonValue::Object(_)) => entry,
+            _ => return Err(parser.error(&format!("{} isn't a table", key))),
+        };
+    }
+    Ok(table)
+}
+
+fn object_entry<'a>(table: &'a mut JsonValue, key: &str) -> Option<&'a mut JsonValue> {
+    match table {
+        JsonValue::Object(entries) => entries.iter_mut().find(|(name, _)| name == key).map(|(_, value)| value),
+        _ => None,
+    }
+}
+
+fn insert(table: &mut JsonValue, key: &str, value: JsonValue) {
+    if let JsonValue::Object(entries) = table {
+        entries.push((key.to_string(), value));
+    }
+}
+
+struct TomlParser {
+    characters: Vec<char>,
+    position: usize,
+}
+
+impl TomlParser {
+    /// A bare, quoted or dotted key like `name`, `"my name"` or `a.b`.
+    fn parse_key(&mut self) -> Result<Vec<String>, String> {
+        let mut path = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments(false);
+            let key = match self.peek() {
+                Some('"') => self.parse_basic_string()?,
+                Some('\'') => self.parse_literal_string()?,
+                _ => {
+                    let start = self.position;
+                    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                        self.position += 1;
+                    }
+                    if start == self.position { return Err(self.error("expected a key")); }
+                    self.characters[start..self.position].iter().collect()
+                },
+            };
+            path.push(key);
+            self.skip_whitespace_and_comments(false);
+            if !self.consume('.') { return Ok(path); }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace_and_comments(false);
+        match self.peek() {
+            Some('"') => self.parse_basic_string().map(JsonValue::String),
+            Some('\'') => self.parse_literal_string().map(JsonValue::String),
+            Some('[') => {
+                self.position += 1;
+                let mut values = Vec::new();
+                loop {
+                    self.skip_whitespace_and_comments(true);
+                    if self.consume(']') { return Ok(JsonValue::Array(values)); }
+                    values.push(self.parse_value()?);
+                    self.skip_whitespace_and_comments(true);
+                    if self.consume(']') { return Ok(JsonValue::Array(values)); }
+                    if !self.consume(',') { return Err(self.error("expected , or ]")); }
+                }
+            },
+            Some('{') => {
+                self.position += 1;
+                let mut table = JsonValue::Object(Vec::new());
+                self.skip_whitespace_and_comments(false);
+                if self.consume('}') { return Ok(table); }
+                loop {
+                    let path = self.parse_key()?;
+                    self.skip_whitespace_and_comments(false);
+                    if !self.consume('=') { return Err(self.error("expected = after the key")); }
+                    let value = self.parse_value()?;
+                    let (last, parents) = path.split_last().expect("keys aren't empty");
+                    insert(table_at(&mut table, parents, self)?, last, value);
+                    self.skip_whitespace_and_comments(false);
+                    if self.consume('}') { return Ok(table); }
+                    if !self.consume(',') { return Err(self.error("expected , or }")); }
+                }
+            },
+            Some(_) => {
+                let start = self.position;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.')) {
+                    self.position += 1;
+                }
+                let word: String = self.characters[start..self.position].iter().collect();
+                Self::parse_scalar(&word).ok_or_else(|| self.error(&format!("{} isn't a valid value", word)))
+            },
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn parse_scalar(word: &str) -> Option<JsonValue> {
+        match word {
+            "true" => return Some(JsonValue::Bool(true)),
+            "false" => return Some(JsonValue::Bool(false)),
+            "" => return None,
+            _ => {},
+        }
+        let digits = word.replace('_', "");
+        let (sign, unsigned) = match digits.strip_prefix('-') {
+            Some(rest) => (-1, rest),
+            None => (1, digits.strip_prefix('+').unwrap_or(&digits)),
+        };
+        let radix = match unsigned.get(..2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+        let number = if radix == 10 { unsigned } else { &unsigned[2..] };
+        if let Ok(value) = i64::from_str_radix(number, radix) {
+            return Some(JsonValue::Integer(sign * value));
+        }
+        digits.parse::<f64>().ok().map(JsonValue::Float)
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, String> {
+        let mut text = String::new();
+        self.position += 1;
+        loop {
+            let Some(character) = self.peek() else { return Err(self.error("the closing \" is missing")); };
+            self.position += 1;
+            match character {
+                '"' => return Ok(text),
+                '\n' => return Err(self.error("the closing \" is missing")),
+                '\\' => {
+                    let Some(escaped) = self.peek() else { continue; };
+                    self.position += 1;
+                    match escaped {
+                        'n' => text.push('\n'),
+                        'r' => text.push('\r'),
+                        't' => text.push('\t'),
+                        other => text.push(other),
+                    }
+                },
+                other => text.push(other),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let start = self.position;
+        while self.peek().is_some_and(|c| c != '\'' && c != '\n') {
+            self.position += 1;
+        }
+        if !self.consume('\'') { return Err(self.error("the closing ' is missing")); }
+        Ok(self.characters[start..self.position - 1].iter().collect())
+    }
+
+    fn skip_whitespace_and_comments(&mut self, including_newlines: bool) {
+        while let Some(character) = self.peek() {
+            match character {
+                '#' => while self.peek().is_some_and(|c| c != '\n') { self.position += 1; },
+                '\n' | '\r' if !including_newlines => return,
+                c if c.is_whitespace() => self.position += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.characters.get(self.position).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.characters.get(self.position + offset).copied()
+    }
+
+    fn consume(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn error(&self, reason: &str) -> String {
+        let line = self.characters[..self.position.min(self.characters.len())].iter().filter(|c| **c == '\n').count() + 1;
+        format!("line {}: {}", line, reason)
+    }
+}